@@ -0,0 +1,628 @@
+/*!
+  Placement of all the printed parts and bought components in the finished
+  tricopter.
+
+  The coordinate system is the one used by `TricopterBody`. The origin is
+  at the center of the body shape on the bottom of the bottom plate, the back
+  arm points along the positive x axis and the front of the craft is in the
+  negative x direction.
+*/
+
+use na;
+use scad::*;
+use scad_util::constants::{x_axis, y_axis, z_axis};
+
+use evaluate::{EvalError, Solid};
+use {TricopterBody, ServoMount, NazeBoard, Esc, ARM_ANGLES};
+use {get_camera_cushion, get_camera_water_seal};
+
+/**
+  Filament that printed parts are made of
+*/
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Material
+{
+    Pla,
+    Petg,
+    Abs,
+    Tpu,
+}
+
+impl Material
+{
+    /**
+      Density of the solid material in g/mm^3
+    */
+    pub fn density(&self) -> f32
+    {
+        match *self
+        {
+            Material::Pla => 1.24e-3,
+            Material::Petg => 1.27e-3,
+            Material::Abs => 1.04e-3,
+            Material::Tpu => 1.21e-3,
+        }
+    }
+}
+
+qstruct!(Motor()
+{
+    diameter: f32 = 28.,
+    height: f32 = 20.,
+    mass: f32 = 28.,
+});
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PartKind
+{
+    Printed(Material),
+    //A bought component with a known mass in grams. The geometry is only
+    //used to spread the mass out
+    Component(f32),
+}
+
+/**
+  A part of the assembly. The object is already moved to where it sits
+  in the finished craft
+*/
+#[derive(Clone)]
+pub struct AssemblyPart
+{
+    pub name: String,
+    pub kind: PartKind,
+    pub object: ScadObject,
+}
+
+impl AssemblyPart
+{
+    fn printed(name: &str, material: Material, object: ScadObject) -> AssemblyPart
+    {
+        AssemblyPart{name: name.to_string(), kind: PartKind::Printed(material), object}
+    }
+
+    fn component(name: &str, mass: f32, object: ScadObject) -> AssemblyPart
+    {
+        AssemblyPart{name: name.to_string(), kind: PartKind::Component(mass), object}
+    }
+}
+
+/**
+  A part that gets printed, in the coordinates of its generator
+*/
+#[derive(Clone)]
+pub struct PrintPart
+{
+    pub name: String,
+    pub material: Material,
+    //Number of copies needed for one craft
+    pub count: u32,
+    pub object: ScadObject,
+    //Rotation in degrees around the x, y and z axes, applied in that order,
+    //that puts the part in the orientation it is printed in
+    pub rotation: na::Vector3<f32>,
+}
+
+impl PrintPart
+{
+    fn new(name: &str, material: Material, object: ScadObject) -> PrintPart
+    {
+        PrintPart{
+            name: name.to_string(),
+            material,
+            count: 1,
+            object,
+            rotation: vec3(0., 0., 0.),
+        }
+    }
+
+    /**
+      Returns the part rotated to how it sits on the print bed
+    */
+    pub fn oriented(&self) -> ScadObject
+    {
+        let rotated_x = scad!(Rotate(self.rotation.x, x_axis()); self.object.clone());
+        let rotated_y = scad!(Rotate(self.rotation.y, y_axis()); rotated_x);
+        scad!(Rotate(self.rotation.z, z_axis()); rotated_y)
+    }
+
+    /**
+      Returns the part in print orientation, moved so that it rests on the
+      print bed at z = 0 with the corner of its bounding box at the origin
+    */
+    pub fn on_bed(&self) -> Result<ScadObject, EvalError>
+    {
+        let oriented = self.oriented();
+        let corner = Solid::from_object(&oriented)?.bounds().min;
+        Ok(scad!(Translate(-corner); oriented))
+    }
+}
+
+qstruct!(Assembly()
+{
+    body: TricopterBody = TricopterBody::new(),
+    servo_mount: ServoMount = ServoMount::new(),
+    flight_controller: NazeBoard = NazeBoard::new(),
+    esc: Esc = Esc::new(),
+    motor: Motor = Motor::new(),
+
+    //Distance from the center of the body to the front motors
+    arm_length: f32 = 150.,
+    //Distance from the center of the body to the tail motor
+    back_arm_length: f32 = 150.,
+    //Distance from the center of the body to the inner end of the arms
+    arm_start: f32 = 40.,
+    arm_mass_per_length: f32 = 0.06,
+
+    //Heights are measured from the top of the top plate
+    flight_controller_height: f32 = 5.,
+    esc_stack_x: f32 = -31.,
+    esc_stack_height: f32 = 4.,
+    esc_stack_spacing: f32 = 6.,
+    vtx_x: f32 = 25.,
+    vtx_height: f32 = 12.,
+
+    prop_diameter: f32 = 127.,
+    //Distance from the top of the motor to the middle of the propeller
+    prop_offset: f32 = 6.,
+    prop_thickness: f32 = 8.,
+    //Smallest allowed gap between the tips of the propellers and anything else
+    prop_tip_clearance: f32 = 5.,
+
+    //Where the center of gravity should be relative to the centroid of the
+    //motors along the x axis. Negative values are in front of it
+    center_of_mass_offset: f32 = -5.,
+
+    material: Material = Material::Petg,
+    pad_material: Material = Material::Tpu,
+    //Fraction of the printed volume that is actually filled with plastic
+    fill_factor: f32 = 0.6,
+    nozzle_width: f32 = 0.4,
+    //Number of extrusions that the thinnest wall has to be made of
+    min_wall_lines: f32 = 2.,
+    //Steepest overhang in degrees from vertical that prints without support
+    max_overhang_angle: f32 = 45.,
+    max_bridge_length: f32 = 10.,
+    //Size of the print bed. Parts may be turned around the z axis to fit
+    bed_width: f32 = 220.,
+    bed_depth: f32 = 220.,
+    bed_height: f32 = 250.,
+});
+
+impl Assembly
+{
+    /**
+      Returns the z coordinate of the top of the top plate
+    */
+    pub fn top_plate_top(&self) -> f32
+    {
+        self.body.get_bottom_total_height() + self.body.height
+    }
+
+    /**
+      Returns the z coordinate that the canopy is placed at. The edge of the
+      canopy reaches down from there to the top plate
+    */
+    pub fn canopy_height(&self) -> f32
+    {
+        self.top_plate_top() + self.body.edge_height
+    }
+
+    /**
+      Returns the thinnest wall that can be printed reliably
+    */
+    pub fn min_wall_thickness(&self) -> f32
+    {
+        self.nozzle_width * self.min_wall_lines
+    }
+
+    /**
+      Returns the z coordinate of the center of the arms
+    */
+    pub fn arm_center_height(&self) -> f32
+    {
+        self.body.height + self.body.arm_width / 2.
+    }
+
+    /**
+      Returns the angles of the arms around the z axis. The first arm is
+      the tail arm
+    */
+    pub fn arm_angles(&self) -> Vec<f32>
+    {
+        ARM_ANGLES.to_vec()
+    }
+
+    /**
+      Returns the x,y positions of the motors, tail motor first
+    */
+    pub fn motor_positions(&self) -> Vec<na::Vector2<f32>>
+    {
+        self.arm_angles().iter()
+            .map(|angle| {
+                let length = if *angle == 0. { self.back_arm_length } else { self.arm_length };
+                let radians = angle.to_radians();
+                vec2(radians.cos() * length, radians.sin() * length)
+            })
+            .collect()
+    }
+
+    /**
+      Returns the point that the thrust of the motors is centered around
+      when they all produce the same thrust
+    */
+    pub fn thrust_centroid(&self) -> na::Vector2<f32>
+    {
+        let positions = self.motor_positions();
+        let sum = positions.iter().fold(vec2(0., 0.), |acc, p| acc + p);
+        sum / positions.len() as f32
+    }
+
+    /**
+      Returns the z coordinate of the bottom of the servo mount
+    */
+    fn servo_mount_height(&self) -> f32
+    {
+        self.arm_center_height() - self.servo_mount.boom_height / 2.
+    }
+
+    /**
+      Returns the z coordinate of the bottom of a motor. The front motors
+      sit on their arms and the tail motor sits on top of the servo
+    */
+    pub fn motor_height(&self, motor: usize) -> f32
+    {
+        if motor == 0
+        {
+            self.servo_mount_height()
+                + self.servo_mount.boom_height
+                + self.servo_mount.servo_height
+        }
+        else
+        {
+            self.arm_center_height() + self.body.arm_width / 2.
+        }
+    }
+
+    /**
+      Returns the z coordinate of the middle of the propeller on a motor
+    */
+    pub fn prop_center_height(&self, motor: usize) -> f32
+    {
+        self.motor_height(motor) + self.motor.height + self.prop_offset
+    }
+
+    /**
+      Returns the disk swept by the propeller on one of the motors
+    */
+    pub fn get_prop_disk(&self, motor: usize, diameter: f32) -> ScadObject
+    {
+        let position = self.motor_positions()[motor];
+        let z = self.prop_center_height(motor) - self.prop_thickness / 2.;
+        scad!(Translate(vec3(position.x, position.y, z)); {
+            scad!(Cylinder(self.prop_thickness, Diameter(diameter)))
+        })
+    }
+
+    /**
+      Returns the position of the middle of the front of the camera lens
+    */
+    pub fn camera_lens_position(&self) -> na::Vector3<f32>
+    {
+        //The lens sits in the lens hole of the canopy
+        self.body.camera_lens_front() + vec3(0., 0., self.canopy_height())
+    }
+
+    /**
+      Returns the unit vector that the camera looks along
+    */
+    pub fn camera_direction(&self) -> na::Vector3<f32>
+    {
+        let uptilt = self.body.camera_uptilt.to_radians();
+        vec3(-uptilt.cos(), 0., uptilt.sin())
+    }
+
+    /**
+      Moves an object in the coordinates of the camera model to where the
+      camera sits behind the lens hole in the canopy
+    */
+    fn place_camera(&self, object: ScadObject) -> ScadObject
+    {
+        let camera = &self.body.camera;
+        let lens_front = self.camera_lens_position();
+        let lens_length = camera.lens_length + camera.thickness;
+
+        //The camera is tilted around the front of the lens
+        scad!(Translate(lens_front); {
+            scad!(Rotate(self.body.camera_uptilt, y_axis()); {
+                scad!(Translate(vec3(lens_length, 0., 0.)); {
+                    scad!(Rotate(-90., y_axis()); object)
+                })
+            })
+        })
+    }
+
+    /**
+      Returns the part of the space that the camera sees, up to `length` mm
+      in front of the lens
+    */
+    pub fn get_camera_view(&self, length: f32) -> ScadObject
+    {
+        self.place_camera(self.body.camera.get_view_cone(length))
+    }
+
+    /**
+      Returns the side plates that can be used instead of the canopy. They
+      are not part of `get_parts` since only one of them is mounted at a time
+    */
+    pub fn get_side_plates(&self) -> Vec<AssemblyPart>
+    {
+        let body = &self.body;
+        let y = body.side_plate_arc_width / 2. - body.side_plate_thickness;
+
+        //The front of the plates is along their positive x axis
+        let plate = scad!(Translate(vec3(0., y, self.top_plate_top())); {
+            scad!(Mirror(x_axis()); {
+                scad!(Rotate(90., x_axis()); body.side_plate_shape())
+            })
+        });
+
+        vec!(
+            AssemblyPart::printed("side_plate_left", self.material, plate.clone()),
+            AssemblyPart::printed("side_plate_right", self.material, {
+                scad!(Mirror(y_axis()); plate)
+            }),
+        )
+    }
+
+    fn get_arms(&self) -> Vec<AssemblyPart>
+    {
+        let width = self.body.arm_width;
+        let z = self.arm_center_height();
+
+        self.arm_angles().iter().enumerate()
+            .map(|(i, angle)| {
+                let end = if *angle == 0. { self.back_arm_length } else { self.arm_length };
+                let length = end - self.arm_start;
+
+                let shape = centered_cube(vec3(length, width, width), (false, true, true));
+                let placed = scad!(Rotate(*angle, z_axis()); {
+                    scad!(Translate(vec3(self.arm_start, 0., z)); shape)
+                });
+
+                let mass = length * self.arm_mass_per_length;
+                AssemblyPart::component(&format!("arm_{}", i), mass, placed)
+            })
+            .collect()
+    }
+
+    fn get_motors(&self) -> Vec<AssemblyPart>
+    {
+        self.motor_positions().iter().enumerate()
+            .map(|(i, position)| {
+                let shape = scad!(Cylinder(self.motor.height, Diameter(self.motor.diameter)));
+                let z = self.motor_height(i);
+                let placed = scad!(Translate(vec3(position.x, position.y, z)); shape);
+                AssemblyPart::component(&format!("motor_{}", i), self.motor.mass, placed)
+            })
+            .collect()
+    }
+
+    /**
+      The servo mount sits at the end of the tail arm with the servo in it.
+      The mount is modeled with the boom along its z axis and up along y
+    */
+    fn get_servo_parts(&self) -> Vec<AssemblyPart>
+    {
+        let servo_mount = &self.servo_mount;
+        let x = self.back_arm_length - servo_mount.servo_depth;
+
+        let place = |object: ScadObject| {
+            let rotated = scad!(Rotate(90., z_axis()); {
+                scad!(Rotate(90., x_axis()); object)
+            });
+            scad!(Translate(vec3(x, 0., self.servo_mount_height())); rotated)
+        };
+
+        let servo = {
+            let size = vec3(
+                servo_mount.servo_width,
+                servo_mount.servo_height,
+                servo_mount.servo_depth
+            );
+            let shape = centered_cube(size, (true, false, false));
+            scad!(Translate(vec3(0., servo_mount.boom_height, 0.)); shape)
+        };
+
+        vec!(
+            AssemblyPart::printed("servo_mount", self.material, place(servo_mount.full())),
+            AssemblyPart::component("servo", servo_mount.servo_mass, place(servo)),
+        )
+    }
+
+    fn get_electronics(&self) -> Vec<AssemblyPart>
+    {
+        let body = &self.body;
+        let top = self.top_plate_top();
+        let mut result = vec!();
+
+        let flight_controller = scad!(
+            Translate(vec3(0., 0., top + self.flight_controller_height));
+            self.flight_controller.get_board()
+        );
+        result.push(AssemblyPart::component(
+            "flight_controller",
+            self.flight_controller.mass,
+            flight_controller
+        ));
+
+        for i in 0..3
+        {
+            let z = top + self.esc_stack_height + i as f32 * self.esc_stack_spacing;
+            let esc = scad!(Translate(vec3(self.esc_stack_x, 0., z)); {
+                scad!(Rotate(90., z_axis()); self.esc.get_pcb((true, true, false)))
+            });
+            result.push(AssemblyPart::component(&format!("esc_{}", i), self.esc.mass, esc));
+        }
+
+        let camera = self.place_camera(body.camera.get_model());
+        result.push(AssemblyPart::component("camera", body.camera.mass, camera));
+
+        let vtx = {
+            let size = vec3(body.vtx.width, body.vtx.width, body.vtx.thickness);
+            let shape = centered_cube(size, (true, true, false));
+            scad!(Translate(vec3(self.vtx_x, 0., top + self.vtx_height)); shape)
+        };
+        result.push(AssemblyPart::component("vtx", body.vtx.mass, vtx));
+
+        let battery = {
+            let battery = &body.battery;
+            let size = vec3(battery.length, battery.width, battery.height);
+            let shape = centered_cube(size, (true, true, false));
+            let pad_thickness = body.battery_pad_thickness;
+            scad!(Translate(vec3(body.battery_position, 0., -battery.height - pad_thickness)); shape)
+        };
+        result.push(AssemblyPart::component("battery", body.battery.mass, battery));
+
+        result
+    }
+
+    pub fn get_printed_parts(&self) -> Vec<AssemblyPart>
+    {
+        let body = &self.body;
+        let top = self.top_plate_top();
+
+        let body_top = scad!(Translate(vec3(0., 0., body.get_bottom_total_height()));
+                             body.get_body_top());
+        let canopy = scad!(Translate(vec3(0., 0., self.canopy_height())); body.get_canopy());
+        let vtx_mount = scad!(Translate(vec3(self.vtx_x, 0., top + self.vtx_height)); {
+            scad!(Mirror(z_axis()); body.vtx.get_mount())
+        });
+        let battery_pad = scad!(Mirror(z_axis()); body.get_battery_pad());
+
+        vec!(
+            AssemblyPart::printed("body_bottom", self.material, body.get_body_bottom()),
+            AssemblyPart::printed("body_top", self.material, body_top),
+            AssemblyPart::printed("canopy", self.material, canopy),
+            AssemblyPart::printed("vtx_mount", self.material, vtx_mount),
+            AssemblyPart::printed("battery_pad", self.pad_material, battery_pad),
+        )
+    }
+
+    /**
+      Returns every part that can be printed for the craft, including the
+      side plates and the parts that hold them which replace the canopy
+    */
+    pub fn get_print_parts(&self) -> Vec<PrintPart>
+    {
+        let body = &self.body;
+        let material = self.material;
+
+        //The side plates are printed with the nut bosses facing up
+        let mut side_plate = PrintPart::new("side_plate", material, body.side_plate_shape());
+        side_plate.count = 2;
+        side_plate.rotation = vec3(180., 0., 0.);
+
+        //The top plate has fewer overhangs when it is printed upside down
+        let mut body_top = PrintPart::new("body_top", material, body.get_body_top());
+        body_top.rotation = vec3(180., 0., 0.);
+
+        //The canopy stands on its front face. Printed upright the inside of
+        //the roof would have to be supported all the way across
+        let mut canopy = PrintPart::new("canopy", material, body.get_canopy());
+        canopy.rotation = vec3(0., -90., 0.);
+
+        //The mount also has less to support when it is printed upside down
+        let mut side_plate_mount =
+            PrintPart::new("side_plate_mount", material, body.get_side_plate_mount());
+        side_plate_mount.rotation = vec3(180., 0., 0.);
+
+        //The bracket stands on the flat end across from the nut so that the
+        //arms on both sides of the slot are printed as walls
+        let mut side_plate_front_bracket =
+            PrintPart::new("side_plate_front_bracket", material, body.side_plate_front_bracket());
+        side_plate_front_bracket.rotation = vec3(-90., 0., 0.);
+
+        vec!(
+            PrintPart::new("body_bottom", material, body.get_body_bottom()),
+            body_top,
+            canopy,
+            PrintPart::new("vtx_mount", material, body.vtx.get_mount()),
+            PrintPart::new("battery_pad", self.pad_material, body.get_battery_pad()),
+            PrintPart::new("servo_mount", material, self.servo_mount.full()),
+            PrintPart::new("servo_flex_holder", self.pad_material, self.servo_mount.flex_holder()),
+            PrintPart::new("camera_cushion", self.pad_material, get_camera_cushion()),
+            PrintPart::new(
+                "camera_water_seal",
+                self.pad_material,
+                get_camera_water_seal(&body.camera, body)
+            ),
+            side_plate,
+            side_plate_mount,
+            side_plate_front_bracket,
+        )
+    }
+
+    /**
+      Returns all the parts of the craft in their assembled positions
+    */
+    pub fn get_parts(&self) -> Vec<AssemblyPart>
+    {
+        let mut result = self.get_printed_parts();
+        result.append(&mut self.get_servo_parts());
+        result.append(&mut self.get_electronics());
+        result.append(&mut self.get_arms());
+        result.append(&mut self.get_motors());
+        result
+    }
+
+    /**
+      Returns the whole assembly as a single object with components coloured
+      so they can be told apart from the printed parts
+    */
+    pub fn get_model(&self) -> ScadObject
+    {
+        let mut result = scad!(Union);
+        for part in self.get_parts()
+        {
+            let object = match part.kind
+            {
+                PartKind::Printed(_) => part.object,
+                PartKind::Component(_) => ::scad_util::add_named_color("dimgray", part.object),
+            };
+            result.add_child(object);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use mass::MassModel;
+
+    #[test]
+    fn motors_and_arms_are_centered_on_the_arms()
+    {
+        let assembly = Assembly::new();
+        let model = MassModel::new(&assembly, 2.).unwrap();
+        let part = |name: String| model.parts.iter().find(|part| part.name == name).unwrap();
+
+        for (i, position) in assembly.motor_positions().iter().enumerate()
+        {
+            let motor = part(format!("motor_{}", i));
+            let z = assembly.motor_height(i) + assembly.motor.height / 2.;
+            assert_eq!(motor.mass, assembly.motor.mass);
+            assert!((motor.center_of_mass - vec3(position.x, position.y, z)).norm() < 0.1);
+
+            //The arms are boxes from the body out to the motors
+            let arm = part(format!("arm_{}", i));
+            let length = position.norm();
+            let middle = position * (assembly.arm_start + length) / (2. * length);
+            let center = vec3(middle.x, middle.y, assembly.arm_center_height());
+            assert_eq!(arm.mass, (length - assembly.arm_start) * assembly.arm_mass_per_length);
+            assert!((arm.center_of_mass - center).norm() < 0.1, "{}", arm.center_of_mass);
+        }
+    }
+}
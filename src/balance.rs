@@ -0,0 +1,256 @@
+/*!
+  Finding the battery position that puts the center of gravity where it
+  should be.
+*/
+
+use assembly::Assembly;
+use evaluate::{EvalError, Shape, Vec2};
+use mass::MassModel;
+use TricopterBody;
+
+use std::fmt;
+
+//Maximum distance in mm between the achieved and the target center of gravity
+const TOLERANCE: f32 = 0.1;
+const MAX_ITERATIONS: usize = 20;
+//Step in mm used when searching for the range of possible battery positions
+const RANGE_STEP: f32 = 0.5;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct BatteryPlacement
+{
+    pub battery_position: f32,
+    pub target_x: f32,
+    //X coordinate of the center of gravity with the battery in place
+    pub center_of_mass_x: f32,
+    //True if the target could not be reached because the battery straps
+    //would not fit on the body
+    pub clamped: bool,
+    //False if the search stopped before the center of gravity was within
+    //the tolerance of the target
+    pub converged: bool,
+}
+
+impl fmt::Display for BatteryPlacement
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        writeln!(f, "Battery position: {:.1} mm", self.battery_position)?;
+        writeln!(f, "Target center of gravity x: {:.1} mm", self.target_x)?;
+        writeln!(f, "Resulting center of gravity x: {:.1} mm", self.center_of_mass_x)?;
+        if self.clamped
+        {
+            writeln!(
+                f,
+                "Warning: the battery can not be moved far enough to reach the target"
+            )?;
+        }
+        else if !self.converged
+        {
+            writeln!(
+                f,
+                "Warning: the search did not converge, the center of gravity is {:.1} mm from the target",
+                (self.center_of_mass_x - self.target_x).abs()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/**
+  Returns true if all the battery strap slots are inside the outline
+*/
+fn strap_slots_fit(body: &TricopterBody, outline: &Shape) -> bool
+{
+    let half_x = (body.battery_strap_width + body.battery_strap_padding) / 2.;
+    let slots = body.battery_strap_slot_pattern();
+    let half_y = slots.diameter / 2.;
+
+    slots.positions_2d().iter().all(|slot| {
+        [slot.x - half_x, slot.x + half_x].iter().all(|x| {
+            [slot.y - half_y, slot.y + half_y].iter()
+                .all(|y| outline.contains(&Vec2::new(*x, *y)))
+        })
+    })
+}
+
+/**
+  Returns the range of battery positions where the battery strap slots are
+  on the part of the body that is covered by the canopy. Returns None if
+  they don't fit anywhere. The battery position of the assembly is not changed
+*/
+pub fn battery_position_range(assembly: &mut Assembly)
+    -> Result<Option<(f32, f32)>, EvalError>
+{
+    let original_position = assembly.body.battery_position;
+    let outline = Shape::from_object(&assembly.body.get_mid_section_outline())?;
+
+    let start = -assembly.body.front_section_length;
+    let steps = ((assembly.body.radius - start) / RANGE_STEP) as usize;
+
+    let mut range: Option<(f32, f32)> = None;
+    for i in 0..steps + 1
+    {
+        let position = start + i as f32 * RANGE_STEP;
+        assembly.body.battery_position = position;
+
+        if strap_slots_fit(&assembly.body, &outline)
+        {
+            range = Some(match range
+            {
+                Some((min, _)) => (min, position),
+                None => (position, position)
+            });
+        }
+    }
+
+    assembly.body.battery_position = original_position;
+    Ok(range)
+}
+
+/**
+  Returns the x coordinate that the center of gravity should be at. This
+  is the centroid of the thrust of the motors moved by the configured offset
+*/
+pub fn target_center_of_mass_x(assembly: &Assembly) -> f32
+{
+    assembly.thrust_centroid().x + assembly.center_of_mass_offset
+}
+
+fn center_of_mass_x(assembly: &mut Assembly, battery_position: f32, resolution: f32)
+    -> Result<f32, EvalError>
+{
+    assembly.body.battery_position = battery_position;
+    Ok(MassModel::new(assembly, resolution)?.center_of_mass().x)
+}
+
+/**
+  Finds the battery position that puts the center of gravity at the target.
+
+  The center of gravity moves almost linearly with the battery so the secant
+  method converges in a few steps. The battery position of the assembly is
+  left at the solution, which also moves the battery straps there. If the
+  search fails the battery position is not changed.
+*/
+pub fn place_battery(assembly: &mut Assembly, resolution: f32)
+    -> Result<BatteryPlacement, EvalError>
+{
+    let original_position = assembly.body.battery_position;
+    let result = search_battery_position(assembly, resolution);
+    if result.is_err()
+    {
+        assembly.body.battery_position = original_position;
+    }
+    result
+}
+
+fn search_battery_position(assembly: &mut Assembly, resolution: f32)
+    -> Result<BatteryPlacement, EvalError>
+{
+    let target_x = target_center_of_mass_x(assembly);
+    let (min, max) = battery_position_range(assembly)?
+        .ok_or_else(|| EvalError::new("The battery straps do not fit on the body"))?;
+    let clamp = |x: f32| x.max(min).min(max);
+
+    let mut previous_position = clamp(assembly.body.battery_position);
+    let mut previous_error = center_of_mass_x(assembly, previous_position, resolution)? - target_x;
+
+    let mut position = clamp(previous_position + 10.);
+    if position == previous_position
+    {
+        position = clamp(previous_position - 10.);
+    }
+    let mut error = center_of_mass_x(assembly, position, resolution)? - target_x;
+
+    for _ in 0..MAX_ITERATIONS
+    {
+        if error.abs() < TOLERANCE || error == previous_error
+        {
+            break;
+        }
+
+        let next = clamp(position - error * (position - previous_position) / (error - previous_error));
+        previous_position = position;
+        previous_error = error;
+        position = next;
+        error = center_of_mass_x(assembly, position, resolution)? - target_x;
+
+        //Stuck against one of the ends of the allowed range
+        if position == previous_position
+        {
+            break;
+        }
+    }
+
+    Ok(BatteryPlacement{
+        battery_position: position,
+        target_x,
+        center_of_mass_x: error + target_x,
+        clamped: error.abs() >= TOLERANCE && (position == min || position == max),
+        converged: error.abs() < TOLERANCE,
+    })
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const RESOLUTION: f32 = 2.;
+
+    #[test]
+    fn battery_range_contains_the_default_position()
+    {
+        let mut assembly = Assembly::new();
+        let original = assembly.body.battery_position;
+        let (min, max) = battery_position_range(&mut assembly).unwrap().unwrap();
+
+        assert!(min < max);
+        assert!(min <= original && original <= max, "{} not in {}..{}", original, min, max);
+        assert_eq!(assembly.body.battery_position, original);
+    }
+
+    #[test]
+    fn solved_battery_puts_the_center_of_gravity_on_the_thrust_centroid()
+    {
+        let mut assembly = Assembly::new();
+        assembly.center_of_mass_offset = 0.;
+
+        let placement = place_battery(&mut assembly, RESOLUTION).unwrap();
+        assert!(!placement.clamped, "{}", placement);
+        assert!(placement.converged, "{}", placement);
+        assert_eq!(placement.battery_position, assembly.body.battery_position);
+
+        let center_of_mass = MassModel::new(&assembly, RESOLUTION).unwrap().center_of_mass();
+        let target = assembly.thrust_centroid().x;
+        assert!((center_of_mass.x - target).abs() < TOLERANCE, "{} != {}", center_of_mass.x, target);
+    }
+
+    #[test]
+    fn unconverged_placements_are_reported()
+    {
+        let placement = BatteryPlacement{
+            battery_position: 10.,
+            target_x: 0.,
+            center_of_mass_x: 2.,
+            clamped: false,
+            converged: false,
+        };
+        assert!(format!("{}", placement).contains("did not converge"));
+
+        let placement = BatteryPlacement{clamped: true, .. placement};
+        assert!(!format!("{}", placement).contains("did not converge"));
+    }
+
+    #[test]
+    fn batteries_without_room_for_straps_are_errors()
+    {
+        let mut assembly = Assembly::new();
+        assembly.body.battery.width = 500.;
+        let original = assembly.body.battery_position;
+
+        assert_eq!(battery_position_range(&mut assembly).unwrap(), None);
+        assert!(place_battery(&mut assembly, RESOLUTION).is_err());
+        assert_eq!(assembly.body.battery_position, original);
+    }
+}
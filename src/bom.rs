@@ -0,0 +1,185 @@
+/*!
+  Bill of materials for one craft.
+
+  The printed parts are the parts that the assembly is printed as. The
+  hardware comes from the threaded joints of the body and the servo mount,
+  since how the thread of a joint is made decides if it needs a nut or an
+  insert.
+*/
+
+use assembly::Assembly;
+use hole_pattern::{Fastener, Threading};
+
+use std::fmt;
+
+/**
+  Fasteners that screw into the same kind of thread
+*/
+#[derive(Clone, PartialEq, Debug)]
+pub struct ThreadedJoint
+{
+    pub name: String,
+    pub fastener: Fastener,
+    pub threading: Threading,
+    //Number of fasteners in one craft
+    pub count: usize,
+}
+
+impl ThreadedJoint
+{
+    /**
+      Returns the names of the things that each fastener of the joint needs
+    */
+    fn hardware(&self) -> Vec<String>
+    {
+        let size = format!("M{}", self.fastener.thread_diameter);
+        let mut result = vec!(format!("{} screw", size));
+        match self.threading
+        {
+            Threading::ThroughNut | Threading::NutTrap => result.push(format!("{} nut", size)),
+            Threading::HeatSetInsert => result.push(format!("{} heat-set insert", size)),
+            Threading::SelfTapping => {}
+        }
+        result
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct BomItem
+{
+    pub name: String,
+    pub count: usize,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Bom
+{
+    pub printed: Vec<BomItem>,
+    pub hardware: Vec<BomItem>,
+}
+
+/**
+  Adds to the count of an item, or adds the item if it is not in the list
+  yet. Items stay in the order they were first added
+*/
+fn add_item(items: &mut Vec<BomItem>, name: &str, count: usize)
+{
+    match items.iter_mut().find(|item| item.name == name)
+    {
+        Some(item) => item.count += count,
+        None => items.push(BomItem{name: name.to_string(), count}),
+    }
+}
+
+impl Bom
+{
+    pub fn of(assembly: &Assembly) -> Bom
+    {
+        let printed = assembly.get_print_parts().into_iter()
+            .map(|part| BomItem{name: part.name, count: part.count as usize})
+            .collect();
+
+        let mut joints = assembly.body.threaded_joints();
+        joints.push(assembly.servo_mount.threaded_joint());
+
+        let mut hardware = vec!();
+        for joint in joints
+        {
+            for name in joint.hardware()
+            {
+                add_item(&mut hardware, &name, joint.count);
+            }
+        }
+
+        Bom{printed, hardware}
+    }
+
+    pub fn count(&self, name: &str) -> usize
+    {
+        self.printed.iter().chain(&self.hardware)
+            .filter(|item| item.name == name)
+            .map(|item| item.count)
+            .sum()
+    }
+}
+
+impl fmt::Display for Bom
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        for &(title, ref items) in &[("Printed parts", &self.printed), ("Hardware", &self.hardware)]
+        {
+            writeln!(f, "{}:", title)?;
+            for item in items.iter()
+            {
+                writeln!(f, "    {:>3} x {}", item.count, item.name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use Vtx;
+
+    #[test]
+    fn threading_decides_the_hardware()
+    {
+        let mut assembly = Assembly::new();
+        let screws = Bom::of(&assembly).count("M3 screw");
+        assert!(screws > 0);
+        assert_eq!(Bom::of(&assembly).count("M3 nut"), screws);
+        assert_eq!(Bom::of(&assembly).count("M3 heat-set insert"), 0);
+
+        assembly.body.plate_screw_threading = Threading::HeatSetInsert;
+        assembly.body.side_plate_threading = Threading::SelfTapping;
+        let bom = Bom::of(&assembly);
+        assert_eq!(bom.count("M3 screw"), screws);
+        assert_eq!(bom.count("M3 heat-set insert"), 4);
+        assert_eq!(bom.count("M3 nut"), screws - 4 - 2);
+    }
+
+    #[test]
+    fn stack_vtx_boards_take_m2_screws()
+    {
+        let mut assembly = Assembly::new();
+        //The servo comes with two M2 screws
+        assert_eq!(Bom::of(&assembly).count("M2 screw"), 2);
+        assert_eq!(Bom::of(&assembly).count("M2 nut"), 0);
+
+        assembly.body.vtx = Vtx::stack_20x20();
+        let bom = Bom::of(&assembly);
+        assert_eq!(bom.count("M2 screw"), 2 + 4);
+        assert_eq!(bom.count("M2 nut"), 4);
+
+        assembly.body.vtx.threading = Threading::SelfTapping;
+        assert_eq!(Bom::of(&assembly).count("M2 nut"), 0);
+    }
+
+    #[test]
+    fn every_body_joint_has_its_own_threading()
+    {
+        let mut assembly = Assembly::new();
+        let screws = Bom::of(&assembly).count("M3 screw");
+
+        assembly.body.arm_mount_threading = Threading::HeatSetInsert;
+        assembly.body.arm_stopper_threading = Threading::HeatSetInsert;
+        assembly.body.side_plate_back_threading = Threading::SelfTapping;
+        let bom = Bom::of(&assembly);
+        assert_eq!(bom.count("M3 screw"), screws);
+        assert_eq!(bom.count("M3 heat-set insert"), 4);
+        assert_eq!(bom.count("M3 nut"), screws - 4 - 4);
+    }
+
+    #[test]
+    fn printed_parts_are_counted()
+    {
+        let bom = Bom::of(&Assembly::new());
+        assert_eq!(bom.count("side_plate"), 2);
+        assert_eq!(bom.count("body_bottom"), 1);
+    }
+}
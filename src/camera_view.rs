@@ -0,0 +1,211 @@
+/*!
+  Checks that nothing on the craft is visible in the camera image.
+
+  The view of the camera is modeled as a cone starting at the lens and
+  opening up to the field of view of the camera. Parts that reach into the
+  cone show up in the image.
+*/
+
+use scad::*;
+use scad_util::add_named_color;
+
+use assembly::Assembly;
+use evaluate::{EvalError, Solid, Vec3};
+
+use std::fmt;
+
+//Size in mm of the cells used when looking for obstructions
+const RESOLUTION: f32 = 1.;
+//How far in front of the camera the view is checked, in mm
+pub const VIEW_DISTANCE: f32 = 300.;
+
+/**
+  A part that is visible to the camera
+*/
+#[derive(Clone, PartialEq, Debug)]
+pub struct ViewObstruction
+{
+    pub name: String,
+    //Volume of the part that is inside the view cone in mm^3
+    pub volume: f32,
+    //Smallest angle in degrees between the view direction and the part, as
+    //seen from the middle of the lens
+    pub angle: f32,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct CameraView
+{
+    pub field_of_view: f32,
+    pub uptilt: f32,
+    pub obstructions: Vec<ViewObstruction>,
+}
+
+/**
+  Returns the parts that may get in the way of the camera
+*/
+fn get_obstacles(assembly: &Assembly) -> Vec<(String, ScadObject)>
+{
+    let body = &assembly.body;
+    let top = assembly.top_plate_top();
+    let canopy_height = assembly.canopy_height();
+
+    //The lens pokes through the hole in the canopy so only the outside of
+    //the canopy around the hole can block the view
+    let canopy = scad!(Translate(vec3(0., 0., canopy_height)); {
+        scad!(Difference; {
+            body.get_canopy_outside(1.),
+            body.get_camera_lens_hole()
+        })
+    });
+
+    let front_section = scad!(LinearExtrude(LinExtrudeParams{
+        height: top,
+        .. Default::default()
+    }); body.get_front_section());
+
+    let mut result = vec!(
+        ("canopy".to_string(), canopy),
+        ("front_section".to_string(), front_section),
+    );
+
+    for i in 0..assembly.motor_positions().len()
+    {
+        result.push((format!("prop_{}", i), assembly.get_prop_disk(i, assembly.prop_diameter)));
+    }
+    result
+}
+
+impl CameraView
+{
+    pub fn new(assembly: &Assembly) -> Result<CameraView, EvalError>
+    {
+        let cone = Solid::from_object(&assembly.get_camera_view(VIEW_DISTANCE))?;
+        let lens = assembly.camera_lens_position();
+        let direction = assembly.camera_direction();
+        let cell_volume = RESOLUTION.powi(3);
+
+        let mut obstructions = vec!();
+        for (name, object) in get_obstacles(assembly)
+        {
+            let solid = Solid::from_object(&object)?;
+            let points = solid.sample_points_in(&cone.bounds(), RESOLUTION);
+
+            let inside = points.iter().filter(|point| cone.contains(point)).count();
+            if inside == 0
+            {
+                continue;
+            }
+
+            let angle = points.iter()
+                .map(|point| {
+                    let offset: Vec3 = point - lens;
+                    (offset.dot(&direction) / offset.norm()).clamp(-1., 1.).acos().to_degrees()
+                })
+                .fold(180., f32::min);
+
+            obstructions.push(ViewObstruction{
+                name,
+                volume: inside as f32 * cell_volume,
+                angle,
+            });
+        }
+
+        Ok(CameraView{
+            field_of_view: assembly.body.camera.field_of_view,
+            uptilt: assembly.body.camera_uptilt,
+            obstructions,
+        })
+    }
+
+    pub fn is_clear(&self) -> bool
+    {
+        self.obstructions.is_empty()
+    }
+}
+
+impl fmt::Display for CameraView
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        writeln!(f, "Field of view: {:.0} degrees", self.field_of_view)?;
+        writeln!(f, "Uptilt: {:.0} degrees", self.uptilt)?;
+
+        if self.is_clear()
+        {
+            return writeln!(f, "Nothing is in view of the camera");
+        }
+
+        writeln!(f)?;
+        writeln!(f, "{:<16} {:>14} {:>20}", "part", "volume (mm^3)", "angle from center")?;
+        for obstruction in &self.obstructions
+        {
+            writeln!(
+                f,
+                "{:<16} {:>14.0} {:>20.1}",
+                obstruction.name, obstruction.volume, obstruction.angle
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/**
+  Returns the assembly with the view of the camera drawn on top of it and
+  the parts of the obstacles that are in view highlighted
+*/
+pub fn get_overlay(assembly: &Assembly) -> ScadObject
+{
+    let cone = assembly.get_camera_view(VIEW_DISTANCE);
+
+    let mut highlights = scad!(Union);
+    for (_, object) in get_obstacles(assembly)
+    {
+        highlights.add_child(scad!(Intersection; {
+            object,
+            cone.clone()
+        }));
+    }
+
+    scad!(Union; {
+        assembly.get_model(),
+        add_named_color("gold", cone),
+        add_named_color("red", highlights)
+    })
+}
+
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn narrower_view_is_clear()
+    {
+        let mut assembly = Assembly::new();
+        assembly.body.camera.field_of_view = 90.;
+
+        let view = CameraView::new(&assembly).unwrap();
+        assert!(view.is_clear(), "{}", view);
+    }
+
+    #[test]
+    fn default_view_sees_the_front_propellers()
+    {
+        let view = CameraView::new(&Assembly::new()).unwrap();
+
+        let mut names = view.obstructions.iter()
+            .map(|obstruction| obstruction.name.as_str())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!("prop_1", "prop_2"));
+
+        //Parts in the cone are at most half the field of view from its center
+        for obstruction in &view.obstructions
+        {
+            assert!(obstruction.volume > 0.);
+            assert!(obstruction.angle <= view.field_of_view / 2. + 1., "{:?}", obstruction);
+        }
+    }
+}
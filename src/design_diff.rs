@@ -0,0 +1,451 @@
+/*!
+  Differences between two versions of the design.
+
+  The parameters of two versions are compared, and every changed parameter
+  is applied on its own to the older version to find the printed parts whose
+  code it changes. That tells which parts need to be printed again, and
+  which change to look at when a part no longer fits.
+
+  The geometry of a part can also be compared between two versions. The
+  material that the new version adds and the material that it removes are
+  found by taking the difference of the versions both ways, so the physical
+  effect of a change can be looked at in OpenSCAD.
+*/
+
+use assembly::Assembly;
+use evaluate::{EvalError, Solid};
+use golden;
+use history::{self, HistoryError};
+use parameters::{set_parameter, ParameterError, ParameterSet};
+use scad_tree::{self, Argument, Node, ParseError, Statement, Value};
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum DiffError
+{
+    Io(io::Error),
+    Parameter(ParameterError),
+    History(HistoryError),
+    Parse(ParseError),
+    //A version without a printed part, with the version and the part
+    UnknownPart(String, String),
+}
+
+impl fmt::Display for DiffError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self
+        {
+            DiffError::Io(ref e) => write!(f, "{}", e),
+            DiffError::Parameter(ref e) => write!(f, "{}", e),
+            DiffError::History(ref e) => write!(f, "{}", e),
+            DiffError::Parse(ref e) => write!(f, "{}", e),
+            DiffError::UnknownPart(ref version, ref part) => {
+                write!(f, "Version {} has no part called {}", version, part)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for DiffError
+{
+    fn from(error: io::Error) -> DiffError
+    {
+        DiffError::Io(error)
+    }
+}
+
+impl From<ParameterError> for DiffError
+{
+    fn from(error: ParameterError) -> DiffError
+    {
+        DiffError::Parameter(error)
+    }
+}
+
+impl From<HistoryError> for DiffError
+{
+    fn from(error: HistoryError) -> DiffError
+    {
+        DiffError::History(error)
+    }
+}
+
+impl From<ParseError> for DiffError
+{
+    fn from(error: ParseError) -> DiffError
+    {
+        DiffError::Parse(error)
+    }
+}
+
+/**
+  A parameter with different values in the two versions
+*/
+#[derive(Clone, PartialEq, Debug)]
+pub struct Change
+{
+    pub name: String,
+    //None if the version doesn't have the parameter
+    pub old: Option<String>,
+    pub new: Option<String>,
+    //Names of the printed parts whose code the change alone changes
+    pub affected_parts: Vec<String>,
+}
+
+impl fmt::Display for Change
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        let value = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+        writeln!(f, "{}: {} -> {}", self.name, value(&self.old), value(&self.new))?;
+        if self.affected_parts.is_empty()
+        {
+            writeln!(f, "    no printed parts change")
+        }
+        else
+        {
+            writeln!(f, "    {}", self.affected_parts.join(", "))
+        }
+    }
+}
+
+/**
+  Loads the parameters of a version of the design. The version is either a
+  parameter file, a history entry or `current` for the parameters in the code
+*/
+pub fn load_version(version: &str) -> Result<ParameterSet, DiffError>
+{
+    if version == "current"
+    {
+        return Ok(ParameterSet::of(&Assembly::new()));
+    }
+
+    if Path::new(version).is_file()
+    {
+        return Ok(ParameterSet::parse(&fs::read_to_string(version)?)?);
+    }
+
+    let directory = history::history_directory();
+    let entry = history::find(&directory, version)?;
+    Ok(history::load_parameters(&directory, &entry)?)
+}
+
+/**
+  Returns the code of a printed part in a version of the design. History
+  entries have the code that was generated then, the other versions are
+  generated from their parameters
+*/
+pub fn load_part_code(version: &str, part: &str) -> Result<String, DiffError>
+{
+    let unknown_part = || DiffError::UnknownPart(version.to_string(), part.to_string());
+
+    if version != "current" && !Path::new(version).is_file()
+    {
+        let directory = history::history_directory();
+        let entry = history::find(&directory, version)?;
+        return history::load_part_code(&directory, &entry, part)?.ok_or_else(unknown_part);
+    }
+
+    assembly_with(&load_version(version)?)?.get_print_parts().into_iter()
+        .find(|print_part| print_part.name == part)
+        .map(|print_part| history::part_code(&print_part.object))
+        .ok_or_else(unknown_part)
+}
+
+/**
+  Returns the default assembly with the parameters of a version applied.
+  Parameters that the code doesn't have anymore are left out, so that
+  versions from before a parameter was removed or renamed can still be built
+*/
+fn assembly_with(parameters: &ParameterSet) -> Result<Assembly, ParameterError>
+{
+    let mut result = Assembly::new();
+    for &(ref name, ref value) in &parameters.values
+    {
+        match set_parameter(&mut result, name, value)
+        {
+            Ok(()) | Err(ParameterError::Unknown(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(result)
+}
+
+/**
+  Returns the names and normalised code of the printed parts
+*/
+fn render_parts(assembly: &Assembly) -> io::Result<Vec<(String, String)>>
+{
+    assembly.get_print_parts().iter()
+        .map(|part| Ok((part.name.clone(), golden::render(part)?)))
+        .collect()
+}
+
+/**
+  Returns the parameters that differ between the versions, in the order of
+  the old version followed by parameters that only the new version has
+*/
+pub fn changed_parameters(old: &ParameterSet, new: &ParameterSet)
+    -> Vec<(String, Option<String>, Option<String>)>
+{
+    let mut names = old.values.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>();
+    for (name, _) in &new.values
+    {
+        if !names.contains(name)
+        {
+            names.push(name.clone());
+        }
+    }
+
+    names.into_iter()
+        .filter_map(|name| {
+            let old_value = old.get(&name).map(|value| value.to_string());
+            let new_value = new.get(&name).map(|value| value.to_string());
+            if old_value != new_value
+            {
+                Some((name, old_value, new_value))
+            }
+            else
+            {
+                None
+            }
+        })
+        .collect()
+}
+
+/**
+  Returns the changes from the old to the new version with the parts that
+  each change affects
+*/
+pub fn diff(old: &ParameterSet, new: &ParameterSet) -> Result<Vec<Change>, DiffError>
+{
+    let changes = changed_parameters(old, new);
+    if changes.is_empty()
+    {
+        return Ok(vec!());
+    }
+
+    let old_parts = render_parts(&assembly_with(old)?)?;
+
+    let mut result = vec!();
+    for (name, old_value, new_value) in changes
+    {
+        //A parameter that the new version doesn't have can't be applied, and
+        //one that the code doesn't have anymore affects nothing
+        let affected_parts = match new_value
+        {
+            Some(ref value) => {
+                let mut assembly = assembly_with(old)?;
+                match set_parameter(&mut assembly, &name, value)
+                {
+                    Ok(()) => {
+                        render_parts(&assembly)?.into_iter()
+                            .zip(old_parts.iter())
+                            .filter(|(part, old_part)| part.1 != old_part.1)
+                            .map(|(part, _)| part.0)
+                            .collect()
+                    }
+                    Err(ParameterError::Unknown(_)) => vec!(),
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            None => vec!(),
+        };
+
+        result.push(Change{name, old: old_value, new: new_value, affected_parts});
+    }
+    Ok(result)
+}
+
+fn node(name: &str, arguments: Vec<Argument>, children: Vec<Node>) -> Node
+{
+    Node{name: name.to_string(), arguments, children, modifier: None}
+}
+
+fn colored(color: &str, child: Node) -> Node
+{
+    let color = Argument{name: None, value: Value::Str(color.to_string())};
+    node("color", vec!(color), vec!(child))
+}
+
+/**
+  The difference in geometry between two versions of a part
+*/
+pub struct GeometryDiff
+{
+    //Top level assignments like `$fn` of the new version
+    assignments: Vec<(String, Value)>,
+    //Material that is only in the new version
+    pub added: Node,
+    //Material that is only in the old version
+    pub removed: Node,
+    //Material that is in both versions
+    pub unchanged: Node,
+}
+
+impl GeometryDiff
+{
+    pub fn new(old_code: &str, new_code: &str) -> Result<GeometryDiff, ParseError>
+    {
+        let old = node("union", vec!(), scad_tree::parse_nodes(old_code)?);
+
+        let mut assignments = vec!();
+        let mut new_nodes = vec!();
+        for statement in scad_tree::parse(new_code)?
+        {
+            match statement
+            {
+                Statement::Module(node) => new_nodes.push(node),
+                Statement::Assignment(name, value) => assignments.push((name, value)),
+            }
+        }
+        let new = node("union", vec!(), new_nodes);
+
+        Ok(GeometryDiff{
+            assignments,
+            added: node("difference", vec!(), vec!(new.clone(), old.clone())),
+            removed: node("difference", vec!(), vec!(old.clone(), new.clone())),
+            unchanged: node("intersection", vec!(), vec!(old, new)),
+        })
+    }
+
+    /**
+      Returns code showing the added material in green and the removed
+      material in red. The unchanged material is drawn transparent around
+      them in the preview
+    */
+    pub fn code(&self) -> String
+    {
+        let mut result = String::new();
+        for (name, value) in &self.assignments
+        {
+            result.push_str(&format!("{}={};\n", name, value));
+        }
+
+        let mut unchanged = self.unchanged.clone();
+        unchanged.modifier = Some('%');
+
+        colored("green", self.added.clone()).write_code(0, &mut result);
+        colored("red", self.removed.clone()).write_code(0, &mut result);
+        unchanged.write_code(0, &mut result);
+        result
+    }
+
+    /**
+      Returns the added and removed volume in mm^3 sampled with cells of
+      the specified size
+    */
+    pub fn volumes(&self, resolution: f32) -> Result<(f32, f32), EvalError>
+    {
+        let volume = |node: &Node| -> Result<f32, EvalError> {
+            let solid = Solid::from_nodes(&[node.clone()])?;
+            Ok(solid.volume_properties(resolution).volume)
+        };
+        Ok((volume(&self.added)?, volume(&self.removed)?))
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn changed(changes: &[(&str, &str)]) -> ParameterSet
+    {
+        let mut assembly = Assembly::new();
+        for &(name, value) in changes
+        {
+            set_parameter(&mut assembly, name, value).unwrap();
+        }
+        ParameterSet::of(&assembly)
+    }
+
+    #[test]
+    fn same_versions_have_no_changes()
+    {
+        let parameters = changed(&[]);
+        assert!(diff(&parameters, &parameters).unwrap().is_empty());
+    }
+
+    #[test]
+    fn changes_list_the_parts_they_affect()
+    {
+        let old = changed(&[]);
+        let new = changed(&[("servo_mount.servo_width", "13"), ("motor.mass", "40")]);
+
+        let changes = diff(&old, &new).unwrap();
+        assert_eq!(changes.len(), 2);
+
+        assert_eq!(changes[0].name, "servo_mount.servo_width");
+        assert_eq!(changes[0].new, Some("13".to_string()));
+        assert!(changes[0].affected_parts.contains(&"servo_mount".to_string()));
+        assert!(!changes[0].affected_parts.contains(&"canopy".to_string()));
+
+        //The motor is not printed
+        assert_eq!(changes[1].name, "motor.mass");
+        assert!(changes[1].affected_parts.is_empty());
+    }
+
+    #[test]
+    fn removed_parameters_are_listed_without_parts()
+    {
+        let mut old = changed(&[]);
+        old.values.push(("body.removed_length".to_string(), "6".to_string()));
+        let new = changed(&[("servo_mount.servo_width", "13")]);
+
+        let changes = diff(&old, &new).unwrap();
+        assert_eq!(changes.len(), 2);
+
+        assert_eq!(changes[0].name, "body.removed_length");
+        assert_eq!(changes[0].new, None);
+        assert!(changes[0].affected_parts.is_empty());
+
+        assert_eq!(changes[1].name, "servo_mount.servo_width");
+        assert!(changes[1].affected_parts.contains(&"servo_mount".to_string()));
+    }
+
+    #[test]
+    fn geometry_diff_finds_added_and_removed_material()
+    {
+        use scad::*;
+
+        let old = history::part_code(&scad!(Cube(vec3(10., 10., 10.))));
+        let new = history::part_code(&scad!(Translate(vec3(5., 0., 0.)); scad!(Cube(vec3(10., 10., 10.)))));
+
+        let diff = GeometryDiff::new(&old, &new).unwrap();
+        let (added, removed) = diff.volumes(0.5).unwrap();
+        assert!((added - 500.).abs() < 10., "{}", added);
+        assert!((removed - 500.).abs() < 10., "{}", removed);
+
+        let code = diff.code();
+        assert!(code.starts_with("$fn=20;"), "{}", code);
+        assert!(code.contains("color(\"green\")") && code.contains("color(\"red\")"));
+        assert!(scad_tree::parse(&code).is_ok());
+    }
+
+    #[test]
+    fn unchanged_part_has_no_added_or_removed_material()
+    {
+        let code = load_part_code("current", "vtx_mount").unwrap();
+        let (added, removed) = GeometryDiff::new(&code, &code).unwrap().volumes(1.).unwrap();
+        assert_eq!((added, removed), (0., 0.));
+    }
+
+    #[test]
+    fn unknown_parts_are_reported()
+    {
+        match load_part_code("current", "wing")
+        {
+            Err(DiffError::UnknownPart(ref version, ref part)) => {
+                assert_eq!((version.as_str(), part.as_str()), ("current", "wing"))
+            }
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,189 @@
+/*!
+  Fillets and chamfers for the edges of parts.
+
+  OpenSCAD has no operation that rounds the edges of a solid, so the helpers
+  work on the 2d outlines that the parts are extruded from. Corners of an
+  outline are rounded or cut with offsets. The edges along the top and bottom
+  of an extruded plate are made of thin layers of the outline, each shrunk by
+  the profile of the edge at its height, which the printer turns into layers
+  anyway.
+*/
+
+use scad::*;
+
+use hole_pattern::HolePattern;
+
+//Number of layers that an edge along a face of a plate is made of
+const EDGE_LAYERS: usize = 4;
+//Height of the cylinders that the chamfer cones are made of
+const CONE_END_HEIGHT: f32 = 0.01;
+
+/**
+  How an edge is finished
+*/
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EdgeFinish
+{
+    Sharp,
+    Round,
+    //A 45 degree chamfer
+    Chamfer,
+}
+
+impl EdgeFinish
+{
+    /**
+      Returns how far the surface is set in from the outline at a height
+      above the face of a plate, for an edge of the specified size
+    */
+    pub fn inset(&self, size: f32, height: f32) -> f32
+    {
+        let height = height.max(0.).min(size);
+        match *self
+        {
+            EdgeFinish::Sharp => 0.,
+            EdgeFinish::Round => size - (size * size - (size - height).powi(2)).sqrt(),
+            EdgeFinish::Chamfer => size - height,
+        }
+    }
+}
+
+/**
+  Rounds or cuts off the outer corners of a 2d outline. The chamfers are
+  tangent to the rounding of the same size
+*/
+pub fn finish_corners(outline: ScadObject, finish: EdgeFinish, size: f32) -> ScadObject
+{
+    if size <= 0.
+    {
+        return outline;
+    }
+
+    let shrink = |outline: ScadObject| scad!(Offset(OffsetType::Delta(-size), false); outline);
+    match finish
+    {
+        EdgeFinish::Sharp => outline,
+        EdgeFinish::Round => scad!(Offset(OffsetType::Radius(size), false); shrink(outline)),
+        EdgeFinish::Chamfer => scad!(Offset(OffsetType::Delta(size), true); shrink(outline)),
+    }
+}
+
+/**
+  Extrudes a 2d outline into a plate with the edges along its bottom and top
+  faces finished. The edges are at most half as large as the plate is thick
+*/
+pub fn extrude_with_edges(outline: ScadObject, height: f32, finish: EdgeFinish, size: f32)
+    -> ScadObject
+{
+    let extrude = |height| LinExtrudeParams{height, ..Default::default()};
+
+    let size = size.min(height / 2.);
+    if finish == EdgeFinish::Sharp || size <= 0.
+    {
+        return scad!(LinearExtrude(extrude(height)); outline);
+    }
+
+    let mut result = scad!(Union);
+    if height - size * 2. > 0.
+    {
+        result.add_child(scad!(Translate(vec3(0., 0., size)); {
+            scad!(LinearExtrude(extrude(height - size * 2.)); outline.clone())
+        }));
+    }
+
+    let layer_height = size / EDGE_LAYERS as f32;
+    for i in 0..EDGE_LAYERS
+    {
+        //The profile is followed at the middle of each layer
+        let inset = finish.inset(size, (i as f32 + 0.5) * layer_height);
+        let layer = scad!(LinearExtrude(extrude(layer_height)); {
+            scad!(Offset(OffsetType::Delta(-inset), false); outline.clone())
+        });
+
+        let bottom = i as f32 * layer_height;
+        let top = height - (i + 1) as f32 * layer_height;
+        result.add_child(scad!(Translate(vec3(0., 0., bottom)); layer.clone()));
+        result.add_child(scad!(Translate(vec3(0., 0., top)); layer));
+    }
+    result
+}
+
+/**
+  Returns a cone from `bottom_radius` at z = 0 to `top_radius` at `height`.
+  The ends are thin cylinders that stick out a bit past both ends so that
+  faces at the ends are cut cleanly
+*/
+pub fn cone(height: f32, bottom_radius: f32, top_radius: f32) -> ScadObject
+{
+    scad!(Hull; {
+        scad!(Translate(vec3(0., 0., -CONE_END_HEIGHT)); {
+            scad!(Cylinder(CONE_END_HEIGHT, Radius(bottom_radius)))
+        }),
+        scad!(Translate(vec3(0., 0., height)); scad!(Cylinder(CONE_END_HEIGHT, Radius(top_radius))))
+    })
+}
+
+/**
+  Returns cones that chamfer the entry of every hole of a pattern by `size`.
+  The entries are at z = 0 with the holes going up from there, like on the
+  bottom face of a plate. Mirror the cones for a top face
+*/
+pub fn screw_entry_chamfers(pattern: &HolePattern, size: f32) -> ScadObject
+{
+    let radius = pattern.diameter / 2.;
+    pattern.place_object(cone(size, radius + size, radius))
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use evaluate::{Shape, Solid, Vec2, Vec3};
+
+    #[test]
+    fn edge_profiles_meet_the_sides()
+    {
+        for finish in &[EdgeFinish::Round, EdgeFinish::Chamfer]
+        {
+            assert_eq!(finish.inset(1., 0.), 1.);
+            assert_eq!(finish.inset(1., 1.), 0.);
+            assert_eq!(finish.inset(1., 3.), 0.);
+        }
+        //The round edge bulges out past the chamfer
+        assert!(EdgeFinish::Round.inset(1., 0.5) < EdgeFinish::Chamfer.inset(1., 0.5));
+    }
+
+    #[test]
+    fn corners_are_rounded()
+    {
+        let square = || scad!(Square(vec2(10., 10.)));
+        let rounded = Shape::from_object(&finish_corners(square(), EdgeFinish::Round, 2.)).unwrap();
+        let chamfered = Shape::from_object(&finish_corners(square(), EdgeFinish::Chamfer, 2.)).unwrap();
+
+        for shape in &[&rounded, &chamfered]
+        {
+            assert!(shape.contains(&Vec2::new(5., 5.)));
+            assert!(shape.contains(&Vec2::new(9.9, 5.)));
+            assert!(!shape.contains(&Vec2::new(9.9, 9.9)));
+        }
+        //The chamfer leaves the material outside of the rounding
+        assert!(!rounded.contains(&Vec2::new(9.8, 9.)));
+        assert!(chamfered.contains(&Vec2::new(9.8, 9.)));
+    }
+
+    #[test]
+    fn plate_edges_are_set_in()
+    {
+        let plate = extrude_with_edges(scad!(Square(vec2(10., 10.))), 4., EdgeFinish::Chamfer, 1.);
+        let solid = Solid::from_object(&plate).unwrap();
+
+        assert!(solid.contains(&Vec3::new(9.9, 5., 2.)));
+        assert!(!solid.contains(&Vec3::new(9.9, 5., 0.05)));
+        assert!(!solid.contains(&Vec3::new(9.9, 5., 3.95)));
+        assert!(solid.contains(&Vec3::new(5., 5., 0.05)));
+
+        let bounds = solid.bounds();
+        assert!(bounds.min.z.abs() < 1e-3 && (bounds.max.z - 4.).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,2251 @@
+/*!
+  Native evaluation of generated geometry.
+
+  The scad code for an object is parsed into a tree of solids which can be
+  queried for approximate signed distances and point containment. This is
+  enough to estimate volumes, centres of mass and overlaps by sampling
+  without running OpenSCAD.
+*/
+
+use na;
+use scad::ScadObject;
+
+use scad_tree::{self, Node, Value};
+
+use std::f32::consts::PI;
+use std::fmt;
+
+pub type Vec3 = na::Vector3<f32>;
+pub type Vec2 = na::Vector2<f32>;
+
+//Number of segments used when approximating round things by points
+const CIRCLE_SEGMENTS: usize = 64;
+//Number of directions used for the support function of hulls
+const HULL_DIRECTIONS_2D: usize = 128;
+const HULL_DIRECTIONS_3D: usize = 400;
+//Number of grid cells along the longest side of a shape when its outline is
+//traced for offsetting
+const TRACE_CELLS: usize = 256;
+//Number of times the edges of the grid are halved to find where an outline
+//crosses them
+const TRACE_BISECTIONS: usize = 12;
+//Step used to compute surface normals from the distance function
+const NORMAL_STEP: f32 = 0.01;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct EvalError
+{
+    pub message: String,
+}
+
+impl EvalError
+{
+    pub fn new(message: &str) -> EvalError
+    {
+        EvalError{message: message.to_string()}
+    }
+}
+
+impl fmt::Display for EvalError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<scad_tree::ParseError> for EvalError
+{
+    fn from(error: scad_tree::ParseError) -> EvalError
+    {
+        EvalError{message: error.to_string()}
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BoundingBox
+{
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl BoundingBox
+{
+    pub fn new(min: Vec3, max: Vec3) -> BoundingBox
+    {
+        BoundingBox{min, max}
+    }
+
+    pub fn empty() -> BoundingBox
+    {
+        let inf = f32::INFINITY;
+        BoundingBox{min: Vec3::new(inf, inf, inf), max: Vec3::new(-inf, -inf, -inf)}
+    }
+
+    pub fn from_points(points: &[Vec3]) -> BoundingBox
+    {
+        let mut result = BoundingBox::empty();
+        for point in points
+        {
+            result.add_point(point);
+        }
+        result
+    }
+
+    pub fn add_point(&mut self, point: &Vec3)
+    {
+        for i in 0..3
+        {
+            self.min[i] = self.min[i].min(point[i]);
+            self.max[i] = self.max[i].max(point[i]);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool
+    {
+        !(0..3).all(|i| self.min[i] <= self.max[i])
+    }
+
+    pub fn union(&self, other: &BoundingBox) -> BoundingBox
+    {
+        let mut result = *self;
+        for i in 0..3
+        {
+            result.min[i] = self.min[i].min(other.min[i]);
+            result.max[i] = self.max[i].max(other.max[i]);
+        }
+        result
+    }
+
+    pub fn intersection(&self, other: &BoundingBox) -> BoundingBox
+    {
+        let mut result = *self;
+        for i in 0..3
+        {
+            result.min[i] = self.min[i].max(other.min[i]);
+            result.max[i] = self.max[i].min(other.max[i]);
+        }
+        result
+    }
+
+    pub fn expanded(&self, amount: f32) -> BoundingBox
+    {
+        if self.is_empty()
+        {
+            return *self;
+        }
+        let offset = Vec3::new(amount, amount, amount);
+        BoundingBox{min: self.min - offset, max: self.max + offset}
+    }
+
+    pub fn size(&self) -> Vec3
+    {
+        if self.is_empty()
+        {
+            return Vec3::new(0., 0., 0.);
+        }
+        self.max - self.min
+    }
+
+    pub fn center(&self) -> Vec3
+    {
+        (self.min + self.max) / 2.
+    }
+
+    pub fn volume(&self) -> f32
+    {
+        let size = self.size();
+        size.x * size.y * size.z
+    }
+
+    pub fn contains(&self, point: &Vec3) -> bool
+    {
+        (0..3).all(|i| point[i] >= self.min[i] && point[i] <= self.max[i])
+    }
+
+    pub fn intersects(&self, other: &BoundingBox) -> bool
+    {
+        !self.intersection(other).is_empty()
+    }
+
+    /**
+      Lower bound of the distance from the point to anything inside the box
+    */
+    pub fn distance(&self, point: &Vec3) -> f32
+    {
+        if self.is_empty()
+        {
+            return f32::INFINITY;
+        }
+        let mut squared = 0.;
+        for i in 0..3
+        {
+            let outside = (self.min[i] - point[i]).max(point[i] - self.max[i]).max(0.);
+            squared += outside * outside;
+        }
+        squared.sqrt()
+    }
+
+    pub fn corners(&self) -> Vec<Vec3>
+    {
+        let mut result = vec!();
+        for &x in &[self.min.x, self.max.x]
+        {
+            for &y in &[self.min.y, self.max.y]
+            {
+                for &z in &[self.min.z, self.max.z]
+                {
+                    result.push(Vec3::new(x, y, z));
+                }
+            }
+        }
+        result
+    }
+
+    pub fn transformed(&self, transform: &Transform) -> BoundingBox
+    {
+        if self.is_empty()
+        {
+            return *self;
+        }
+        let corners = self.corners().iter()
+            .map(|corner| transform.apply(corner))
+            .collect::<Vec<_>>();
+        BoundingBox::from_points(&corners)
+    }
+}
+
+/**
+  An affine transformation of the form `linear * p + translation`
+*/
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Transform
+{
+    pub linear: na::Matrix3<f32>,
+    pub translation: Vec3,
+}
+
+impl Transform
+{
+    pub fn identity() -> Transform
+    {
+        Transform{linear: na::Matrix3::identity(), translation: Vec3::new(0., 0., 0.)}
+    }
+
+    pub fn translation(offset: Vec3) -> Transform
+    {
+        Transform{linear: na::Matrix3::identity(), translation: offset}
+    }
+
+    pub fn rotation(angle: f32, axis: Vec3) -> Transform
+    {
+        let linear = if axis.norm() == 0.
+        {
+            na::Matrix3::identity()
+        }
+        else
+        {
+            let rotation = na::Rotation3::from_axis_angle(
+                &na::Unit::new_normalize(axis),
+                angle.to_radians()
+            );
+            *rotation.matrix()
+        };
+        Transform{linear, translation: Vec3::new(0., 0., 0.)}
+    }
+
+    pub fn scale(factors: Vec3) -> Transform
+    {
+        let linear = na::Matrix3::new(
+            factors.x, 0., 0.,
+            0., factors.y, 0.,
+            0., 0., factors.z
+        );
+        Transform{linear, translation: Vec3::new(0., 0., 0.)}
+    }
+
+    /**
+      Mirroring in the plane through the origin with the specified normal
+    */
+    pub fn mirror(normal: Vec3) -> Transform
+    {
+        if normal.norm() == 0.
+        {
+            return Transform::identity();
+        }
+        let n = normal.normalize();
+        let linear = na::Matrix3::identity() - (n * n.transpose()) * 2.;
+        Transform{linear, translation: Vec3::new(0., 0., 0.)}
+    }
+
+    pub fn apply(&self, point: &Vec3) -> Vec3
+    {
+        self.linear * point + self.translation
+    }
+
+    pub fn apply_2d(&self, point: &Vec2) -> Vec2
+    {
+        let result = self.apply(&Vec3::new(point.x, point.y, 0.));
+        Vec2::new(result.x, result.y)
+    }
+
+    /**
+      Returns the transform that applies `inner` first and then self
+    */
+    pub fn then(&self, inner: &Transform) -> Transform
+    {
+        Transform{
+            linear: self.linear * inner.linear,
+            translation: self.linear * inner.translation + self.translation,
+        }
+    }
+
+    pub fn inverse(&self) -> Option<Transform>
+    {
+        self.linear.try_inverse().map(|linear| Transform{
+            linear,
+            translation: -(linear * self.translation),
+        })
+    }
+
+    /**
+      Returns the smallest factor that distances are scaled by when
+      transformed. Used to keep distances conservative under scaling
+    */
+    fn min_scale(&self) -> f32
+    {
+        (0..3).map(|i| self.linear.column(i).norm())
+            .fold(f32::INFINITY, f32::min)
+    }
+}
+
+/**
+  Combines the distance to a 2d shape or radius with the distance to
+  the top and bottom planes of an extruded object
+*/
+fn combine_extruded_distance(shape_distance: f32, z_distance: f32) -> f32
+{
+    if shape_distance > 0. && z_distance > 0.
+    {
+        (shape_distance * shape_distance + z_distance * z_distance).sqrt()
+    }
+    else
+    {
+        shape_distance.max(z_distance)
+    }
+}
+
+fn circle_points(radius: f32) -> Vec<Vec2>
+{
+    (0..CIRCLE_SEGMENTS)
+        .map(|i| {
+            let angle = 2. * PI * i as f32 / CIRCLE_SEGMENTS as f32;
+            Vec2::new(radius * angle.cos(), radius * angle.sin())
+        })
+        .collect()
+}
+
+/**
+  Roughly evenly distributed unit vectors on a sphere. Includes the
+  coordinate axis directions so that the hull never extends outside the
+  bounding box of its points
+*/
+fn sphere_directions(count: usize) -> Vec<Vec3>
+{
+    let golden_angle = PI * (3. - (5f32).sqrt());
+    let mut result = (0..count)
+        .map(|i| {
+            let z = 1. - 2. * (i as f32 + 0.5) / count as f32;
+            let radius = (1. - z * z).sqrt();
+            let angle = golden_angle * i as f32;
+            Vec3::new(radius * angle.cos(), radius * angle.sin(), z)
+        })
+        .collect::<Vec<_>>();
+
+    for i in 0..3
+    {
+        let mut axis = Vec3::new(0., 0., 0.);
+        axis[i] = 1.;
+        result.push(axis);
+        result.push(-axis);
+    }
+    result
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+//                              2d shapes
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Debug)]
+enum ShapeKind
+{
+    Empty,
+    Union(Vec<Shape>),
+    Intersection(Vec<Shape>),
+    Difference(Box<Shape>, Vec<Shape>),
+    Transformed{inverse: Transform, forward: Transform, scale: f32, child: Box<Shape>},
+    Circle(f32),
+    //Axis aligned rectangle from the origin to the size
+    Rectangle(Vec2),
+    //List of closed outlines. Inside is decided by the even-odd rule
+    Polygon(Vec<Vec<Vec2>>),
+    //Round offset of a shape whose distance is exact
+    Offset(Box<Shape>, f32),
+    //Directions and the support function value in that direction
+    Hull(Vec<(Vec2, f32)>),
+}
+
+/**
+  A 2d shape in the xy plane. The bounding box uses z = 0
+*/
+#[derive(Clone, Debug)]
+pub struct Shape
+{
+    kind: ShapeKind,
+    bounds: BoundingBox,
+}
+
+fn distance_to_segment(point: &Vec2, start: &Vec2, end: &Vec2) -> f32
+{
+    let segment = end - start;
+    let length_squared = segment.norm_squared();
+    let t = if length_squared == 0.
+    {
+        0.
+    }
+    else
+    {
+        ((point - start).dot(&segment) / length_squared).clamp(0., 1.)
+    };
+    (point - (start + segment * t)).norm()
+}
+
+/**
+  Returns true if a transform in the xy plane keeps distances
+*/
+fn is_rigid(transform: &Transform) -> bool
+{
+    let linear = &transform.linear;
+    (0..2).all(|i| (linear.column(i).norm() - 1.).abs() < 1e-5)
+        && linear.column(0).dot(&linear.column(1)).abs() < 1e-5
+}
+
+/**
+  How the corners of an outline are joined when it is offset
+*/
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum OffsetCorners
+{
+    //offset(r = ...)
+    Round,
+    //offset(delta = ...)
+    Sharp,
+    //offset(delta = ..., chamfer = true)
+    Chamfered,
+}
+
+fn signed_area(outline: &[Vec2]) -> f32
+{
+    let count = outline.len();
+    (0..count)
+        .map(|i| {
+            let (start, end) = (outline[i], outline[(i + 1) % count]);
+            start.x * end.y - end.x * start.y
+        })
+        .sum::<f32>() / 2.
+}
+
+fn outline_contains(outline: &[Vec2], point: &Vec2) -> bool
+{
+    let count = outline.len();
+    let mut inside = false;
+    for i in 0..count
+    {
+        let (start, end) = (&outline[i], &outline[(i + 1) % count]);
+        if (start.y > point.y) != (end.y > point.y)
+        {
+            let x = start.x + (point.y - start.y) / (end.y - start.y) * (end.x - start.x);
+            if point.x < x
+            {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+/**
+  Removes repeated points from closed outlines and turns them so that the
+  inside of the shape is on the left. Outlines are nested by the even-odd
+  rule
+*/
+fn orient_outlines(outlines: &[Vec<Vec2>]) -> Vec<Vec<Vec2>>
+{
+    let cleaned = outlines.iter()
+        .map(|outline| {
+            let mut result: Vec<Vec2> = vec!();
+            for point in outline
+            {
+                match result.last()
+                {
+                    Some(last) if (last - point).norm() <= 1e-6 => {}
+                    _ => result.push(*point)
+                }
+            }
+            while result.len() > 1 && (result[0] - result[result.len() - 1]).norm() <= 1e-6
+            {
+                result.pop();
+            }
+            result
+        })
+        .filter(|outline| outline.len() >= 3)
+        .collect::<Vec<_>>();
+
+    cleaned.iter().enumerate()
+        .map(|(i, outline)| {
+            let depth = cleaned.iter().enumerate()
+                .filter(|&(j, other)| i != j && outline_contains(other, &outline[0]))
+                .count();
+            let mut result = outline.clone();
+            if (signed_area(outline) > 0.) != (depth % 2 == 0)
+            {
+                result.reverse();
+            }
+            result
+        })
+        .collect()
+}
+
+/**
+  Removes the points of an open line that are closer than the tolerance to
+  the simplified line
+*/
+fn simplify_line(points: &[Vec2], tolerance: f32) -> Vec<Vec2>
+{
+    let mut keep = vec!(false; points.len());
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+
+    let mut ranges = vec!((0, points.len() - 1));
+    while let Some((start, end)) = ranges.pop()
+    {
+        let farthest = (start + 1..end)
+            .map(|i| (i, distance_to_segment(&points[i], &points[start], &points[end])))
+            .fold(None, |best: Option<(usize, f32)>, candidate| match best
+            {
+                Some(best) if best.1 >= candidate.1 => Some(best),
+                _ => Some(candidate)
+            });
+
+        if let Some((index, distance)) = farthest
+        {
+            if distance > tolerance
+            {
+                keep[index] = true;
+                ranges.push((start, index));
+                ranges.push((index, end));
+            }
+        }
+    }
+
+    points.iter().zip(keep).filter(|&(_, keep)| keep).map(|(point, _)| *point).collect()
+}
+
+/**
+  Simplifies a closed outline by splitting it at the point furthest from the
+  first point
+*/
+fn simplify_outline(outline: &[Vec2], tolerance: f32) -> Vec<Vec2>
+{
+    let farthest = (0..outline.len())
+        .max_by(|&a, &b| {
+            (outline[a] - outline[0]).norm()
+                .partial_cmp(&(outline[b] - outline[0]).norm())
+                .unwrap_or(::std::cmp::Ordering::Equal)
+        })
+        .unwrap_or(0);
+    if farthest == 0
+    {
+        return outline.to_vec();
+    }
+
+    let mut second_half = outline[farthest..].to_vec();
+    second_half.push(outline[0]);
+
+    let mut result = simplify_line(&outline[..farthest + 1], tolerance);
+    result.pop();
+    result.extend(simplify_line(&second_half, tolerance));
+    result.pop();
+    result
+}
+
+fn line_intersection(start: &Vec2, end: &Vec2, other_start: &Vec2, other_end: &Vec2) -> Option<Vec2>
+{
+    let direction = end - start;
+    let other_direction = other_end - other_start;
+    let cross = |a: &Vec2, b: &Vec2| a.x * b.y - a.y * b.x;
+
+    let determinant = cross(&direction, &other_direction);
+    if determinant.abs() < 1e-6 * direction.norm() * other_direction.norm()
+    {
+        return None;
+    }
+    Some(start + direction * (cross(&(other_start - start), &other_direction) / determinant))
+}
+
+/**
+  Marching squares cuts sharp corners off with short edges. This replaces
+  the short edges with the corner where the edges on either side meet if
+  it is close enough to have been cut off
+*/
+fn sharpen_corners(outline: &[Vec2], cell: f32) -> Vec<Vec2>
+{
+    let count = outline.len();
+    let long_edges = (0..count)
+        .filter(|&i| (outline[(i + 1) % count] - outline[i]).norm() >= cell * 2.)
+        .collect::<Vec<_>>();
+    if long_edges.len() < 2
+    {
+        return outline.to_vec();
+    }
+
+    let mut result = vec!();
+    for (k, &edge) in long_edges.iter().enumerate()
+    {
+        let next = long_edges[(k + 1) % long_edges.len()];
+        let mut between = vec!((edge + 1) % count);
+        while between[between.len() - 1] != next
+        {
+            between.push((between[between.len() - 1] + 1) % count);
+        }
+
+        let corner = if between.len() > 1
+        {
+            line_intersection(&outline[edge], &outline[between[0]], &outline[next], &outline[(next + 1) % count])
+                .filter(|corner| between.iter().all(|&i| (outline[i] - corner).norm() < cell * 2.))
+        }
+        else
+        {
+            None
+        };
+
+        match corner
+        {
+            Some(corner) => result.push(corner),
+            None => result.extend(between.iter().map(|&i| outline[i]))
+        }
+    }
+    result
+}
+
+/**
+  Moves the edges of an outline with the inside on the left outwards by the
+  amount. Corners where the edges move apart are extended until the edges
+  meet or cut off like openscad does with `chamfer = true`
+*/
+fn offset_outline(outline: &[Vec2], amount: f32, chamfer: bool) -> Vec<Vec2>
+{
+    let count = outline.len();
+    let mut result = vec!();
+    for i in 0..count
+    {
+        let point = outline[i];
+        let incoming = (point - outline[(i + count - 1) % count]).normalize();
+        let outgoing = (outline[(i + 1) % count] - point).normalize();
+        let incoming_normal = Vec2::new(incoming.y, -incoming.x);
+        let outgoing_normal = Vec2::new(outgoing.y, -outgoing.x);
+
+        let cross = incoming.x * outgoing.y - incoming.y * outgoing.x;
+        let cos = incoming_normal.dot(&outgoing_normal);
+        let opening = cross * amount > 0.;
+
+        if opening && chamfer
+        {
+            //The cut is perpendicular to the bisector of the corner at the
+            //distance of the offset from the corner
+            let extension = amount.abs() * (cross.abs().atan2(cos) / 4.).tan();
+            result.push(point + incoming_normal * amount + incoming * extension);
+            result.push(point + outgoing_normal * amount - outgoing * extension);
+        }
+        else if 1. + cos < 1e-3
+        {
+            //The edges turn back on themselves and never meet
+            result.push(point + incoming_normal * amount);
+            result.push(point + outgoing_normal * amount);
+        }
+        else
+        {
+            result.push(point + (incoming_normal + outgoing_normal) * amount / (1. + cos));
+        }
+    }
+    result
+}
+
+impl Shape
+{
+    fn new(kind: ShapeKind) -> Shape
+    {
+        let bounds = match kind
+        {
+            ShapeKind::Empty => BoundingBox::empty(),
+            ShapeKind::Union(ref children) => children.iter()
+                .fold(BoundingBox::empty(), |acc, child| acc.union(&child.bounds)),
+            ShapeKind::Intersection(ref children) => {
+                let mut iter = children.iter();
+                match iter.next()
+                {
+                    Some(first) => iter.fold(first.bounds, |acc, child| acc.intersection(&child.bounds)),
+                    None => BoundingBox::empty()
+                }
+            }
+            ShapeKind::Difference(ref main, _) => main.bounds,
+            ShapeKind::Transformed{ref forward, ref child, ..} => child.bounds.transformed(forward),
+            ShapeKind::Circle(radius) => BoundingBox::new(
+                Vec3::new(-radius, -radius, 0.),
+                Vec3::new(radius, radius, 0.)
+            ),
+            ShapeKind::Rectangle(size) => BoundingBox::new(
+                Vec3::new(size.x.min(0.), size.y.min(0.), 0.),
+                Vec3::new(size.x.max(0.), size.y.max(0.), 0.)
+            ),
+            ShapeKind::Polygon(ref outlines) => {
+                let points = outlines.iter()
+                    .flat_map(|outline| outline.iter())
+                    .map(|p| Vec3::new(p.x, p.y, 0.))
+                    .collect::<Vec<_>>();
+                BoundingBox::from_points(&points)
+            }
+            ShapeKind::Offset(ref child, amount) => {
+                let mut bounds = child.bounds.expanded(amount.max(0.));
+                bounds.min.z = 0.;
+                bounds.max.z = 0.;
+                bounds
+            }
+            ShapeKind::Hull(ref support) => {
+                let mut bounds = BoundingBox::empty();
+                for &(direction, value) in support
+                {
+                    //The axis directions are part of the support directions
+                    if direction.x == 1. { bounds.max.x = value; }
+                    if direction.x == -1. { bounds.min.x = -value; }
+                    if direction.y == 1. { bounds.max.y = value; }
+                    if direction.y == -1. { bounds.min.y = -value; }
+                }
+                bounds.min.z = 0.;
+                bounds.max.z = 0.;
+                bounds
+            }
+        };
+
+        Shape{kind, bounds}
+    }
+
+    fn empty() -> Shape
+    {
+        Shape::new(ShapeKind::Empty)
+    }
+
+    fn hull(points: &[Vec2]) -> Shape
+    {
+        if points.is_empty()
+        {
+            return Shape::empty();
+        }
+
+        let support = (0..HULL_DIRECTIONS_2D)
+            .map(|i| {
+                let angle = 2. * PI * i as f32 / HULL_DIRECTIONS_2D as f32;
+                //Avoid rounding errors in the axis directions
+                let axes = [
+                    Vec2::new(1., 0.),
+                    Vec2::new(0., 1.),
+                    Vec2::new(-1., 0.),
+                    Vec2::new(0., -1.)
+                ];
+                let direction = if i * 4 % HULL_DIRECTIONS_2D == 0
+                {
+                    axes[i * 4 / HULL_DIRECTIONS_2D]
+                }
+                else
+                {
+                    Vec2::new(angle.cos(), angle.sin())
+                };
+                let value = points.iter()
+                    .map(|p| direction.dot(p))
+                    .fold(-f32::INFINITY, f32::max);
+                (direction, value)
+            })
+            .collect();
+
+        Shape::new(ShapeKind::Hull(support))
+    }
+
+    /**
+      Returns true if the distance to the shape is exact rather than a lower
+      bound. Round offsets are only exact for such shapes
+    */
+    fn has_exact_distance(&self) -> bool
+    {
+        match self.kind
+        {
+            ShapeKind::Circle(_) | ShapeKind::Rectangle(_) | ShapeKind::Polygon(_) => true,
+            ShapeKind::Transformed{ref forward, ref child, ..} => {
+                is_rigid(forward) && child.has_exact_distance()
+            }
+            ShapeKind::Union(ref children) => children.len() == 1 && children[0].has_exact_distance(),
+            _ => false
+        }
+    }
+
+    /**
+      Returns the outlines of the shape with the inside on the left if they
+      follow directly from the shape
+    */
+    fn exact_outlines(&self) -> Option<Vec<Vec<Vec2>>>
+    {
+        match self.kind
+        {
+            ShapeKind::Empty => Some(vec!()),
+            ShapeKind::Circle(radius) => Some(orient_outlines(&[circle_points(radius)])),
+            ShapeKind::Rectangle(_) => Some(orient_outlines(&[self.points()])),
+            ShapeKind::Polygon(ref outlines) => Some(orient_outlines(outlines)),
+            ShapeKind::Transformed{ref forward, ref child, ..} => {
+                let linear = &forward.linear;
+                let mirrored = linear[(0, 0)] * linear[(1, 1)] - linear[(0, 1)] * linear[(1, 0)] < 0.;
+                child.exact_outlines().map(|outlines| outlines.iter()
+                    .map(|outline| {
+                        let mut result = outline.iter()
+                            .map(|point| forward.apply_2d(point))
+                            .collect::<Vec<_>>();
+                        if mirrored
+                        {
+                            result.reverse();
+                        }
+                        result
+                    })
+                    .collect())
+            }
+            ShapeKind::Union(ref children) if children.len() == 1 => children[0].exact_outlines(),
+            _ => None
+        }
+    }
+
+    /**
+      Traces the outlines of the shape with marching squares on a grid that
+      covers it. The outlines have the inside on the left
+    */
+    fn traced_outlines(&self) -> Vec<Vec<Vec2>>
+    {
+        let size = self.bounds.size();
+        let cell = size.x.max(size.y) / TRACE_CELLS as f32;
+        if self.bounds.is_empty() || cell <= 0.
+        {
+            return vec!();
+        }
+
+        //A border of cells outside the shape keeps all the outlines closed
+        let origin = Vec2::new(self.bounds.min.x - cell, self.bounds.min.y - cell);
+        let columns = (size.x / cell).ceil() as usize + 2;
+        let rows = (size.y / cell).ceil() as usize + 2;
+        let node = |i: usize, j: usize| j * (columns + 1) + i;
+        let position = |i: usize, j: usize| origin + Vec2::new(i as f32, j as f32) * cell;
+
+        let mut inside_nodes = Vec::with_capacity((columns + 1) * (rows + 1));
+        for j in 0..rows + 1
+        {
+            for i in 0..columns + 1
+            {
+                inside_nodes.push(self.contains(&position(i, j)));
+            }
+        }
+        let inside = |(i, j): (usize, usize)| inside_nodes[node(i, j)];
+
+        //The distance is only a bound inside some shapes so the crossings are
+        //found by bisecting the edges of the grid
+        let crossing = |inside_point: Vec2, outside_point: Vec2| {
+            let (mut inside_point, mut outside_point) = (inside_point, outside_point);
+            for _ in 0..TRACE_BISECTIONS
+            {
+                let middle = (inside_point + outside_point) / 2.;
+                if self.contains(&middle)
+                {
+                    inside_point = middle;
+                }
+                else
+                {
+                    outside_point = middle;
+                }
+            }
+            (inside_point + outside_point) / 2.
+        };
+
+        //Crossings are identified by the edge of the grid that they are on,
+        //even numbers for edges along x and odd numbers for edges along y
+        let mut points = ::std::collections::HashMap::new();
+        let mut segments = ::std::collections::HashMap::new();
+        for j in 0..rows
+        {
+            for i in 0..columns
+            {
+                //Corners and edges of the cell counterclockwise
+                let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+                let edges = [node(i, j) * 2, node(i + 1, j) * 2 + 1, node(i, j + 1) * 2, node(i, j) * 2 + 1];
+
+                let mut crossings = vec!();
+                for k in 0..4
+                {
+                    let (a, b) = (corners[k], corners[(k + 1) % 4]);
+                    if inside(a) != inside(b)
+                    {
+                        points.entry(edges[k]).or_insert_with(|| {
+                            let (a, b) = if inside(a) { (a, b) } else { (b, a) };
+                            crossing(position(a.0, a.1), position(b.0, b.1))
+                        });
+                        crossings.push((edges[k], inside(a)));
+                    }
+                }
+
+                //Lines go from where the outline leaves the inside to where
+                //it enters it. Saddles join the corners on the side that the
+                //middle of the cell is on
+                let count = crossings.len();
+                let center_inside = count == 4
+                    && self.contains(&(position(i, j) + Vec2::new(cell, cell) / 2.));
+                for c in 0..count
+                {
+                    if crossings[c].1
+                    {
+                        let end = if count == 2 || center_inside { (c + 1) % count } else { (c + count - 1) % count };
+                        segments.insert(crossings[c].0, crossings[end].0);
+                    }
+                }
+            }
+        }
+
+        let mut outlines = vec!();
+        while let Some(&start) = segments.keys().next()
+        {
+            let mut outline = vec!();
+            let mut current = start;
+            while let Some(next) = segments.remove(&current)
+            {
+                outline.push(points[&current]);
+                current = next;
+            }
+            outlines.push(sharpen_corners(&simplify_outline(&outline, cell / 64.), cell));
+        }
+        outlines.into_iter().filter(|outline| outline.len() >= 3).collect()
+    }
+
+    /**
+      Offsets the shape like the openscad offset module. Growing distributes
+      over unions and shrinking over intersections and differences, so only
+      the shapes that can't be split further are offset by their outlines
+    */
+    fn offset(child: Shape, amount: f32, corners: OffsetCorners) -> Shape
+    {
+        if amount == 0. || child.bounds.is_empty()
+        {
+            return child;
+        }
+
+        let offset_all = |children: Vec<Shape>, amount: f32| children.into_iter()
+            .map(|child| Shape::offset(child, amount, corners))
+            .collect::<Vec<_>>();
+
+        match child.kind
+        {
+            ShapeKind::Union(mut children) if children.len() == 1 => {
+                Shape::offset(children.remove(0), amount, corners)
+            }
+            ShapeKind::Union(children) if amount > 0. => {
+                Shape::new(ShapeKind::Union(offset_all(children, amount)))
+            }
+            ShapeKind::Intersection(children) if amount < 0. => {
+                Shape::new(ShapeKind::Intersection(offset_all(children, amount)))
+            }
+            ShapeKind::Difference(main, cutouts) if amount < 0. => {
+                Shape::new(ShapeKind::Difference(
+                    Box::new(Shape::offset(*main, amount, corners)),
+                    offset_all(cutouts, -amount)
+                ))
+            }
+            ShapeKind::Transformed{inverse, forward, scale, child} if is_rigid(&forward) => {
+                Shape::new(ShapeKind::Transformed{
+                    inverse,
+                    forward,
+                    scale,
+                    child: Box::new(Shape::offset(*child, amount, corners))
+                })
+            }
+            kind => Shape::offset_outlines(Shape::new(kind), amount, corners)
+        }
+    }
+
+    /**
+      Offsets a shape by its outlines. Shapes without an exact distance or
+      known outlines are traced first
+    */
+    fn offset_outlines(child: Shape, amount: f32, corners: OffsetCorners) -> Shape
+    {
+        if corners == OffsetCorners::Round && child.has_exact_distance()
+        {
+            return Shape::new(ShapeKind::Offset(Box::new(child), amount));
+        }
+
+        let outlines = match child.exact_outlines()
+        {
+            Some(outlines) => outlines,
+            None => child.traced_outlines()
+        };
+        let outline_shape = Shape::new(ShapeKind::Polygon(outlines.clone()));
+        let rounded = Shape::new(ShapeKind::Offset(Box::new(outline_shape), amount));
+        if corners == OffsetCorners::Round
+        {
+            return rounded;
+        }
+
+        let sharp = Shape::new(ShapeKind::Polygon(
+            outlines.iter()
+                .map(|outline| offset_outline(outline, amount, corners == OffsetCorners::Chamfered))
+                .collect()
+        ));
+
+        //Offset outlines cross themselves where the offset is larger than
+        //the features of the shape. Those parts are inside the round offset
+        //when growing and outside it when shrinking
+        if amount > 0.
+        {
+            Shape::new(ShapeKind::Union(vec!(rounded, sharp)))
+        }
+        else
+        {
+            Shape::new(ShapeKind::Intersection(vec!(rounded, sharp)))
+        }
+    }
+
+    /**
+      Builds a shape from a 2d scad object
+    */
+    pub fn from_object(object: &ScadObject) -> Result<Shape, EvalError>
+    {
+        let children = scad_tree::parse_nodes(&object.get_code())?.iter()
+            .map(build_shape)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Shape::new(ShapeKind::Union(children)))
+    }
+
+    pub fn bounds(&self) -> BoundingBox
+    {
+        self.bounds
+    }
+
+    fn bounds_distance(&self, point: &Vec2) -> f32
+    {
+        self.bounds.distance(&Vec3::new(point.x, point.y, 0.))
+    }
+
+    /**
+      Approximate signed distance from the point to the edge of the shape.
+      Negative inside the shape
+    */
+    pub fn distance(&self, point: &Vec2) -> f32
+    {
+        match self.kind
+        {
+            ShapeKind::Empty => f32::INFINITY,
+            ShapeKind::Union(ref children) => {
+                let mut result = f32::INFINITY;
+                for child in children
+                {
+                    //Inside the bounds of a child the child can be deeper
+                    //than the children already checked
+                    let lower_bound = child.bounds_distance(point);
+                    if lower_bound < result || lower_bound == 0.
+                    {
+                        result = result.min(child.distance(point));
+                    }
+                }
+                result
+            }
+            ShapeKind::Intersection(ref children) => children.iter()
+                .map(|child| child.distance(point))
+                .fold(-f32::INFINITY, f32::max),
+            ShapeKind::Difference(ref main, ref cutouts) => {
+                let main_distance = main.distance(point);
+                let threshold = -main_distance;
+                let mut result = main_distance;
+                for cutout in cutouts
+                {
+                    let lower_bound = cutout.bounds_distance(point);
+                    if lower_bound > 0. && lower_bound >= threshold
+                    {
+                        continue;
+                    }
+                    result = result.max(-cutout.distance(point));
+                }
+                result
+            }
+            ShapeKind::Transformed{ref inverse, scale, ref child, ..} => {
+                child.distance(&inverse.apply_2d(point)) * scale
+            }
+            ShapeKind::Circle(radius) => point.norm() - radius,
+            ShapeKind::Rectangle(size) => {
+                let half = size / 2.;
+                let center = half;
+                let q = Vec2::new(
+                    (point.x - center.x).abs() - half.x.abs(),
+                    (point.y - center.y).abs() - half.y.abs()
+                );
+                let outside = Vec2::new(q.x.max(0.), q.y.max(0.)).norm();
+                outside + q.x.max(q.y).min(0.)
+            }
+            ShapeKind::Polygon(ref outlines) => {
+                let mut distance = f32::INFINITY;
+                let mut inside = false;
+                for outline in outlines
+                {
+                    let count = outline.len();
+                    for i in 0..count
+                    {
+                        let start = &outline[i];
+                        let end = &outline[(i + 1) % count];
+                        distance = distance.min(distance_to_segment(point, start, end));
+
+                        if (start.y > point.y) != (end.y > point.y)
+                        {
+                            let x = start.x + (point.y - start.y) / (end.y - start.y) * (end.x - start.x);
+                            if point.x < x
+                            {
+                                inside = !inside;
+                            }
+                        }
+                    }
+                }
+                if inside { -distance } else { distance }
+            }
+            ShapeKind::Offset(ref child, amount) => child.distance(point) - amount,
+            ShapeKind::Hull(ref support) => support.iter()
+                .map(|&(direction, value)| direction.dot(point) - value)
+                .fold(-f32::INFINITY, f32::max),
+        }
+    }
+
+    pub fn contains(&self, point: &Vec2) -> bool
+    {
+        if self.bounds_distance(point) > 0.
+        {
+            return false;
+        }
+
+        match self.kind
+        {
+            ShapeKind::Empty => false,
+            ShapeKind::Union(ref children) => children.iter().any(|child| child.contains(point)),
+            ShapeKind::Intersection(ref children) => children.iter().all(|child| child.contains(point)),
+            ShapeKind::Difference(ref main, ref cutouts) => {
+                main.contains(point) && !cutouts.iter().any(|cutout| cutout.contains(point))
+            }
+            ShapeKind::Transformed{ref inverse, ref child, ..} => {
+                child.contains(&inverse.apply_2d(point))
+            }
+            ShapeKind::Polygon(ref outlines) => {
+                outlines.iter().filter(|outline| outline_contains(outline, point)).count() % 2 == 1
+            }
+            _ => self.distance(point) <= 0.
+        }
+    }
+
+    /**
+      Points on the outline of the shape, used for computing hulls
+    */
+    fn points(&self) -> Vec<Vec2>
+    {
+        match self.kind
+        {
+            ShapeKind::Empty => vec!(),
+            ShapeKind::Union(ref children) => children.iter().flat_map(|child| child.points()).collect(),
+            ShapeKind::Intersection(ref children) => {
+                let bounds = self.bounds;
+                children.iter()
+                    .flat_map(|child| child.points())
+                    .filter(|p| bounds.expanded(1e-3).contains(&Vec3::new(p.x, p.y, 0.)))
+                    .collect()
+            }
+            ShapeKind::Difference(ref main, _) => main.points(),
+            ShapeKind::Transformed{ref forward, ref child, ..} => {
+                child.points().iter().map(|p| forward.apply_2d(p)).collect()
+            }
+            ShapeKind::Circle(radius) => circle_points(radius),
+            ShapeKind::Rectangle(size) => vec!(
+                Vec2::new(0., 0.),
+                Vec2::new(size.x, 0.),
+                Vec2::new(size.x, size.y),
+                Vec2::new(0., size.y)
+            ),
+            ShapeKind::Polygon(ref outlines) => outlines.iter().flat_map(|o| o.clone()).collect(),
+            ShapeKind::Offset(ref child, amount) => {
+                let child_points = child.points();
+                if amount <= 0.
+                {
+                    return child_points;
+                }
+                let circle = circle_points(amount);
+                child_points.iter()
+                    .flat_map(|p| circle.iter().map(move |c| p + c))
+                    .collect()
+            }
+            ShapeKind::Hull(ref support) => {
+                //The corners of the hull are the intersections of the lines
+                //of neighbouring support directions
+                let count = support.len();
+                (0..count).filter_map(|i| {
+                    let (d1, v1) = support[i];
+                    let (d2, v2) = support[(i + 1) % count];
+                    let determinant = d1.x * d2.y - d1.y * d2.x;
+                    if determinant.abs() < 1e-9
+                    {
+                        return None;
+                    }
+                    Some(Vec2::new(
+                        (v1 * d2.y - v2 * d1.y) / determinant,
+                        (d1.x * v2 - d2.x * v1) / determinant
+                    ))
+                }).collect()
+            }
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+//                              3d solids
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Debug)]
+enum SolidKind
+{
+    Empty,
+    Union(Vec<Solid>),
+    Intersection(Vec<Solid>),
+    Difference(Box<Solid>, Vec<Solid>),
+    Transformed{inverse: Transform, forward: Transform, scale: f32, child: Box<Solid>},
+    //Axis aligned box from the origin to the size
+    Cuboid(Vec3),
+    //Cone segment from z = 0 to height
+    Cylinder{height: f32, bottom_radius: f32, top_radius: f32},
+    Sphere(f32),
+    Extrusion{shape: Shape, height: f32},
+    //Rotation of a shape in the xz plane around the z axis
+    Revolution{shape: Shape, angle: f32},
+    Hull(Vec<(Vec3, f32)>),
+}
+
+/**
+  A solid that can be queried for distances and containment
+*/
+#[derive(Clone, Debug)]
+pub struct Solid
+{
+    kind: SolidKind,
+    bounds: BoundingBox,
+}
+
+impl Solid
+{
+    fn new(kind: SolidKind) -> Solid
+    {
+        let bounds = match kind
+        {
+            SolidKind::Empty => BoundingBox::empty(),
+            SolidKind::Union(ref children) => children.iter()
+                .fold(BoundingBox::empty(), |acc, child| acc.union(&child.bounds)),
+            SolidKind::Intersection(ref children) => {
+                let mut iter = children.iter();
+                match iter.next()
+                {
+                    Some(first) => iter.fold(first.bounds, |acc, child| acc.intersection(&child.bounds)),
+                    None => BoundingBox::empty()
+                }
+            }
+            SolidKind::Difference(ref main, _) => main.bounds,
+            SolidKind::Transformed{ref forward, ref child, ..} => child.bounds.transformed(forward),
+            SolidKind::Cuboid(size) => BoundingBox::new(
+                Vec3::new(size.x.min(0.), size.y.min(0.), size.z.min(0.)),
+                Vec3::new(size.x.max(0.), size.y.max(0.), size.z.max(0.))
+            ),
+            SolidKind::Cylinder{height, bottom_radius, top_radius} => {
+                let radius = bottom_radius.max(top_radius);
+                BoundingBox::new(
+                    Vec3::new(-radius, -radius, 0.),
+                    Vec3::new(radius, radius, height)
+                )
+            }
+            SolidKind::Sphere(radius) => BoundingBox::new(
+                Vec3::new(-radius, -radius, -radius),
+                Vec3::new(radius, radius, radius)
+            ),
+            SolidKind::Extrusion{ref shape, height} => {
+                if shape.bounds.is_empty()
+                {
+                    BoundingBox::empty()
+                }
+                else
+                {
+                    BoundingBox::new(
+                        Vec3::new(shape.bounds.min.x, shape.bounds.min.y, 0.),
+                        Vec3::new(shape.bounds.max.x, shape.bounds.max.y, height)
+                    )
+                }
+            }
+            SolidKind::Revolution{ref shape, ..} => {
+                if shape.bounds.is_empty()
+                {
+                    BoundingBox::empty()
+                }
+                else
+                {
+                    let radius = shape.bounds.max.x.abs().max(shape.bounds.min.x.abs());
+                    BoundingBox::new(
+                        Vec3::new(-radius, -radius, shape.bounds.min.y),
+                        Vec3::new(radius, radius, shape.bounds.max.y)
+                    )
+                }
+            }
+            SolidKind::Hull(ref support) => {
+                let mut bounds = BoundingBox::empty();
+                for &(direction, value) in support
+                {
+                    for i in 0..3
+                    {
+                        if direction[i] == 1. { bounds.max[i] = value; }
+                        if direction[i] == -1. { bounds.min[i] = -value; }
+                    }
+                }
+                bounds
+            }
+        };
+
+        Solid{kind, bounds}
+    }
+
+    fn empty() -> Solid
+    {
+        Solid::new(SolidKind::Empty)
+    }
+
+    fn hull(points: &[Vec3]) -> Solid
+    {
+        if points.is_empty()
+        {
+            return Solid::empty();
+        }
+
+        let support = sphere_directions(HULL_DIRECTIONS_3D).into_iter()
+            .map(|direction| {
+                let value = points.iter()
+                    .map(|p| direction.dot(p))
+                    .fold(-f32::INFINITY, f32::max);
+                (direction, value)
+            })
+            .collect();
+
+        Solid::new(SolidKind::Hull(support))
+    }
+
+    fn transformed(child: Solid, transform: Transform) -> Solid
+    {
+        match transform.inverse()
+        {
+            Some(inverse) => Solid::new(SolidKind::Transformed{
+                inverse,
+                forward: transform,
+                scale: transform.min_scale(),
+                child: Box::new(child)
+            }),
+            None => Solid::empty()
+        }
+    }
+
+    /**
+      Builds a solid from a scad object
+    */
+    pub fn from_object(object: &ScadObject) -> Result<Solid, EvalError>
+    {
+        Solid::from_code(&object.get_code())
+    }
+
+    /**
+      Builds a solid from scad code. All top level objects are unioned
+    */
+    pub fn from_code(code: &str) -> Result<Solid, EvalError>
+    {
+        let nodes = scad_tree::parse_nodes(code)?;
+        Solid::from_nodes(&nodes)
+    }
+
+    pub fn from_nodes(nodes: &[Node]) -> Result<Solid, EvalError>
+    {
+        let children = nodes.iter()
+            .map(build_solid)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Solid::new(SolidKind::Union(children)))
+    }
+
+    /**
+      Returns a copy of the solid moved by a transform
+    */
+    pub fn with_transform(&self, transform: Transform) -> Solid
+    {
+        Solid::transformed(self.clone(), transform)
+    }
+
+    pub fn bounds(&self) -> BoundingBox
+    {
+        self.bounds
+    }
+
+    /**
+      Approximate signed distance to the surface of the solid. Negative inside
+    */
+    pub fn distance(&self, point: &Vec3) -> f32
+    {
+        match self.kind
+        {
+            SolidKind::Empty => f32::INFINITY,
+            SolidKind::Union(ref children) => {
+                let mut result = f32::INFINITY;
+                for child in children
+                {
+                    if child.bounds.distance(point) < result
+                    {
+                        result = result.min(child.distance(point));
+                    }
+                }
+                result
+            }
+            SolidKind::Intersection(ref children) => children.iter()
+                .map(|child| child.distance(point))
+                .fold(-f32::INFINITY, f32::max),
+            SolidKind::Difference(ref main, ref cutouts) => {
+                let main_distance = main.distance(point);
+                let threshold = -main_distance;
+                let mut result = main_distance;
+                for cutout in cutouts
+                {
+                    let lower_bound = cutout.bounds.distance(point);
+                    if lower_bound > 0. && lower_bound >= threshold
+                    {
+                        continue;
+                    }
+                    result = result.max(-cutout.distance(point));
+                }
+                result
+            }
+            SolidKind::Transformed{ref inverse, scale, ref child, ..} => {
+                child.distance(&inverse.apply(point)) * scale
+            }
+            SolidKind::Cuboid(size) => {
+                let half = size / 2.;
+                let q = Vec3::new(
+                    (point.x - half.x).abs() - half.x.abs(),
+                    (point.y - half.y).abs() - half.y.abs(),
+                    (point.z - half.z).abs() - half.z.abs()
+                );
+                let outside = Vec3::new(q.x.max(0.), q.y.max(0.), q.z.max(0.)).norm();
+                outside + q.x.max(q.y).max(q.z).min(0.)
+            }
+            SolidKind::Cylinder{height, bottom_radius, top_radius} => {
+                let t = if height == 0. { 0. } else { (point.z / height).clamp(0., 1.) };
+                let radius = bottom_radius + (top_radius - bottom_radius) * t;
+                let radial = (point.x * point.x + point.y * point.y).sqrt() - radius;
+                let z_distance = (-point.z).max(point.z - height);
+                combine_extruded_distance(radial, z_distance)
+            }
+            SolidKind::Sphere(radius) => point.norm() - radius,
+            SolidKind::Extrusion{ref shape, height} => {
+                let shape_distance = shape.distance(&Vec2::new(point.x, point.y));
+                let z_distance = (-point.z).max(point.z - height);
+                combine_extruded_distance(shape_distance, z_distance)
+            }
+            SolidKind::Revolution{ref shape, angle} => {
+                let radius = (point.x * point.x + point.y * point.y).sqrt();
+                let shape_distance = shape.distance(&Vec2::new(radius, point.z));
+
+                let mut point_angle = point.y.atan2(point.x).to_degrees();
+                if point_angle < 0.
+                {
+                    point_angle += 360.;
+                }
+                if point_angle <= angle
+                {
+                    shape_distance
+                }
+                else
+                {
+                    let gap = (point_angle - angle).min(360. - point_angle);
+                    shape_distance.max(radius * gap.to_radians().sin().abs())
+                }
+            }
+            SolidKind::Hull(ref support) => support.iter()
+                .map(|&(direction, value)| direction.dot(point) - value)
+                .fold(-f32::INFINITY, f32::max),
+        }
+    }
+
+    /**
+      Returns the outwards pointing normal of the surface closest to a
+      point. The normal is zero where the distance doesn't change, like at
+      the middle of a solid
+    */
+    pub fn normal(&self, point: &Vec3) -> Vec3
+    {
+        let axis = |x, y, z| {
+            let offset = Vec3::new(x, y, z) * NORMAL_STEP;
+            self.distance(&(point + offset)) - self.distance(&(point - offset))
+        };
+        let gradient = Vec3::new(axis(1., 0., 0.), axis(0., 1., 0.), axis(0., 0., 1.));
+
+        let length = gradient.norm();
+        if length == 0. { gradient } else { gradient / length }
+    }
+
+    pub fn contains(&self, point: &Vec3) -> bool
+    {
+        if !self.bounds.contains(point)
+        {
+            return false;
+        }
+
+        match self.kind
+        {
+            SolidKind::Empty => false,
+            SolidKind::Union(ref children) => children.iter().any(|child| child.contains(point)),
+            SolidKind::Intersection(ref children) => children.iter().all(|child| child.contains(point)),
+            SolidKind::Difference(ref main, ref cutouts) => {
+                main.contains(point) && !cutouts.iter().any(|cutout| cutout.contains(point))
+            }
+            SolidKind::Transformed{ref inverse, ref child, ..} => {
+                child.contains(&inverse.apply(point))
+            }
+            SolidKind::Extrusion{ref shape, height} => {
+                point.z >= 0. && point.z <= height && shape.contains(&Vec2::new(point.x, point.y))
+            }
+            _ => self.distance(point) <= 0.
+        }
+    }
+
+    /**
+      Points on the surface of the solid, used for computing hulls
+    */
+    fn points(&self) -> Vec<Vec3>
+    {
+        match self.kind
+        {
+            SolidKind::Empty => vec!(),
+            SolidKind::Union(ref children) => children.iter().flat_map(|child| child.points()).collect(),
+            SolidKind::Intersection(ref children) => {
+                let bounds = self.bounds.expanded(1e-3);
+                children.iter()
+                    .flat_map(|child| child.points())
+                    .filter(|p| bounds.contains(p))
+                    .collect()
+            }
+            SolidKind::Difference(ref main, _) => main.points(),
+            SolidKind::Transformed{ref forward, ref child, ..} => {
+                child.points().iter().map(|p| forward.apply(p)).collect()
+            }
+            SolidKind::Cuboid(size) => BoundingBox::new(Vec3::new(0., 0., 0.), size).corners(),
+            SolidKind::Cylinder{height, bottom_radius, top_radius} => {
+                let bottom = circle_points(bottom_radius).into_iter()
+                    .map(|p| Vec3::new(p.x, p.y, 0.));
+                let top = circle_points(top_radius).into_iter()
+                    .map(|p| Vec3::new(p.x, p.y, height));
+                bottom.chain(top).collect()
+            }
+            SolidKind::Sphere(radius) => {
+                sphere_directions(CIRCLE_SEGMENTS * 4).into_iter()
+                    .map(|direction| direction * radius)
+                    .collect()
+            }
+            SolidKind::Extrusion{ref shape, height} => {
+                shape.points().iter()
+                    .flat_map(|p| vec!(Vec3::new(p.x, p.y, 0.), Vec3::new(p.x, p.y, height)))
+                    .collect()
+            }
+            SolidKind::Revolution{ref shape, angle} => {
+                let profile = shape.points();
+                let steps = CIRCLE_SEGMENTS / 2;
+                (0..steps + 1).flat_map(|i| {
+                    let a = (angle * i as f32 / steps as f32).to_radians();
+                    profile.iter()
+                        .map(|p| Vec3::new(p.x * a.cos(), p.x * a.sin(), p.y))
+                        .collect::<Vec<_>>()
+                }).collect()
+            }
+            SolidKind::Hull(ref support) => {
+                //Approximation, the exact corners are not needed for the
+                //hulls that are nested in other hulls
+                let bounds = self.bounds;
+                support.iter()
+                    .map(|&(direction, value)| {
+                        let center = bounds.center();
+                        let distance = value - direction.dot(&center);
+                        center + direction * distance
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /**
+      Returns the centres of all the cells in a grid of the specified
+      resolution that are inside the solid
+    */
+    pub fn sample_points(&self, resolution: f32) -> Vec<Vec3>
+    {
+        self.sample_points_in(&self.bounds, resolution)
+    }
+
+    /**
+      Like `sample_points` but only samples the part of the solid that is
+      inside the bounding box
+    */
+    pub fn sample_points_in(&self, bounds: &BoundingBox, resolution: f32) -> Vec<Vec3>
+    {
+        sample_grid(&self.bounds.intersection(bounds), resolution)
+            .into_iter()
+            .filter(|point| self.contains(point))
+            .collect()
+    }
+
+    /**
+      Estimates the volume, centroid and second moments of the solid by
+      sampling it on a grid
+    */
+    pub fn volume_properties(&self, resolution: f32) -> VolumeProperties
+    {
+        VolumeProperties::from_points(&self.sample_points(resolution), resolution.powi(3))
+    }
+}
+
+/**
+  Returns the centres of the cells of a grid covering the bounding box
+*/
+pub fn sample_grid(bounds: &BoundingBox, resolution: f32) -> Vec<Vec3>
+{
+    if bounds.is_empty() || resolution <= 0.
+    {
+        return vec!();
+    }
+
+    let size = bounds.size();
+    let counts = [
+        (size.x / resolution).ceil().max(1.) as usize,
+        (size.y / resolution).ceil().max(1.) as usize,
+        (size.z / resolution).ceil().max(1.) as usize,
+    ];
+
+    //Center the grid in the bounding box
+    let start = bounds.center() - Vec3::new(
+        counts[0] as f32 * resolution / 2.,
+        counts[1] as f32 * resolution / 2.,
+        counts[2] as f32 * resolution / 2.
+    ) + Vec3::new(resolution, resolution, resolution) / 2.;
+
+    let mut result = Vec::with_capacity(counts[0] * counts[1] * counts[2]);
+    for x in 0..counts[0]
+    {
+        for y in 0..counts[1]
+        {
+            for z in 0..counts[2]
+            {
+                result.push(start + Vec3::new(x as f32, y as f32, z as f32) * resolution);
+            }
+        }
+    }
+    result
+}
+
+/**
+  Volume integrals of a solid
+*/
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct VolumeProperties
+{
+    pub volume: f32,
+    pub centroid: Vec3,
+    //Integral of r * r^T over the volume, relative to the origin
+    pub second_moment: na::Matrix3<f32>,
+}
+
+impl VolumeProperties
+{
+    pub fn zero() -> VolumeProperties
+    {
+        VolumeProperties{
+            volume: 0.,
+            centroid: Vec3::new(0., 0., 0.),
+            second_moment: na::Matrix3::zeros(),
+        }
+    }
+
+    pub fn from_points(points: &[Vec3], cell_volume: f32) -> VolumeProperties
+    {
+        if points.is_empty()
+        {
+            return VolumeProperties::zero();
+        }
+
+        let mut sum = [0f64; 3];
+        let mut second = [[0f64; 3]; 3];
+        for point in points
+        {
+            for i in 0..3
+            {
+                sum[i] += point[i] as f64;
+                for j in 0..3
+                {
+                    second[i][j] += point[i] as f64 * point[j] as f64;
+                }
+            }
+        }
+
+        let count = points.len() as f64;
+        let centroid = Vec3::new(
+            (sum[0] / count) as f32,
+            (sum[1] / count) as f32,
+            (sum[2] / count) as f32
+        );
+        let mut second_moment = na::Matrix3::zeros();
+        for i in 0..3
+        {
+            for j in 0..3
+            {
+                second_moment[(i, j)] = (second[i][j] * cell_volume as f64) as f32;
+            }
+        }
+
+        VolumeProperties{
+            volume: (count * cell_volume as f64) as f32,
+            centroid,
+            second_moment,
+        }
+    }
+}
+
+
+////////////////////////////////////////////////////////////////////////////////
+//                      Building solids from scad trees
+////////////////////////////////////////////////////////////////////////////////
+
+fn number_argument(node: &Node, name: &str, position: usize) -> Option<f32>
+{
+    node.argument(name, position).and_then(|v| v.as_number())
+}
+
+fn bool_argument(node: &Node, name: &str, position: usize) -> bool
+{
+    node.argument(name, position).and_then(|v| v.as_bool()).unwrap_or(false)
+}
+
+fn vector3(value: &Value, default: f32) -> Option<Vec3>
+{
+    match *value
+    {
+        Value::Number(n) => Some(Vec3::new(n, n, n)),
+        Value::Vector(_) => {
+            let numbers = value.as_numbers()?;
+            let get = |i: usize| numbers.get(i).cloned().unwrap_or(default);
+            Some(Vec3::new(get(0), get(1), get(2)))
+        }
+        _ => None
+    }
+}
+
+/**
+  Reads a radius from the r or d arguments or the positional argument
+  at `position`
+*/
+fn radius_argument(node: &Node, radius_name: &str, diameter_name: &str, position: Option<usize>)
+    -> Option<f32>
+{
+    if let Some(diameter) = node.named_argument(diameter_name).and_then(|v| v.as_number())
+    {
+        return Some(diameter / 2.);
+    }
+    match position
+    {
+        Some(position) => number_argument(node, radius_name, position),
+        None => node.named_argument(radius_name).and_then(|v| v.as_number())
+    }
+}
+
+/**
+  Returns the transform of a transformation node or None if the node is
+  not a transformation
+*/
+fn node_transform(node: &Node) -> Result<Option<Transform>, EvalError>
+{
+    let vector_argument = |default: f32| {
+        node.argument("v", 0)
+            .and_then(|v| vector3(v, default))
+            .ok_or_else(|| EvalError::new(&format!("Invalid arguments to {}", node.name)))
+    };
+
+    let result = match node.name.as_str()
+    {
+        "translate" => Transform::translation(vector_argument(0.)?),
+        "scale" => Transform::scale(vector_argument(1.)?),
+        "mirror" => Transform::mirror(vector_argument(0.)?),
+        "rotate" => {
+            match node.argument("a", 0)
+            {
+                Some(&Value::Number(angle)) => {
+                    let axis = node.argument("v", 1)
+                        .and_then(|v| vector3(v, 0.))
+                        .unwrap_or(Vec3::new(0., 0., 1.));
+                    Transform::rotation(angle, axis)
+                }
+                Some(value @ &Value::Vector(_)) => {
+                    let angles = vector3(value, 0.)
+                        .ok_or_else(|| EvalError::new("Invalid rotation angles"))?;
+                    Transform::rotation(angles.z, Vec3::new(0., 0., 1.))
+                        .then(&Transform::rotation(angles.y, Vec3::new(0., 1., 0.)))
+                        .then(&Transform::rotation(angles.x, Vec3::new(1., 0., 0.)))
+                }
+                _ => return Err(EvalError::new("Invalid arguments to rotate"))
+            }
+        }
+        "multmatrix" => {
+            let rows = match node.argument("m", 0)
+            {
+                Some(Value::Vector(rows)) => rows.iter()
+                    .map(|row| row.as_numbers())
+                    .collect::<Option<Vec<_>>>(),
+                _ => None
+            }.ok_or_else(|| EvalError::new("Invalid arguments to multmatrix"))?;
+
+            let get = |r: usize, c: usize| rows.get(r).and_then(|row| row.get(c)).cloned()
+                .unwrap_or(if r == c { 1. } else { 0. });
+
+            Transform{
+                linear: na::Matrix3::new(
+                    get(0, 0), get(0, 1), get(0, 2),
+                    get(1, 0), get(1, 1), get(1, 2),
+                    get(2, 0), get(2, 1), get(2, 2)
+                ),
+                translation: Vec3::new(get(0, 3), get(1, 3), get(2, 3)),
+            }
+        }
+        _ => return Ok(None)
+    };
+    Ok(Some(result))
+}
+
+fn build_solid(node: &Node) -> Result<Solid, EvalError>
+{
+    //Things that are disabled or only for debugging do not add geometry
+    if node.modifier == Some('*') || node.modifier == Some('%')
+    {
+        return Ok(Solid::empty());
+    }
+
+    let children = || node.children.iter().map(build_solid).collect::<Result<Vec<_>, _>>();
+
+    if let Some(transform) = node_transform(node)?
+    {
+        return Ok(Solid::transformed(Solid::new(SolidKind::Union(children()?)), transform));
+    }
+
+    let result = match node.name.as_str()
+    {
+        "union" | "group" | "color" | "render" => Solid::new(SolidKind::Union(children()?)),
+        "intersection" => Solid::new(SolidKind::Intersection(children()?)),
+        "difference" => {
+            let mut children = children()?;
+            if children.is_empty()
+            {
+                Solid::empty()
+            }
+            else
+            {
+                let main = children.remove(0);
+                Solid::new(SolidKind::Difference(Box::new(main), children))
+            }
+        }
+        "hull" => {
+            let points = children()?.iter().flat_map(|child| child.points()).collect::<Vec<_>>();
+            Solid::hull(&points)
+        }
+        "cube" => {
+            let size = node.argument("size", 0)
+                .and_then(|v| vector3(v, 0.))
+                .unwrap_or(Vec3::new(1., 1., 1.));
+            let cube = Solid::new(SolidKind::Cuboid(size));
+            if bool_argument(node, "center", 1)
+            {
+                Solid::transformed(cube, Transform::translation(-size / 2.))
+            }
+            else
+            {
+                cube
+            }
+        }
+        "cylinder" => {
+            let height = number_argument(node, "h", 0).unwrap_or(1.);
+            let radius = radius_argument(node, "r", "d", None).unwrap_or(1.);
+            let bottom_radius = radius_argument(node, "r1", "d1", Some(1)).unwrap_or(radius);
+            let top_radius = radius_argument(node, "r2", "d2", Some(2)).unwrap_or(radius);
+            let cylinder = Solid::new(SolidKind::Cylinder{height, bottom_radius, top_radius});
+
+            if bool_argument(node, "center", 3)
+            {
+                Solid::transformed(cylinder, Transform::translation(Vec3::new(0., 0., -height / 2.)))
+            }
+            else
+            {
+                cylinder
+            }
+        }
+        "sphere" => {
+            let radius = radius_argument(node, "r", "d", Some(0)).unwrap_or(1.);
+            Solid::new(SolidKind::Sphere(radius))
+        }
+        "linear_extrude" => {
+            let twist = number_argument(node, "twist", 3).unwrap_or(0.);
+            let scale = node.argument("scale", 5).and_then(|v| vector3(v, 1.));
+            if twist != 0. || scale.map(|scale| scale.x != 1. || scale.y != 1.).unwrap_or(false)
+            {
+                return Err(EvalError::new("Twisted and scaled extrusions are not supported"));
+            }
+
+            let height = number_argument(node, "height", 0).unwrap_or(1.);
+            let shape = shape_union(node)?;
+            let extruded = Solid::new(SolidKind::Extrusion{shape, height});
+
+            if bool_argument(node, "center", 1)
+            {
+                Solid::transformed(extruded, Transform::translation(Vec3::new(0., 0., -height / 2.)))
+            }
+            else
+            {
+                extruded
+            }
+        }
+        "rotate_extrude" => {
+            let angle = node.named_argument("angle")
+                .and_then(|v| v.as_number())
+                .unwrap_or(360.)
+                .min(360.);
+            Solid::new(SolidKind::Revolution{shape: shape_union(node)?, angle})
+        }
+        //2d objects are ignored in 3d contexts like they are in openscad
+        "circle" | "square" | "polygon" | "offset" | "text" => Solid::empty(),
+        other => return Err(EvalError::new(&format!("Unsupported module '{}'", other)))
+    };
+    Ok(result)
+}
+
+fn shape_union(node: &Node) -> Result<Shape, EvalError>
+{
+    let children = node.children.iter().map(build_shape).collect::<Result<Vec<_>, _>>()?;
+    Ok(Shape::new(ShapeKind::Union(children)))
+}
+
+fn build_shape(node: &Node) -> Result<Shape, EvalError>
+{
+    if node.modifier == Some('*') || node.modifier == Some('%')
+    {
+        return Ok(Shape::empty());
+    }
+
+    let children = || node.children.iter().map(build_shape).collect::<Result<Vec<_>, _>>();
+
+    if let Some(transform) = node_transform(node)?
+    {
+        //Only the part of the transform that acts in the xy plane matters
+        let mut planar = transform;
+        planar.translation.z = 0.;
+        for i in 0..3
+        {
+            planar.linear[(2, i)] = if i == 2 { 1. } else { 0. };
+            planar.linear[(i, 2)] = if i == 2 { 1. } else { 0. };
+        }
+
+        let child = Shape::new(ShapeKind::Union(children()?));
+        return Ok(match planar.inverse()
+        {
+            Some(inverse) => Shape::new(ShapeKind::Transformed{
+                inverse,
+                forward: planar,
+                scale: planar.min_scale(),
+                child: Box::new(child)
+            }),
+            None => Shape::empty()
+        });
+    }
+
+    let result = match node.name.as_str()
+    {
+        "union" | "group" | "color" | "render" => Shape::new(ShapeKind::Union(children()?)),
+        "intersection" => Shape::new(ShapeKind::Intersection(children()?)),
+        "difference" => {
+            let mut children = children()?;
+            if children.is_empty()
+            {
+                Shape::empty()
+            }
+            else
+            {
+                let main = children.remove(0);
+                Shape::new(ShapeKind::Difference(Box::new(main), children))
+            }
+        }
+        "hull" => {
+            let points = children()?.iter().flat_map(|child| child.points()).collect::<Vec<_>>();
+            Shape::hull(&points)
+        }
+        "offset" => {
+            let child = Shape::new(ShapeKind::Union(children()?));
+            let (amount, corners) = match node.named_argument("delta").and_then(|v| v.as_number())
+            {
+                Some(delta) if bool_argument(node, "chamfer", 2) => (delta, OffsetCorners::Chamfered),
+                Some(delta) => (delta, OffsetCorners::Sharp),
+                None => (number_argument(node, "r", 0).unwrap_or(0.), OffsetCorners::Round)
+            };
+            Shape::offset(child, amount, corners)
+        }
+        "circle" => {
+            let radius = radius_argument(node, "r", "d", Some(0)).unwrap_or(1.);
+            Shape::new(ShapeKind::Circle(radius))
+        }
+        "square" => {
+            let size = node.argument("size", 0)
+                .and_then(|v| vector3(v, 0.))
+                .map(|v| Vec2::new(v.x, v.y))
+                .unwrap_or(Vec2::new(1., 1.));
+            let square = Shape::new(ShapeKind::Rectangle(size));
+            if bool_argument(node, "center", 1)
+            {
+                let translation = Transform::translation(Vec3::new(-size.x / 2., -size.y / 2., 0.));
+                Shape::new(ShapeKind::Transformed{
+                    inverse: translation.inverse().unwrap_or(Transform::identity()),
+                    forward: translation,
+                    scale: 1.,
+                    child: Box::new(square)
+                })
+            }
+            else
+            {
+                square
+            }
+        }
+        "polygon" => {
+            let points = match node.argument("points", 0)
+            {
+                Some(Value::Vector(points)) => points.iter()
+                    .map(|p| p.as_numbers().and_then(|p| {
+                        if p.len() >= 2 { Some(Vec2::new(p[0], p[1])) } else { None }
+                    }))
+                    .collect::<Option<Vec<_>>>(),
+                _ => None
+            }.ok_or_else(|| EvalError::new("Invalid polygon points"))?;
+
+            let outlines = match node.argument("paths", 1)
+            {
+                Some(Value::Vector(paths)) => paths.iter()
+                    .map(|path| {
+                        path.as_numbers()
+                            .ok_or_else(|| EvalError::new("Invalid polygon path"))?
+                            .iter()
+                            .map(|&i| points.get(i as usize).cloned()
+                                 .ok_or_else(|| EvalError::new("Polygon path index out of range")))
+                            .collect::<Result<Vec<_>, _>>()
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+                _ => vec!(points)
+            };
+            Shape::new(ShapeKind::Polygon(outlines))
+        }
+        //3d objects are ignored in 2d contexts like they are in openscad
+        "cube" | "cylinder" | "sphere" | "linear_extrude" | "rotate_extrude" => Shape::empty(),
+        other => return Err(EvalError::new(&format!("Unsupported 2d module '{}'", other)))
+    };
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn shape(code: &str) -> Shape
+    {
+        let children = scad_tree::parse_nodes(code).unwrap().iter()
+            .map(build_shape)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        Shape::new(ShapeKind::Union(children))
+    }
+
+    fn solid(code: &str) -> Solid
+    {
+        Solid::from_code(code).unwrap()
+    }
+
+    #[test]
+    fn volume_properties_of_a_cube()
+    {
+        let properties = solid("cube([2,3,4]);").volume_properties(0.1);
+        assert!((properties.volume - 24.).abs() < 0.1, "{}", properties.volume);
+        assert!((properties.centroid - Vec3::new(1., 1.5, 2.)).norm() < 1e-3);
+    }
+
+    #[test]
+    fn booleans_combine_children()
+    {
+        let cube = "cube(10,center=true);";
+        let cylinder = "cylinder(h=20,r=2,center=true);";
+        let union = solid(&format!("union(){{{}translate([10,0,0]){}}}", cube, cylinder));
+        let difference = solid(&format!("difference(){{{}{}}}", cube, cylinder));
+        let intersection = solid(&format!("intersection(){{{}{}}}", cube, cylinder));
+
+        assert!(union.contains(&Vec3::new(0., 0., 0.)));
+        assert!(union.contains(&Vec3::new(11., 0., 8.)));
+        assert!(!union.contains(&Vec3::new(7., 0., 0.)));
+
+        assert!(!difference.contains(&Vec3::new(0., 0., 0.)));
+        assert!(difference.contains(&Vec3::new(4., 4., 4.)));
+
+        assert!(intersection.contains(&Vec3::new(1., 1., 4.)));
+        assert!(!intersection.contains(&Vec3::new(4., 4., 4.)));
+        assert!(!intersection.contains(&Vec3::new(0., 0., 6.)));
+    }
+
+    #[test]
+    fn transforms_move_children()
+    {
+        let moved = solid("translate([10,0,0])rotate([0,0,90])cube([2,1,1]);");
+        assert!(moved.contains(&Vec3::new(9.5, 1.5, 0.5)));
+        assert!(!moved.contains(&Vec3::new(10.5, 0.5, 0.5)));
+
+        let rotated = solid("rotate(a=90,v=[1,0,0])cube([1,1,3]);");
+        assert!(rotated.contains(&Vec3::new(0.5, -2.5, 0.5)));
+
+        assert!(solid("mirror([1,0,0])cube(1);").contains(&Vec3::new(-0.5, 0.5, 0.5)));
+        assert!(solid("scale([2,1,1])cube(1);").contains(&Vec3::new(1.5, 0.5, 0.5)));
+        assert!(solid("multmatrix(m=[[1,0,0,5],[0,1,0,0],[0,0,1,0]])cube(1);")
+            .contains(&Vec3::new(5.5, 0.5, 0.5)));
+
+        let bounds = moved.bounds();
+        assert!((bounds.min - Vec3::new(9., 0., 0.)).norm() < 1e-5);
+        assert!((bounds.max - Vec3::new(10., 2., 1.)).norm() < 1e-5);
+    }
+
+    #[test]
+    fn extrusions_sweep_shapes()
+    {
+        let extruded = solid("linear_extrude(height=5,center=true)square([2,2],center=true);");
+        assert!(extruded.contains(&Vec3::new(0.9, 0.9, 2.4)));
+        assert!(!extruded.contains(&Vec3::new(0., 0., 2.6)));
+        assert!(!extruded.contains(&Vec3::new(1.1, 0., 0.)));
+
+        let torus = solid("rotate_extrude()translate([5,0])circle(r=1);");
+        assert!(torus.contains(&Vec3::new(5., 0., 0.)));
+        assert!(torus.contains(&Vec3::new(0., -5., 0.5)));
+        assert!(!torus.contains(&Vec3::new(0., 0., 0.)));
+
+        let half = solid("rotate_extrude(angle=180)translate([5,0])circle(r=1);");
+        assert!(half.contains(&Vec3::new(0., 5., 0.)));
+        assert!(!half.contains(&Vec3::new(0., -5., 0.)));
+
+        let volume = solid("linear_extrude(height=2)circle(r=3);").volume_properties(0.05).volume;
+        assert!((volume - PI * 18.).abs() < 0.5, "{}", volume);
+    }
+
+    #[test]
+    fn twisted_and_scaled_extrusions_are_rejected()
+    {
+        assert!(Solid::from_code("linear_extrude(height=5,twist=90)square(1);").is_err());
+        assert!(Solid::from_code("linear_extrude(height=5,scale=2)square(1);").is_err());
+        assert!(Solid::from_code("linear_extrude(height=5,twist=0,scale=[1,1])square(1);").is_ok());
+    }
+
+    #[test]
+    fn unknown_modules_are_errors()
+    {
+        assert!(Solid::from_code("minkowski(){cube(1);sphere(1);}").is_err());
+        assert!(Solid::from_code("cube(1").is_err());
+    }
+
+    #[test]
+    fn normals_point_out_of_the_nearest_face()
+    {
+        let cube = solid("cube(10);");
+        let close = |a: Vec3, b: Vec3| (a - b).norm() < 1e-3;
+        assert!(close(cube.normal(&Vec3::new(5., 5., 9.9)), Vec3::new(0., 0., 1.)));
+        assert!(close(cube.normal(&Vec3::new(0.2, 5., 4.)), Vec3::new(-1., 0., 0.)));
+        assert!(close(cube.normal(&Vec3::new(5., 12., 5.)), Vec3::new(0., 1., 0.)));
+    }
+
+    #[test]
+    fn hulls_fill_between_children()
+    {
+        let hull = solid("hull(){cube(1);translate([5,0,0])cube(1);}");
+        assert!(hull.contains(&Vec3::new(3., 0.5, 0.5)));
+        assert!(!hull.contains(&Vec3::new(3., 1.5, 0.5)));
+    }
+
+    #[test]
+    fn offsets_follow_the_corner_style()
+    {
+        let square = "square(10,center=true);";
+        let round = shape(&format!("offset(r=2){}", square));
+        let sharp = shape(&format!("offset(delta=2){}", square));
+        let chamfered = shape(&format!("offset(delta=2,chamfer=true){}", square));
+
+        for offset in &[&round, &sharp, &chamfered]
+        {
+            assert!(offset.contains(&Vec2::new(6.9, 0.)));
+            assert!(!offset.contains(&Vec2::new(7.1, 0.)));
+        }
+
+        //The corner is rounded, kept or cut 2 from the corner along the
+        //diagonal
+        assert!(!round.contains(&Vec2::new(6.5, 6.5)));
+        assert!(sharp.contains(&Vec2::new(6.9, 6.9)));
+        assert!(chamfered.contains(&Vec2::new(6.9, 5.5)));
+        assert!(!chamfered.contains(&Vec2::new(6.5, 6.5)));
+    }
+
+    #[test]
+    fn offsets_in_and_back_out_round_the_corners()
+    {
+        let rounded = shape("offset(r=2)offset(delta=-2)square(10,center=true);");
+        assert!(rounded.contains(&Vec2::new(4.9, 0.)));
+        assert!(rounded.contains(&Vec2::new(4.2, 4.2)));
+        assert!(!rounded.contains(&Vec2::new(4.8, 4.8)));
+    }
+
+    #[test]
+    fn offsets_of_combined_shapes_keep_sharp_corners()
+    {
+        let l_shape = "union(){square([10,4]);square([4,10]);}";
+
+        let grown = shape(&format!("offset(delta=1){}", l_shape));
+        assert!(grown.contains(&Vec2::new(10.8, -0.8)));
+        assert!(grown.contains(&Vec2::new(4.9, 4.9)));
+        assert!(!grown.contains(&Vec2::new(5.2, 5.2)));
+
+        //The inner corner of the L stays sharp when shrinking with delta but
+        //gets rounded when shrinking with a radius
+        let shrunk = shape(&format!("offset(delta=-1){}", l_shape));
+        let shrunk_round = shape(&format!("offset(r=-1){}", l_shape));
+        assert!(shrunk.contains(&Vec2::new(2., 8.)));
+        assert!(!shrunk.contains(&Vec2::new(0.5, 5.)));
+        assert!(!shrunk.contains(&Vec2::new(3.2, 3.2)));
+        assert!(shrunk_round.contains(&Vec2::new(3.2, 3.2)));
+    }
+}
@@ -0,0 +1,323 @@
+/*!
+  Golden files that the generated code of every printed part is compared
+  against, to catch changes to parts that were not meant to change.
+
+  The code is stored in a normalised form where numbers are rounded so that
+  the files don't change with tiny floating point differences. Arguments are
+  named, sorted and left out when they have their default value, since
+  versions of the scad crate write the same module call differently, some
+  pass arguments by position and some spell out every default. Intentional
+  changes are accepted by running `cargo run -- bless` which rewrites the
+  files from the current parts.
+*/
+
+use assembly::{Assembly, PrintPart};
+use scad_tree::{self, Argument, Node, Value};
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+//Numbers are rounded to this many decimals
+const DECIMALS: i32 = 4;
+const EXTENSION: &str = "scad";
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Mismatch
+{
+    //A part without a golden file
+    Missing(String),
+    //A part whose code differs from the golden file, with the first line
+    //that differs
+    Changed(String, usize),
+    //A golden file without a part
+    Unused(String),
+}
+
+impl fmt::Display for Mismatch
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self
+        {
+            Mismatch::Missing(ref name) => write!(f, "{} has no golden file", name),
+            Mismatch::Changed(ref name, line) => {
+                write!(f, "{} differs from its golden file from line {}", name, line)
+            }
+            Mismatch::Unused(ref name) => write!(f, "{} is a golden file without a part", name),
+        }
+    }
+}
+
+/**
+  Returns the directory that the golden files are checked in to
+*/
+pub fn golden_directory() -> PathBuf
+{
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+}
+
+fn round_value(value: &Value) -> Value
+{
+    let factor = 10f32.powi(DECIMALS);
+    match *value
+    {
+        //Adding 0 turns -0 into 0
+        Value::Number(number) => Value::Number((number * factor).round() / factor + 0.),
+        Value::Vector(ref values) => Value::Vector(values.iter().map(round_value).collect()),
+        ref other => other.clone(),
+    }
+}
+
+/**
+  Returns the names of the arguments that a module takes by position, in
+  the order that OpenSCAD takes them
+*/
+fn positional_names(module: &str) -> &'static [&'static str]
+{
+    match module
+    {
+        "translate" | "scale" | "mirror" => &["v"],
+        "rotate" => &["a", "v"],
+        "cube" | "square" => &["size", "center"],
+        "cylinder" => &["h", "r1", "r2", "center"],
+        "sphere" | "circle" => &["r"],
+        "polygon" => &["points", "paths", "convexity"],
+        "polyhedron" => &["points", "faces", "convexity"],
+        "linear_extrude" => &["height", "center", "convexity", "twist", "slices", "scale"],
+        "rotate_extrude" => &["angle", "convexity"],
+        "offset" => &["r", "delta", "chamfer"],
+        "color" => &["c", "alpha"],
+        "import" => &["file"],
+        "projection" => &["cut"],
+        _ => &[],
+    }
+}
+
+/**
+  Returns true if a named argument can be left out without changing the
+  geometry, either because it has its default value or because it is only
+  a hint for rendering like `convexity`
+*/
+fn is_default(module: &str, name: &str, value: &Value) -> bool
+{
+    match (name, value)
+    {
+        //Older versions of the scad crate spell convexity as convecity
+        ("convexity", _) | ("convecity", _) => true,
+        ("paths", &Value::Undef) => true,
+        ("center", &Value::Bool(false)) | ("chamfer", &Value::Bool(false)) => true,
+        ("twist", &Value::Number(twist)) => twist == 0.,
+        ("slices", &Value::Number(slices)) => slices == 1.,
+        ("scale", &Value::Number(scale)) => module == "linear_extrude" && scale == 1.,
+        ("angle", &Value::Number(angle)) => module == "rotate_extrude" && angle == 360.,
+        _ => false,
+    }
+}
+
+fn normalise_arguments(node: &Node) -> Vec<Argument>
+{
+    let names = positional_names(&node.name);
+
+    let mut position = 0;
+    let mut result = vec!();
+    for argument in &node.arguments
+    {
+        let name = match argument.name
+        {
+            Some(ref name) => Some(name.clone()),
+            None => {
+                position += 1;
+                names.get(position - 1).map(|name| name.to_string())
+            }
+        };
+
+        if name.as_ref().is_some_and(|name| is_default(&node.name, name, &argument.value))
+        {
+            continue;
+        }
+        result.push(Argument{name, value: round_value(&argument.value)});
+    }
+
+    //Arguments that are still positional keep their order in front of the
+    //named ones
+    result.sort_by(|a, b| a.name.cmp(&b.name));
+    result
+}
+
+fn normalise(node: &Node) -> Node
+{
+    let mut result = node.clone();
+    result.arguments = normalise_arguments(node);
+    result.children = node.children.iter().map(normalise).collect();
+    result
+}
+
+/**
+  Returns the normalised form of OpenSCAD code
+*/
+fn normalise_code(code: &str) -> io::Result<String>
+{
+    let nodes = scad_tree::parse_nodes(code)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+    let mut result = String::new();
+    for node in &nodes
+    {
+        normalise(node).write_code(0, &mut result);
+    }
+    Ok(result)
+}
+
+/**
+  Returns the normalised code of a part
+*/
+pub fn render(part: &PrintPart) -> io::Result<String>
+{
+    normalise_code(&part.object.get_code())
+}
+
+fn golden_file(directory: &Path, name: &str) -> PathBuf
+{
+    directory.join(name).with_extension(EXTENSION)
+}
+
+/**
+  Writes the golden files of all the parts. Golden files of parts that no
+  longer exist are removed. Returns the names of the files that changed
+*/
+pub fn bless(assembly: &Assembly, directory: &Path) -> io::Result<Vec<String>>
+{
+    fs::create_dir_all(directory)?;
+
+    let mut changed = vec!();
+    for mismatch in compare(assembly, directory)?
+    {
+        match mismatch
+        {
+            Mismatch::Unused(name) => {
+                fs::remove_file(golden_file(directory, &name))?;
+                changed.push(name);
+            }
+            Mismatch::Missing(name) | Mismatch::Changed(name, _) => changed.push(name),
+        }
+    }
+
+    for part in assembly.get_print_parts()
+    {
+        fs::write(golden_file(directory, &part.name), render(&part)?)?;
+    }
+    Ok(changed)
+}
+
+/**
+  Compares the parts to their golden files
+*/
+pub fn compare(assembly: &Assembly, directory: &Path) -> io::Result<Vec<Mismatch>>
+{
+    let parts = assembly.get_print_parts();
+    let mut result = vec!();
+
+    for part in &parts
+    {
+        let path = golden_file(directory, &part.name);
+        if !path.exists()
+        {
+            result.push(Mismatch::Missing(part.name.clone()));
+            continue;
+        }
+
+        let expected = fs::read_to_string(path)?;
+        let actual = render(part)?;
+        if expected != actual
+        {
+            let line = expected.lines()
+                .zip(actual.lines())
+                .take_while(|&(a, b)| a == b)
+                .count() + 1;
+            result.push(Mismatch::Changed(part.name.clone(), line));
+        }
+    }
+
+    if directory.exists()
+    {
+        for entry in fs::read_dir(directory)?
+        {
+            let path = entry?.path();
+            if path.extension().is_none_or(|extension| extension != EXTENSION)
+            {
+                continue;
+            }
+
+            let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+            if !parts.iter().any(|part| part.name == name)
+            {
+                result.push(Mismatch::Unused(name));
+            }
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn parts_match_golden_files()
+    {
+        let mismatches = compare(&Assembly::new(), &golden_directory()).unwrap();
+
+        let messages = mismatches.iter().map(|m| m.to_string()).collect::<Vec<_>>();
+        assert!(
+            mismatches.is_empty(),
+            "{}\nRun `cargo run -- bless` if the changes are intentional",
+            messages.join("\n")
+        );
+    }
+
+    #[test]
+    fn numbers_are_rounded()
+    {
+        let value = Value::Vector(vec!(Value::Number(0.300_01), Value::Number(-0.00001)));
+        assert_eq!(round_value(&value), Value::Vector(vec!(Value::Number(0.3), Value::Number(0.))));
+    }
+
+    #[test]
+    fn code_from_every_scad_version_is_normalised_the_same()
+    {
+        let normalised = |code: &str| normalise_code(code).unwrap();
+
+        //What versions 1.0 and 1.1 of the scad crate write
+        let old = "linear_extrude(height=2,center=false,convecity=10,twist=0,slices=1)\
+            {offset(r=1,chamfer=false){polygon(points=[[0,0],[1,0],[0,1],],paths=undef,convexity=10);}}";
+        //What version 1.2 writes
+        let new = "linear_extrude(height=2,center=false,convexity=10,twist=0,slices=1)\
+            {offset(r=1,chamfer=false){polygon(points=[[0,0],[1,0],[0,1],],paths=undef,convexity=10);}}";
+        //The same geometry with the arguments by position and in another order
+        let positional = "linear_extrude(slices=1,height=2)\
+            {offset(1){polygon([[0,0],[1,0],[0,1]]);}}";
+
+        assert_eq!(normalised(old), normalised(new));
+        assert_eq!(normalised(positional), normalised(new));
+        assert_eq!(
+            normalised(new).replace(char::is_whitespace, ""),
+            "linear_extrude(height=2){offset(r=1){polygon(points=[[0,0],[1,0],[0,1]]);}}"
+        );
+    }
+
+    #[test]
+    fn arguments_that_change_the_geometry_are_kept()
+    {
+        let normalised = |code: &str| normalise_code(code).unwrap().replace(char::is_whitespace, "");
+
+        assert_eq!(
+            normalised("linear_extrude(height=2,center=true,convexity=10,twist=90,slices=1)cube(1);"),
+            "linear_extrude(center=true,height=2,twist=90){cube(size=1);}"
+        );
+        assert_eq!(normalised("offset(delta=1,chamfer=true)circle(r=1);"), "offset(chamfer=true,delta=1){circle(r=1);}");
+        assert_eq!(normalised("rotate(90,[0,0,1])cube(1);"), "rotate(a=90,v=[0,0,1]){cube(size=1);}");
+    }
+}
@@ -0,0 +1,521 @@
+/*!
+  History of the generated designs.
+
+  Every time the design is generated an entry is added to the history
+  directory. An entry is a directory with the generated code, the code of
+  each printed part, the parameters that it was generated from and a file
+  describing it: when it was made, the parts, the git commit of the
+  code if there is one and a hash of the generated code. Generating the same
+  code with the same parameters twice in a row does not add a new entry.
+*/
+
+use scad::*;
+
+use parameters::{ParameterError, ParameterSet};
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const ENTRY_FILE: &str = "entry.txt";
+const PARAMETER_FILE: &str = "parameters.txt";
+const CODE_FILE: &str = "out.scad";
+//Directory in an entry with the code of each part
+const PART_DIRECTORY: &str = "parts";
+//Detail of the stored code of the parts, like in the generated files
+const PART_DETAIL: i32 = 20;
+
+#[derive(Debug)]
+pub enum HistoryError
+{
+    Io(io::Error),
+    Parameter(ParameterError),
+    //An entry file without one of the fields, with the name of the entry
+    //and the field
+    MissingField(String, String),
+    //A field that could not be read, with the name of the entry, the field
+    //and its value
+    InvalidField(String, String, String),
+    //An entry without the code of one of its parts, with the name of the
+    //entry and the part. Entries from before the parts were stored have none
+    MissingPartCode(String, String),
+    NotFound(String),
+}
+
+impl fmt::Display for HistoryError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self
+        {
+            HistoryError::Io(ref e) => write!(f, "{}", e),
+            HistoryError::Parameter(ref e) => write!(f, "{}", e),
+            HistoryError::MissingField(ref entry, ref field) => {
+                write!(f, "History entry {} has no {}", entry, field)
+            }
+            HistoryError::InvalidField(ref entry, ref field, ref value) => {
+                write!(f, "History entry {} has an invalid {}: {}", entry, field, value)
+            }
+            HistoryError::MissingPartCode(ref entry, ref part) => {
+                write!(f, "History entry {} has no code for {}", entry, part)
+            }
+            HistoryError::NotFound(ref id) => write!(f, "There is no history entry {}", id),
+        }
+    }
+}
+
+impl From<io::Error> for HistoryError
+{
+    fn from(error: io::Error) -> HistoryError
+    {
+        HistoryError::Io(error)
+    }
+}
+
+impl From<ParameterError> for HistoryError
+{
+    fn from(error: ParameterError) -> HistoryError
+    {
+        HistoryError::Parameter(error)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Entry
+{
+    pub id: String,
+    //Seconds since the unix epoch
+    pub timestamp: u64,
+    pub parts: Vec<String>,
+    pub commit: Option<String>,
+    //Hash of the generated code
+    pub hash: String,
+}
+
+impl fmt::Display for Entry
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        let commit = self.commit.as_ref()
+            .map(|commit| commit.chars().take(10).collect::<String>())
+            .unwrap_or_else(|| "-".to_string());
+
+        write!(
+            f,
+            "{}  {}  {:<10}  {}  {}",
+            self.id,
+            format_time(self.timestamp),
+            commit,
+            self.hash,
+            self.parts.join(", ")
+        )
+    }
+}
+
+/**
+  Returns the directory that the history is stored in
+*/
+pub fn history_directory() -> PathBuf
+{
+    PathBuf::from("history")
+}
+
+/**
+  64 bit FNV-1a hash. The hash of the standard library is not guaranteed to
+  stay the same between compiler versions
+*/
+pub fn hash(text: &str) -> String
+{
+    let mut result: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in text.bytes()
+    {
+        result ^= byte as u64;
+        result = result.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{:016x}", result)
+}
+
+/**
+  Formats seconds since the unix epoch as an UTC date and time
+*/
+pub fn format_time(timestamp: u64) -> String
+{
+    let days = (timestamp / 86400) as i64;
+    let seconds = timestamp % 86400;
+
+    //Conversion from days to a date in the proleptic gregorian calendar
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year, month, day, seconds / 3600, seconds / 60 % 60, seconds % 60
+    )
+}
+
+/**
+  Returns the commit that the working directory is on, with a + at the end
+  if there are uncommitted changes. None if git is not available
+*/
+fn current_commit() -> Option<String>
+{
+    let output = Command::new("git").args(["rev-parse", "HEAD"]).output().ok()?;
+    if !output.status.success()
+    {
+        return None;
+    }
+    let commit = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    let dirty = Command::new("git").args(["status", "--porcelain", "--untracked-files=no"])
+        .output()
+        .map(|output| !output.stdout.is_empty())
+        .unwrap_or(false);
+
+    Some(if dirty { format!("{}+", commit) } else { commit })
+}
+
+fn read_entry(directory: &Path, id: &str) -> Result<Entry, HistoryError>
+{
+    let text = fs::read_to_string(directory.join(id).join(ENTRY_FILE))?;
+    let field = |name: &str| {
+        text.lines()
+            .filter_map(|line| {
+                let mut parts = line.splitn(2, ':');
+                match (parts.next(), parts.next())
+                {
+                    (Some(key), Some(value)) if key.trim() == name => Some(value.trim().to_string()),
+                    _ => None,
+                }
+            })
+            .next()
+            .ok_or_else(|| HistoryError::MissingField(id.to_string(), name.to_string()))
+    };
+
+    let timestamp = field("timestamp")?;
+    Ok(Entry{
+        id: id.to_string(),
+        timestamp: timestamp.parse().map_err(|_| {
+            HistoryError::InvalidField(id.to_string(), "timestamp".to_string(), timestamp.clone())
+        })?,
+        parts: field("parts")?
+            .split(',')
+            .map(|part| part.trim().to_string())
+            .filter(|part| !part.is_empty())
+            .collect(),
+        commit: field("commit").ok(),
+        hash: field("hash")?,
+    })
+}
+
+fn write_entry(directory: &Path, entry: &Entry) -> io::Result<()>
+{
+    let mut text = format!("timestamp: {}\n", entry.timestamp);
+    text.push_str(&format!("parts: {}\n", entry.parts.join(", ")));
+    if let Some(ref commit) = entry.commit
+    {
+        text.push_str(&format!("commit: {}\n", commit));
+    }
+    text.push_str(&format!("hash: {}\n", entry.hash));
+    fs::write(directory.join(&entry.id).join(ENTRY_FILE), text)
+}
+
+/**
+  Returns the ids of all the entries, oldest first
+*/
+fn entry_ids(directory: &Path) -> Result<Vec<String>, HistoryError>
+{
+    if !directory.exists()
+    {
+        return Ok(vec!());
+    }
+
+    let mut ids = vec!();
+    for dir_entry in fs::read_dir(directory)?
+    {
+        let dir_entry = dir_entry?;
+        if dir_entry.path().join(ENTRY_FILE).exists()
+        {
+            ids.push(dir_entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    //Ids are numbers that grow with every entry. They are padded to 4
+    //digits, so they have to be compared as numbers once there are more
+    ids.sort_by_key(|id| (id.parse::<u64>().unwrap_or(u64::MAX), id.clone()));
+    Ok(ids)
+}
+
+/**
+  Returns all the entries, oldest first
+*/
+pub fn list(directory: &Path) -> Result<Vec<Entry>, HistoryError>
+{
+    entry_ids(directory)?.iter().map(|id| read_entry(directory, id)).collect()
+}
+
+/**
+  Finds an entry by its id. `last` is the newest entry
+*/
+pub fn find(directory: &Path, id: &str) -> Result<Entry, HistoryError>
+{
+    let entries = list(directory)?;
+    let found = if id == "last"
+    {
+        entries.last()
+    }
+    else
+    {
+        //Leading zeros can be left out
+        let number = id.parse::<u64>().ok();
+        entries.iter().find(|entry| {
+            entry.id == id || (number.is_some() && entry.id.parse::<u64>().ok() == number)
+        })
+    };
+    found.cloned().ok_or_else(|| HistoryError::NotFound(id.to_string()))
+}
+
+pub fn load_code(directory: &Path, entry: &Entry) -> Result<String, HistoryError>
+{
+    Ok(fs::read_to_string(directory.join(&entry.id).join(CODE_FILE))?)
+}
+
+/**
+  Returns the code of a part the way that it is stored in an entry
+*/
+pub fn part_code(object: &ScadObject) -> String
+{
+    let mut file = ScadFile::new();
+    file.set_detail(PART_DETAIL);
+    file.add_object(object.clone());
+    file.get_code()
+}
+
+/**
+  Returns the code of a part of an entry. None if the entry has no part with
+  the name
+*/
+pub fn load_part_code(directory: &Path, entry: &Entry, part: &str)
+    -> Result<Option<String>, HistoryError>
+{
+    if !entry.parts.iter().any(|name| name == part)
+    {
+        return Ok(None);
+    }
+
+    let path = directory.join(&entry.id).join(PART_DIRECTORY).join(part).with_extension("scad");
+    match fs::read_to_string(path)
+    {
+        Ok(code) => Ok(Some(code)),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+            Err(HistoryError::MissingPartCode(entry.id.clone(), part.to_string()))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn load_parameters(directory: &Path, entry: &Entry) -> Result<ParameterSet, HistoryError>
+{
+    let text = fs::read_to_string(directory.join(&entry.id).join(PARAMETER_FILE))?;
+    Ok(ParameterSet::parse(&text)?)
+}
+
+/**
+  Adds an entry for generated code unless the newest entry has the same code
+  and parameters. `parts` are the names and code of the printed parts.
+  Returns the entry that the code is stored in. Only the newest entry is
+  read, so broken older entries don't stop new ones from being added
+*/
+pub fn record(
+    directory: &Path,
+    parts: &[(String, String)],
+    parameters: &ParameterSet,
+    code: &str
+) -> Result<Entry, HistoryError>
+{
+    let ids = entry_ids(directory)?;
+    let code_hash = hash(code);
+
+    //A newest entry that can't be read is never the same as the new code
+    if let Some(Ok(last)) = ids.last().map(|id| read_entry(directory, id))
+    {
+        if last.hash == code_hash && load_parameters(directory, &last).ok().as_ref() == Some(parameters)
+        {
+            return Ok(last);
+        }
+    }
+
+    let number = ids.last()
+        .and_then(|id| id.parse::<u64>().ok())
+        .map_or(1, |number| number + 1);
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+
+    let entry = Entry{
+        id: format!("{:04}", number),
+        timestamp,
+        parts: parts.iter().map(|(name, _)| name.clone()).collect(),
+        commit: current_commit(),
+        hash: code_hash,
+    };
+
+    let entry_directory = directory.join(&entry.id);
+    fs::create_dir_all(&entry_directory)?;
+    fs::write(entry_directory.join(PARAMETER_FILE), parameters.to_text())?;
+    fs::write(entry_directory.join(CODE_FILE), code)?;
+    let part_directory = entry_directory.join(PART_DIRECTORY);
+    fs::create_dir_all(&part_directory)?;
+    for (name, part_code) in parts
+    {
+        fs::write(part_directory.join(name).with_extension("scad"), part_code)?;
+    }
+    //The entry file is written last so that entries that failed half way
+    //are not listed
+    write_entry(directory, &entry)?;
+    Ok(entry)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use assembly::Assembly;
+    use parameters::set_parameter;
+
+    use std::env;
+
+    fn canopy() -> Vec<(String, String)>
+    {
+        vec!(("canopy".to_string(), "cube(1);".to_string()))
+    }
+
+    fn temporary_directory(name: &str) -> PathBuf
+    {
+        let directory = env::temp_dir()
+            .join(format!("tricopter_history_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    #[test]
+    fn entries_are_recorded_once()
+    {
+        let directory = temporary_directory("recorded_once");
+        let mut assembly = Assembly::new();
+        let parameters = ParameterSet::of(&assembly);
+
+        let first = record(&directory, &canopy(), &parameters, "cube(1);").unwrap();
+        let same = record(&directory, &canopy(), &parameters, "cube(1);").unwrap();
+        assert_eq!(first, same);
+
+        set_parameter(&mut assembly, "body.radius", "80").unwrap();
+        let changed = record(&directory, &canopy(), &ParameterSet::of(&assembly), "cube(1);").unwrap();
+        assert_eq!(changed.id, "0002");
+
+        assert_eq!(list(&directory).unwrap(), vec!(first.clone(), changed.clone()));
+        assert_eq!(find(&directory, "1").unwrap(), first);
+        assert_eq!(find(&directory, "last").unwrap(), changed);
+        assert_eq!(load_code(&directory, &first).unwrap(), "cube(1);");
+        assert_eq!(load_parameters(&directory, &changed).unwrap().get("body.radius"), Some("80"));
+        assert_eq!(load_part_code(&directory, &first, "canopy").unwrap(), Some("cube(1);".to_string()));
+        assert_eq!(load_part_code(&directory, &first, "body_top").unwrap(), None);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn ids_are_ordered_past_four_digits()
+    {
+        let directory = temporary_directory("four_digits");
+        let mut assembly = Assembly::new();
+
+        //An entry with the largest 4 digit id, as if 9998 were removed
+        record(&directory, &canopy(), &ParameterSet::of(&assembly), "cube(1);").unwrap();
+        fs::rename(directory.join("0001"), directory.join("9999")).unwrap();
+
+        set_parameter(&mut assembly, "body.radius", "80").unwrap();
+        let first = record(&directory, &canopy(), &ParameterSet::of(&assembly), "cube(1);").unwrap();
+        assert_eq!(first.id, "10000");
+
+        set_parameter(&mut assembly, "body.radius", "85").unwrap();
+        let second = record(&directory, &canopy(), &ParameterSet::of(&assembly), "cube(1);").unwrap();
+        assert_eq!(second.id, "10001");
+
+        let ids = list(&directory).unwrap().into_iter().map(|entry| entry.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec!("9999", "10000", "10001"));
+        assert_eq!(find(&directory, "last").unwrap(), second);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn broken_entries_are_errors()
+    {
+        let directory = temporary_directory("broken");
+        let entry = record(&directory, &canopy(), &ParameterSet::of(&Assembly::new()), "cube(1);").unwrap();
+
+        //Entries from before the code of the parts was stored
+        fs::remove_dir_all(directory.join(&entry.id).join(PART_DIRECTORY)).unwrap();
+        match load_part_code(&directory, &entry, "canopy")
+        {
+            Err(HistoryError::MissingPartCode(ref id, ref part)) => {
+                assert_eq!((id.as_str(), part.as_str()), ("0001", "canopy"))
+            }
+            other => panic!("{:?}", other),
+        }
+
+        let entry_file = directory.join(&entry.id).join(ENTRY_FILE);
+        let text = fs::read_to_string(&entry_file).unwrap();
+        let timestamp = format!("timestamp: {}", entry.timestamp);
+        fs::write(&entry_file, text.replace(&timestamp, "timestamp: yesterday")).unwrap();
+        match list(&directory)
+        {
+            Err(HistoryError::InvalidField(_, ref field, ref value)) => {
+                assert_eq!((field.as_str(), value.as_str()), ("timestamp", "yesterday"))
+            }
+            other => panic!("{:?}", other),
+        }
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn broken_entries_are_skipped_when_recording()
+    {
+        let directory = temporary_directory("record_broken");
+        let mut assembly = Assembly::new();
+        let first = record(&directory, &canopy(), &ParameterSet::of(&assembly), "cube(1);").unwrap();
+
+        let entry_file = directory.join(&first.id).join(ENTRY_FILE);
+        fs::write(&entry_file, "timestamp: yesterday\n").unwrap();
+
+        set_parameter(&mut assembly, "body.radius", "80").unwrap();
+        let second = record(&directory, &canopy(), &ParameterSet::of(&assembly), "cube(1);").unwrap();
+        assert_eq!(second.id, "0002");
+
+        //The broken entry is older, so the newest entry is still found
+        let same = record(&directory, &canopy(), &ParameterSet::of(&assembly), "cube(1);").unwrap();
+        assert_eq!(same, second);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn times_are_formatted_as_dates()
+    {
+        assert_eq!(format_time(0), "1970-01-01 00:00:00");
+        assert_eq!(format_time(951_827_696), "2000-02-29 12:34:56");
+    }
+}
@@ -0,0 +1,506 @@
+/*!
+  Named hole positions shared by the parts that are fastened together.
+
+  Parts place their holes from a `HolePattern` instead of computing the
+  positions themselves, so that mating parts use the same positions and the
+  holes of the craft can be listed.
+*/
+
+use scad::*;
+use scad_util::nut;
+
+use edges;
+use na;
+use SCREW_DIAMETER;
+
+use std::fmt;
+
+//Extra depth of a nut trap so that the nut sits below the face of the part
+const NUT_TRAP_PADDING: f32 = 0.3;
+//Extra diameter of a recess for a screw head so that the head and the tool
+//fit in it
+const HEAD_CLEARANCE: f32 = 1.;
+
+/**
+  How the thread that a fastener screws into is made at a joint
+*/
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Threading
+{
+    //The fastener goes through the part and a nut is put on it behind it
+    ThroughNut,
+    //A pocket at the far side of the part holds the nut
+    NutTrap,
+    //The fastener cuts its own thread into a pilot hole
+    SelfTapping,
+    //A threaded insert is melted into the part from the side that the
+    //fastener enters from
+    HeatSetInsert,
+}
+
+/**
+  The head of a screw and the recess that it sits in
+*/
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HeadRecess
+{
+    //A socket cap head in a flat bottomed recess
+    Counterbore,
+    //A 90 degree countersunk head
+    Countersink,
+    ButtonHead,
+}
+
+/**
+  Returns how deep the head of a screw that is `length` long under its head
+  has to be sunk into a part that is `thickness` thick for the screw to
+  reach `reach` past the far side of the part. Long screws are not sunk,
+  they stick out further instead
+*/
+pub fn head_recess_depth(length: f32, thickness: f32, reach: f32) -> f32
+{
+    (thickness + reach - length).max(0.)
+}
+
+/**
+  The fastener that goes through the holes of a pattern
+*/
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Fastener
+{
+    //Nominal diameter of the thread in mm
+    pub thread_diameter: f32,
+    //Diameter in mm of a hole that the fastener slides through
+    pub clearance_diameter: f32,
+    //Width of the pocket that holds a nut, as passed to `nut`
+    pub nut_width: f32,
+    pub nut_height: f32,
+    //Diameter of a hole that the fastener cuts its thread into when printed
+    pub pilot_diameter: f32,
+    //Diameter and depth of the hole that a heat-set insert is melted into
+    pub insert_diameter: f32,
+    pub insert_length: f32,
+    pub cap_head_diameter: f32,
+    pub button_head_diameter: f32,
+    pub countersunk_head_diameter: f32,
+}
+
+impl Fastener
+{
+    pub fn m3() -> Fastener
+    {
+        Fastener{
+            thread_diameter: 3.,
+            clearance_diameter: SCREW_DIAMETER,
+            nut_width: 5.3,
+            nut_height: 2.4,
+            pilot_diameter: 2.5,
+            insert_diameter: 4.,
+            insert_length: 4.,
+            cap_head_diameter: 5.5,
+            button_head_diameter: 5.7,
+            countersunk_head_diameter: 6.,
+        }
+    }
+
+    pub fn m2() -> Fastener
+    {
+        Fastener{
+            thread_diameter: 2.,
+            clearance_diameter: 2.4,
+            nut_width: 4.,
+            nut_height: 1.6,
+            pilot_diameter: 1.6,
+            insert_diameter: 3.2,
+            insert_length: 3.,
+            cap_head_diameter: 3.8,
+            button_head_diameter: 3.5,
+            countersunk_head_diameter: 3.8,
+        }
+    }
+
+    /**
+      Returns how far the fastener has to reach into a part that is
+      `thickness` thick, that it screws into with the threading. With a nut
+      it goes through the part and the nut
+    */
+    pub fn thread_reach(&self, threading: Threading, thickness: f32) -> f32
+    {
+        match threading
+        {
+            Threading::ThroughNut => thickness + self.nut_height,
+            Threading::NutTrap => thickness,
+            Threading::SelfTapping => (self.thread_diameter * 2.).min(thickness),
+            Threading::HeatSetInsert => self.insert_length.min(thickness),
+        }
+    }
+
+    /**
+      Returns the diameter of the recess for a head
+    */
+    pub fn head_recess_diameter(&self, head: HeadRecess) -> f32
+    {
+        let head_diameter = match head
+        {
+            HeadRecess::Counterbore => self.cap_head_diameter,
+            HeadRecess::Countersink => self.countersunk_head_diameter,
+            HeadRecess::ButtonHead => self.button_head_diameter,
+        };
+        head_diameter + HEAD_CLEARANCE
+    }
+
+    /**
+      Returns the cut for the head of the fastener. The face of the part that
+      the head is sunk into is at z = 0 and the fastener goes up into the
+      part. The head rests `depth` into the part, or for a countersunk head
+      is flush with that depth. The recess continues `access` below the face
+      so that the head and the tool can get to it
+    */
+    pub fn get_head_recess(&self, head: HeadRecess, depth: f32, access: f32) -> ScadObject
+    {
+        let diameter = self.head_recess_diameter(head);
+        let recess = scad!(Translate(vec3(0., 0., -access)); {
+            scad!(Cylinder(access + depth, Diameter(diameter)))
+        });
+
+        match head
+        {
+            HeadRecess::Counterbore | HeadRecess::ButtonHead => recess,
+            HeadRecess::Countersink => {
+                //The sides of the countersink are at 45 degrees to the axis
+                let cone_height = (diameter - self.clearance_diameter) / 2.;
+                let cone = edges::cone(cone_height, diameter / 2., self.clearance_diameter / 2.);
+                scad!(Union; {
+                    recess,
+                    scad!(Translate(vec3(0., 0., depth)); cone)
+                })
+            }
+        }
+    }
+
+    /**
+      Returns the diameter of the hole that the fastener goes through in a
+      part with the threading
+    */
+    pub fn hole_diameter(&self, threading: Threading) -> f32
+    {
+        match threading
+        {
+            Threading::SelfTapping => self.pilot_diameter,
+            _ => self.clearance_diameter,
+        }
+    }
+
+    /**
+      Returns the diameter of the widest cut that the threading makes
+      around the hole. The nut is assumed to be measured across its flats
+    */
+    pub fn cut_diameter(&self, threading: Threading) -> f32
+    {
+        match threading
+        {
+            Threading::ThroughNut => self.clearance_diameter,
+            Threading::NutTrap => self.nut_width / 30_f32.to_radians().cos(),
+            Threading::SelfTapping => self.pilot_diameter,
+            Threading::HeatSetInsert => self.insert_diameter.max(self.clearance_diameter),
+        }
+    }
+
+    /**
+      Returns the cut for a hole through a part that is `depth` thick with
+      the threading. The fastener enters the part at z = 0 and the far side
+      of the part is at z = `depth`
+    */
+    pub fn get_threaded_hole(&self, threading: Threading, depth: f32) -> ScadObject
+    {
+        let hole = scad!(Cylinder(depth, Diameter(self.hole_diameter(threading))));
+        match threading
+        {
+            Threading::ThroughNut | Threading::SelfTapping => hole,
+            Threading::NutTrap => {
+                let trap_height = (self.nut_height + NUT_TRAP_PADDING).min(depth);
+                scad!(Union; {
+                    hole,
+                    scad!(Translate(vec3(0., 0., depth - trap_height)); {
+                        nut(self.nut_width, trap_height)
+                    })
+                })
+            }
+            Threading::HeatSetInsert => scad!(Union; {
+                hole,
+                scad!(Cylinder(self.insert_length.min(depth), Diameter(self.insert_diameter)))
+            }),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Hole
+{
+    pub name: String,
+    pub position: na::Vector3<f32>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct HolePattern
+{
+    pub name: String,
+    //Holes that are not for fasteners, like wire holes, have no fastener
+    pub fastener: Option<Fastener>,
+    pub diameter: f32,
+    pub holes: Vec<Hole>,
+}
+
+impl HolePattern
+{
+    /**
+      Creates a pattern without holes
+    */
+    pub fn new(name: &str, fastener: Option<Fastener>, diameter: f32) -> HolePattern
+    {
+        HolePattern{
+            name: name.to_string(),
+            fastener,
+            diameter,
+            holes: vec!(),
+        }
+    }
+
+    /**
+      Creates a pattern for a fastener with holes that the fastener slides
+      through
+    */
+    pub fn for_fastener(name: &str, fastener: Fastener) -> HolePattern
+    {
+        HolePattern::new(name, Some(fastener), fastener.clearance_diameter)
+    }
+
+    pub fn with_hole(mut self, name: &str, x: f32, y: f32) -> HolePattern
+    {
+        self.holes.push(Hole{
+            name: name.to_string(),
+            position: na::Vector3::new(x, y, 0.),
+        });
+        self
+    }
+
+    /**
+      Adds a pair of holes on the left and right side of the x axis. The
+      left hole is on the negative y side
+    */
+    pub fn with_symmetric_holes(self, name: &str, x: f32, y: f32) -> HolePattern
+    {
+        self.with_hole(&format!("{}_right", name), x, y)
+            .with_hole(&format!("{}_left", name), x, -y)
+    }
+
+    /**
+      Returns the pattern moved by an offset
+    */
+    pub fn translated(&self, offset: na::Vector3<f32>) -> HolePattern
+    {
+        let mut result = self.clone();
+        for hole in &mut result.holes
+        {
+            hole.position += offset;
+        }
+        result
+    }
+
+    /**
+      Returns the cuts for threaded holes through a part that is `depth`
+      thick. The fasteners enter the part at z = 0. Holes without a fastener
+      are plain cylinders
+    */
+    pub fn get_threaded_holes(&self, threading: Threading, depth: f32) -> ScadObject
+    {
+        match self.fastener
+        {
+            Some(fastener) => self.place_object(fastener.get_threaded_hole(threading, depth)),
+            None => self.get_cylinders(depth),
+        }
+    }
+
+    /**
+      Returns the pattern with holes as wide as the widest cut that the
+      threading makes, for checking the material around the holes of the
+      part that the fastener screws into
+    */
+    pub fn threaded(&self, threading: Threading) -> HolePattern
+    {
+        let mut result = self.clone();
+        if let Some(fastener) = self.fastener
+        {
+            result.diameter = fastener.cut_diameter(threading);
+        }
+        result
+    }
+
+    /**
+      Returns the positions of the holes in the x-y plane
+    */
+    pub fn positions_2d(&self) -> Vec<na::Vector2<f32>>
+    {
+        self.holes.iter()
+            .map(|hole| na::Vector2::new(hole.position.x, hole.position.y))
+            .collect()
+    }
+
+    /**
+      Returns a copy of the object at every hole
+    */
+    pub fn place_object(&self, object: ScadObject) -> ScadObject
+    {
+        let mut result = scad!(Union);
+        for hole in &self.holes
+        {
+            result.add_child(scad!(Translate(hole.position); object.clone()));
+        }
+        result
+    }
+
+    /**
+      Returns circles with the diameter of the holes
+    */
+    pub fn get_circles(&self) -> ScadObject
+    {
+        self.place_object(scad!(Circle(Diameter(self.diameter))))
+    }
+
+    /**
+      Returns cylinders with the diameter of the holes going up from the
+      holes
+    */
+    pub fn get_cylinders(&self, height: f32) -> ScadObject
+    {
+        self.place_object(scad!(Cylinder(height, Diameter(self.diameter))))
+    }
+}
+
+impl fmt::Display for HolePattern
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        let fastener = match self.fastener
+        {
+            Some(fastener) => format!("M{}", fastener.thread_diameter),
+            None => "no fastener".to_string(),
+        };
+        writeln!(f, "{} ({}, {:.1} mm holes)", self.name, fastener, self.diameter)?;
+        for hole in &self.holes
+        {
+            let p = hole.position;
+            writeln!(f, "    {:<18} {:>8.2} {:>8.2} {:>8.2}", hole.name, p.x, p.y, p.z)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use evaluate::{Shape, Solid, Vec2, Vec3};
+
+    #[test]
+    fn symmetric_holes_are_mirrored_across_the_x_axis()
+    {
+        let pattern = HolePattern::for_fastener("test", Fastener::m3())
+            .with_hole("center", 1., 0.)
+            .with_symmetric_holes("side", 2., 3.);
+
+        let names = pattern.holes.iter().map(|hole| hole.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!("center", "side_right", "side_left"));
+        assert_eq!(
+            pattern.positions_2d(),
+            vec!(na::Vector2::new(1., 0.), na::Vector2::new(2., 3.), na::Vector2::new(2., -3.))
+        );
+        assert_eq!(pattern.diameter, Fastener::m3().clearance_diameter);
+    }
+
+    #[test]
+    fn translated_patterns_move_every_hole()
+    {
+        let pattern = HolePattern::new("test", None, 2.).with_symmetric_holes("hole", 1., 2.);
+        let moved = pattern.translated(na::Vector3::new(10., 0., 5.));
+
+        assert_eq!(moved.name, pattern.name);
+        for (hole, moved) in pattern.holes.iter().zip(&moved.holes)
+        {
+            assert_eq!(moved.name, hole.name);
+            assert_eq!(moved.position - hole.position, na::Vector3::new(10., 0., 5.));
+        }
+    }
+
+    #[test]
+    fn objects_are_placed_at_every_hole()
+    {
+        let pattern = HolePattern::new("test", None, 2.)
+            .with_hole("a", 10., 0.)
+            .with_symmetric_holes("b", -5., 8.);
+        let circles = Shape::from_object(&pattern.get_circles()).unwrap();
+
+        for position in pattern.positions_2d()
+        {
+            assert!(circles.contains(&Vec2::new(position.x + 0.9, position.y)), "{:?}", position);
+            assert!(!circles.contains(&Vec2::new(position.x + 1.1, position.y)), "{:?}", position);
+        }
+        assert!(!circles.contains(&Vec2::new(0., 0.)));
+
+        let cylinders = Solid::from_object(&pattern.get_cylinders(3.)).unwrap();
+        assert!(cylinders.contains(&Vec3::new(10., 0., 2.9)));
+        assert!(!cylinders.contains(&Vec3::new(10., 0., 3.1)));
+    }
+
+    #[test]
+    fn threadings_cut_their_holes()
+    {
+        let fastener = Fastener::m3();
+        let depth = 4.;
+        let cut = |threading| Solid::from_object(&fastener.get_threaded_hole(threading, depth)).unwrap();
+
+        let through = cut(Threading::ThroughNut);
+        let pilot = cut(Threading::SelfTapping);
+        assert!(through.contains(&Vec3::new(fastener.pilot_diameter / 2. + 0.1, 0., 2.)));
+        assert!(!pilot.contains(&Vec3::new(fastener.pilot_diameter / 2. + 0.1, 0., 2.)));
+        assert!(pilot.contains(&Vec3::new(0., 0., 3.9)));
+
+        //The nut is held at the far side and the insert at the near side
+        let beside_hole = fastener.clearance_diameter / 2. + 0.2;
+        let trap = cut(Threading::NutTrap);
+        assert!(trap.contains(&Vec3::new(beside_hole, 0., depth - 0.1)));
+        assert!(!trap.contains(&Vec3::new(beside_hole, 0., 0.1)));
+        let insert = cut(Threading::HeatSetInsert);
+        assert!(insert.contains(&Vec3::new(fastener.insert_diameter / 2. - 0.1, 0., 0.1)));
+        assert!(!insert.contains(&Vec3::new(fastener.insert_diameter / 2. + 0.1, 0., 0.1)));
+
+        let pattern = HolePattern::for_fastener("test", fastener).with_hole("a", 0., 0.);
+        assert_eq!(pattern.threaded(Threading::ThroughNut), pattern);
+        assert_eq!(pattern.threaded(Threading::HeatSetInsert).diameter, fastener.insert_diameter);
+    }
+
+    #[test]
+    fn heads_are_sunk_deep_enough_for_the_screw_to_reach()
+    {
+        assert_eq!(head_recess_depth(10., 4., 8.), 2.);
+        assert_eq!(head_recess_depth(16., 4., 8.), 0.);
+
+        let fastener = Fastener::m3();
+        let recess = |head| Solid::from_object(&fastener.get_head_recess(head, 2., 1.)).unwrap();
+        let counterbore = recess(HeadRecess::Counterbore);
+        let countersink = recess(HeadRecess::Countersink);
+        let button = recess(HeadRecess::ButtonHead);
+
+        let radius = fastener.head_recess_diameter(HeadRecess::Counterbore) / 2.;
+        assert!(counterbore.contains(&Vec3::new(radius - 0.1, 0., -0.9)));
+        assert!(counterbore.contains(&Vec3::new(radius - 0.1, 0., 1.9)));
+        assert!(!counterbore.contains(&Vec3::new(radius - 0.1, 0., 2.1)));
+        assert!(!button.contains(&Vec3::new(3.4, 0., 1.)));
+
+        //The countersink narrows by as much as it goes deeper
+        let radius = fastener.head_recess_diameter(HeadRecess::Countersink) / 2.;
+        assert!(countersink.contains(&Vec3::new(radius - 1.1, 0., 3.)));
+        assert!(!countersink.contains(&Vec3::new(radius - 0.9, 0., 3.)));
+    }
+}
@@ -0,0 +1,231 @@
+/*!
+  Export of the mass properties of the craft in formats that simulators
+  can use. Everything is converted to SI units on export. The axes are the
+  ones of `TricopterBody`, so x points towards the tail, unless a report is
+  `rotated`.
+*/
+
+use na;
+
+use mass::MassModel;
+use evaluate::Vec3;
+
+/**
+  Mass properties of the whole craft in SI units
+*/
+#[derive(Clone, PartialEq, Debug)]
+pub struct InertiaReport
+{
+    //kg
+    pub mass: f32,
+    //m, relative to the center of the body shape
+    pub center_of_mass: Vec3,
+    //kg*m^2 around the center of gravity
+    pub tensor: na::Matrix3<f32>,
+}
+
+impl InertiaReport
+{
+    pub fn new(model: &MassModel) -> InertiaReport
+    {
+        InertiaReport{
+            mass: model.total_mass() * 1e-3,
+            center_of_mass: model.center_of_mass() * 1e-3,
+            tensor: model.inertia_tensor() * 1e-9,
+        }
+    }
+
+    /**
+      Returns the report with the center of mass relative to `origin`
+      instead of the center of the body. `origin` is in meters
+    */
+    pub fn relative_to(&self, origin: Vec3) -> InertiaReport
+    {
+        InertiaReport{
+            mass: self.mass,
+            center_of_mass: self.center_of_mass - origin,
+            tensor: self.tensor,
+        }
+    }
+
+    /**
+      Returns the report in axes that are rotated by `rotation` from the
+      axes of the body
+    */
+    pub fn rotated(&self, rotation: &na::Matrix3<f32>) -> InertiaReport
+    {
+        InertiaReport{
+            mass: self.mass,
+            center_of_mass: rotation * self.center_of_mass,
+            tensor: rotation * self.tensor * rotation.transpose(),
+        }
+    }
+
+    fn ixx(&self) -> f32 { self.tensor[(0, 0)] }
+    fn iyy(&self) -> f32 { self.tensor[(1, 1)] }
+    fn izz(&self) -> f32 { self.tensor[(2, 2)] }
+    fn ixy(&self) -> f32 { self.tensor[(0, 1)] }
+    fn ixz(&self) -> f32 { self.tensor[(0, 2)] }
+    fn iyz(&self) -> f32 { self.tensor[(1, 2)] }
+
+    pub fn to_json(&self) -> String
+    {
+        let c = self.center_of_mass;
+        let rows = (0..3)
+            .map(|row| format!(
+                "    [{:e}, {:e}, {:e}]",
+                self.tensor[(row, 0)], self.tensor[(row, 1)], self.tensor[(row, 2)]
+            ))
+            .collect::<Vec<_>>()
+            .join(",\n");
+
+        format!(
+            "{{\n  \"mass\": {:e},\n  \"center_of_mass\": [{:e}, {:e}, {:e}],\n  \"inertia\": [\n{}\n  ]\n}}\n",
+            self.mass, c.x, c.y, c.z, rows
+        )
+    }
+
+    /**
+      `<inertial>` element for URDF models
+    */
+    pub fn to_urdf_inertial(&self) -> String
+    {
+        let c = self.center_of_mass;
+        format!(
+            concat!(
+                "<inertial>\n",
+                "  <origin xyz=\"{:e} {:e} {:e}\" rpy=\"0 0 0\"/>\n",
+                "  <mass value=\"{:e}\"/>\n",
+                "  <inertia ixx=\"{:e}\" ixy=\"{:e}\" ixz=\"{:e}\" iyy=\"{:e}\" iyz=\"{:e}\" izz=\"{:e}\"/>\n",
+                "</inertial>\n"
+            ),
+            c.x, c.y, c.z,
+            self.mass,
+            self.ixx(), self.ixy(), self.ixz(), self.iyy(), self.iyz(), self.izz()
+        )
+    }
+
+    /**
+      `<inertial>` element for SDF models
+    */
+    pub fn to_sdf_inertial(&self) -> String
+    {
+        let c = self.center_of_mass;
+        format!(
+            concat!(
+                "<inertial>\n",
+                "  <pose>{:e} {:e} {:e} 0 0 0</pose>\n",
+                "  <mass>{:e}</mass>\n",
+                "  <inertia>\n",
+                "    <ixx>{:e}</ixx>\n",
+                "    <ixy>{:e}</ixy>\n",
+                "    <ixz>{:e}</ixz>\n",
+                "    <iyy>{:e}</iyy>\n",
+                "    <iyz>{:e}</iyz>\n",
+                "    <izz>{:e}</izz>\n",
+                "  </inertia>\n",
+                "</inertial>\n"
+            ),
+            c.x, c.y, c.z,
+            self.mass,
+            self.ixx(), self.ixy(), self.ixz(), self.iyy(), self.iyz(), self.izz()
+        )
+    }
+
+    /**
+      Yaml parameters in the style used by multirotor simulators
+    */
+    pub fn to_yaml(&self) -> String
+    {
+        format!(
+            concat!(
+                "mass: {:e}\n",
+                "inertia:\n",
+                "  xx: {:e}\n",
+                "  xy: {:e}\n",
+                "  xz: {:e}\n",
+                "  yy: {:e}\n",
+                "  yz: {:e}\n",
+                "  zz: {:e}\n"
+            ),
+            self.mass,
+            self.ixx(), self.ixy(), self.ixz(), self.iyy(), self.iyz(), self.izz()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use evaluate::Solid;
+    use mass::PartMass;
+
+    /**
+      Mass properties of a solid box of the size starting at the corner
+    */
+    fn box_part(corner: Vec3, size: Vec3, mass: f32) -> PartMass
+    {
+        let code = format!(
+            "translate([{},{},{}])cube([{},{},{}]);",
+            corner.x, corner.y, corner.z, size.x, size.y, size.z
+        );
+        let properties = Solid::from_code(&code).unwrap().volume_properties(1.);
+        PartMass{
+            name: "box".to_string(),
+            mass,
+            center_of_mass: properties.centroid,
+            second_moment: properties.second_moment * (mass / properties.volume),
+        }
+    }
+
+    #[test]
+    fn tensor_of_a_box_matches_the_analytic_one()
+    {
+        //A 100 x 60 x 20 mm box of 500 g made from two halves that are away
+        //from the origin
+        let corner = Vec3::new(-20., -40., -5.);
+        let half = Vec3::new(50., 60., 20.);
+        let model = MassModel{parts: vec!(
+            box_part(corner, half, 250.),
+            box_part(corner + Vec3::new(50., 0., 0.), half, 250.),
+        )};
+        let report = InertiaReport::new(&model);
+
+        let (mass, x, y, z) = (0.5, 0.1, 0.06, 0.02);
+        let expected = [
+            mass * (y * y + z * z) / 12.,
+            mass * (x * x + z * z) / 12.,
+            mass * (x * x + y * y) / 12.,
+        ];
+
+        assert!((report.mass - mass).abs() < 1e-6);
+        assert!((report.center_of_mass - Vec3::new(0.03, -0.01, 0.005)).norm() < 1e-5);
+        for i in 0..3
+        {
+            let relative = (report.tensor[(i, i)] - expected[i]).abs() / expected[i];
+            assert!(relative < 0.01, "{}: {} != {}", i, report.tensor[(i, i)], expected[i]);
+            for j in 0..3
+            {
+                if i != j
+                {
+                    assert!(report.tensor[(i, j)].abs() < expected[2] * 1e-3, "{}", report.tensor);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn relative_reports_only_move_the_center()
+    {
+        let report = InertiaReport::new(&MassModel{parts: vec!(
+            box_part(Vec3::new(0., 0., 0.), Vec3::new(10., 10., 10.), 10.)
+        )});
+        let moved = report.relative_to(Vec3::new(0.005, 0., 0.));
+
+        assert!((moved.center_of_mass - Vec3::new(0., 0.005, 0.005)).norm() < 1e-6);
+        assert_eq!(moved.tensor, report.tensor);
+        assert_eq!(moved.mass, report.mass);
+    }
+}
@@ -0,0 +1,161 @@
+/*!
+  Detection of parts of the assembly that occupy the same space.
+
+  Parts whose bounding boxes don't overlap can't collide so they are skipped.
+  The rest are sampled on a grid over the overlap of their bounding boxes and
+  every cell that is inside both parts counts towards the overlap volume.
+*/
+
+use assembly::{Assembly, AssemblyPart};
+use evaluate::{sample_grid, EvalError, Solid};
+
+use std::fmt;
+
+/**
+  Two parts that overlap
+*/
+#[derive(Clone, PartialEq, Debug)]
+pub struct Interference
+{
+    pub first: String,
+    pub second: String,
+    //Overlapping volume in mm^3
+    pub volume: f32,
+}
+
+impl fmt::Display for Interference
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "{} and {} overlap by {:.1} mm^3", self.first, self.second, self.volume)
+    }
+}
+
+/**
+  Returns the overlapping volume of two solids sampled with cells of the
+  specified size
+*/
+pub fn overlap_volume(first: &Solid, second: &Solid, resolution: f32) -> f32
+{
+    let bounds = first.bounds().intersection(&second.bounds());
+    if bounds.is_empty()
+    {
+        return 0.;
+    }
+
+    //Parts that only touch have samples exactly on the surface of both, so
+    //only points strictly inside both count
+    let count = sample_grid(&bounds, resolution).iter()
+        .filter(|point| first.distance(point) < 0. && second.distance(point) < 0.)
+        .count();
+
+    count as f32 * resolution.powi(3)
+}
+
+/**
+  Returns all pairs of parts that overlap
+*/
+pub fn find_interferences(parts: &[AssemblyPart], resolution: f32)
+    -> Result<Vec<Interference>, EvalError>
+{
+    let solids = parts.iter()
+        .map(|part| Solid::from_object(&part.object))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut result = vec!();
+    for i in 0..solids.len()
+    {
+        for j in i + 1..solids.len()
+        {
+            if !solids[i].bounds().intersects(&solids[j].bounds())
+            {
+                continue;
+            }
+
+            let volume = overlap_volume(&solids[i], &solids[j], resolution);
+            if volume > 0.
+            {
+                result.push(Interference{
+                    first: parts[i].name.clone(),
+                    second: parts[j].name.clone(),
+                    volume,
+                });
+            }
+        }
+    }
+    Ok(result)
+}
+
+/**
+  Returns all pairs of parts in the assembly that overlap
+*/
+pub fn check_assembly(assembly: &Assembly, resolution: f32)
+    -> Result<Vec<Interference>, EvalError>
+{
+    find_interferences(&assembly.get_parts(), resolution)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use scad::*;
+    use assembly::PartKind;
+
+    fn cube_at(name: &str, x: f32) -> AssemblyPart
+    {
+        AssemblyPart{
+            name: name.to_string(),
+            kind: PartKind::Component(1.),
+            object: scad!(Translate(vec3(x, 0., 0.)); scad!(Cube(vec3(10., 10., 10.)))),
+        }
+    }
+
+    #[test]
+    fn overlapping_cubes_are_found()
+    {
+        let parts = vec!(cube_at("a", 0.), cube_at("b", 5.), cube_at("c", 30.));
+        let result = find_interferences(&parts, 0.5).unwrap();
+
+        assert_eq!(result.len(), 1);
+        assert_eq!((result[0].first.as_str(), result[0].second.as_str()), ("a", "b"));
+        assert!((result[0].volume - 500.).abs() < 1.);
+    }
+
+    #[test]
+    fn touching_cubes_do_not_interfere()
+    {
+        let parts = vec!(cube_at("a", 0.), cube_at("b", 10.));
+        assert!(find_interferences(&parts, 0.5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn default_assembly_has_no_interferences()
+    {
+        let result = check_assembly(&Assembly::new(), 1.).unwrap();
+        assert!(result.is_empty(), "{:?}", result);
+    }
+
+    #[test]
+    fn canopy_covers_the_esc_stack()
+    {
+        let parts = Assembly::new().get_parts();
+        let canopy = parts.iter().find(|part| part.name == "canopy").unwrap();
+        let canopy = Solid::from_object(&canopy.object).unwrap().bounds();
+
+        let stack = parts.iter()
+            .filter(|part| part.name.starts_with("flight_controller") || part.name.starts_with("esc_"))
+            .collect::<Vec<_>>();
+        assert!(!stack.is_empty());
+        for part in stack
+        {
+            let bounds = Solid::from_object(&part.object).unwrap().bounds();
+            for i in 0..2
+            {
+                assert!(bounds.min[i] > canopy.min[i] && bounds.max[i] < canopy.max[i], "{}", part.name);
+            }
+            assert!(bounds.max.z < canopy.max.z, "{}", part.name);
+        }
+    }
+}
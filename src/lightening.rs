@@ -0,0 +1,144 @@
+/*!
+  Mass saved by cutting lightening pockets into the body plates.
+
+  The plates are weighed with the mass model with and without the pockets,
+  using the same material and fill factor as the rest of the craft.
+*/
+
+use assembly::Assembly;
+use evaluate::EvalError;
+use mass::MassModel;
+use LighteningPattern;
+
+use std::fmt;
+
+//Size in mm of the cells used to sample the plates. The plates are only a
+//few mm thick so they need finer cells than the rest of the craft
+const RESOLUTION: f32 = 0.5;
+//The parts that the pockets are cut into
+const PLATES: [&str; 2] = ["body_bottom", "body_top"];
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct PlateSaving
+{
+    pub name: String,
+    //Masses in grams
+    pub solid_mass: f32,
+    pub lightened_mass: f32,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct LighteningReport
+{
+    pub pattern: LighteningPattern,
+    pub plates: Vec<PlateSaving>,
+}
+
+/**
+  Returns the mass of each of the plates of an assembly
+*/
+fn plate_masses(assembly: &Assembly) -> Result<MassModel, EvalError>
+{
+    let plates = assembly.get_printed_parts().into_iter()
+        .filter(|part| PLATES.contains(&part.name.as_str()))
+        .collect::<Vec<_>>();
+    MassModel::of_parts(&plates, assembly, RESOLUTION)
+}
+
+impl LighteningReport
+{
+    /**
+      Weighs the plates of the assembly without pockets and with the pockets
+      of the pattern. The other lightening parameters of the assembly are
+      used as they are
+    */
+    pub fn new(assembly: &mut Assembly, pattern: LighteningPattern)
+        -> Result<LighteningReport, EvalError>
+    {
+        let original = assembly.body.lightening;
+
+        assembly.body.lightening = LighteningPattern::None;
+        let solid = plate_masses(assembly);
+        assembly.body.lightening = pattern;
+        let lightened = plate_masses(assembly);
+        assembly.body.lightening = original;
+
+        let plates = solid?.parts.into_iter()
+            .zip(lightened?.parts.into_iter())
+            .map(|(solid, lightened)| PlateSaving{
+                name: solid.name,
+                solid_mass: solid.mass,
+                lightened_mass: lightened.mass,
+            })
+            .collect();
+
+        Ok(LighteningReport{pattern, plates})
+    }
+
+    pub fn mass_saved(&self) -> f32
+    {
+        self.plates.iter().map(|plate| plate.solid_mass - plate.lightened_mass).sum()
+    }
+}
+
+impl fmt::Display for LighteningReport
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        writeln!(f, "{:?}:", self.pattern)?;
+        for plate in &self.plates
+        {
+            writeln!(
+                f,
+                "    {:<12} {:>6.1} g -> {:>6.1} g",
+                plate.name, plate.solid_mass, plate.lightened_mass
+            )?;
+        }
+        writeln!(f, "    saves {:.1} g", self.mass_saved())
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use evaluate::{Shape, Vec2};
+
+    #[test]
+    fn pockets_make_the_plates_lighter()
+    {
+        let mut assembly = Assembly::new();
+        for pattern in &[LighteningPattern::Truss, LighteningPattern::Honeycomb, LighteningPattern::Slots]
+        {
+            let report = LighteningReport::new(&mut assembly, *pattern).unwrap();
+            assert_eq!(report.plates.len(), 2);
+            for plate in &report.plates
+            {
+                assert!(plate.lightened_mass < plate.solid_mass, "{:?}", report);
+            }
+        }
+        assert_eq!(assembly.body.lightening, LighteningPattern::None);
+    }
+
+    #[test]
+    fn pockets_keep_away_from_holes()
+    {
+        let mut assembly = Assembly::new();
+        assembly.body.lightening = LighteningPattern::Honeycomb;
+        let body = &assembly.body;
+
+        let pockets = Shape::from_object(&body.get_lightening_pocket_outline()).unwrap();
+        for pattern in body.hole_patterns()
+        {
+            for hole in pattern.positions_2d()
+            {
+                let distance = pockets.distance(&Vec2::new(hole.x, hole.y));
+                assert!(
+                    distance >= pattern.diameter / 2. + body.lightening_keepout - 0.1,
+                    "{} is {} mm from a pocket", pattern.name, distance
+                );
+            }
+        }
+    }
+}
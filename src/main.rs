@@ -4,6 +4,32 @@ extern crate scad;
 extern crate scad_util;
 extern crate nalgebra as na;
 
+mod scad_tree;
+mod evaluate;
+mod assembly;
+mod mass;
+mod balance;
+mod inertia;
+mod mesh;
+mod simulation;
+mod props;
+mod camera_view;
+mod interference;
+mod wall_thickness;
+mod overhang;
+mod golden;
+mod hole_pattern;
+mod mating;
+mod scad_modules;
+mod parameters;
+mod history;
+mod design_diff;
+mod sweep;
+mod targets;
+mod lightening;
+mod edges;
+mod bom;
+
 use scad::*;
 
 use std::string::String;
@@ -14,72 +40,273 @@ use scad_util::{
     nut
 };
 
+use assembly::Assembly;
+use bom::ThreadedJoint;
+use edges::EdgeFinish;
+use hole_pattern::{head_recess_depth, Fastener, HeadRecess, HolePattern, Threading};
+use mass::MassModel;
+use mating::{Feature, MatingInterface};
+
 const SCREW_DIAMETER: f32 = 3.5;
+//How far cuts through a part reach past its faces so that the faces are cut
+//cleanly
+const CUT_OVERSHOOT: f32 = 1.;
+const BATTERY_WIRE_HOLE_RADIUS: f32 = 4.;
+//Angles of the arms around the z axis in degrees, the tail arm first
+const ARM_ANGLES: [f32; 3] = [0., 120., 240.];
+
+/**
+  The connector that the antenna is attached to the VTX with. Decides the
+  size of the hole in the canopy that the connector goes through
+*/
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AntennaConnector
+{
+    Sma,
+    RpSma,
+    Mmcx,
+    UflPigtail,
+}
 
-fn get_vtx_mount() -> ScadObject
+impl AntennaConnector
 {
-    let hole_diameter = 3.5;
-    let hole_padding = 2.;
+    /**
+      Diameter of the part of the connector that goes through the canopy wall
+    */
+    pub fn panel_diameter(&self) -> f32
+    {
+        match *self
+        {
+            AntennaConnector::Sma | AntennaConnector::RpSma => 6.,
+            AntennaConnector::Mmcx => 4.5,
+            AntennaConnector::UflPigtail => 3.,
+        }
+    }
 
-    let hole_edge_distance = 8.;
-    let vtx_thickness = 8.;
-    let vtx_width = 21.;
+    /**
+      Length of the part that goes through the wall. This is the length of
+      the thread for the connectors that are held by a nut
+    */
+    pub fn panel_length(&self) -> f32
+    {
+        match *self
+        {
+            AntennaConnector::Sma | AntennaConnector::RpSma => 2.5,
+            AntennaConnector::Mmcx => 1.5,
+            AntennaConnector::UflPigtail => 1.,
+        }
+    }
 
-    let put_at_hole = |object: ScadObject|
+    /**
+      Diameter of the clearance on the inside of the canopy, for the nut
+      or the body of the connector
+    */
+    pub fn clearance_diameter(&self) -> f32
     {
-        scad!(Union;
+        match *self
         {
-            scad!(Translate2d(vec2(vtx_width/2. + (hole_edge_distance+vtx_thickness), 0.));
-            {
-                object.clone()
-            }),
-            scad!(Translate2d(vec2(-(vtx_width/2. + (hole_edge_distance+vtx_thickness)), 0.));
-            {
-                object.clone()
-            }),
-        })
-    };
+            AntennaConnector::Sma | AntennaConnector::RpSma => 8.,
+            AntennaConnector::Mmcx => 6.,
+            AntennaConnector::UflPigtail => 5.,
+        }
+    }
+}
 
-    let outer_holes = put_at_hole(scad!(Circle(Diameter(hole_diameter + hole_padding*2.))));
+/**
+  How the VTX is attached to its mount. Boxed VTXs are held between two
+  screws on the sides while stack boards are screwed on through the holes in
+  the corners of the pcb
+*/
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum VtxMounting
+{
+    Boxed,
+    Stack,
+}
+
+qstruct!(Vtx()
+{
+    mounting: VtxMounting = VtxMounting::Boxed,
+    width: f32 = 21.,
+    thickness: f32 = 8.,
+    //Only used by boxed VTXs
+    hole_edge_distance: f32 = 8.,
+    //Only used by stack VTXs
+    hole_distance: f32 = 20.,
+    hole_diameter: f32 = 3.5,
+    hole_padding: f32 = 2.,
+    mount_thickness: f32 = 1.5,
+    //How the threads are made for the screws that hold the VTX, in the mount
+    threading: Threading = Threading::ThroughNut,
+    connector: AntennaConnector = AntennaConnector::Sma,
+    mass: f32 = 10.,
+});
 
-    let center_box = centered_square(vec2(vtx_width, vtx_width), (true, true));
+impl Vtx
+{
+    /**
+      Legacy VTX in a box, held in place by two screws on the sides
+    */
+    pub fn boxed() -> Vtx
+    {
+        Vtx::new()
+    }
 
-    let outer_shape = scad!(Hull;
+    /**
+      VTX board with 20x20 mm M2 mounting holes
+    */
+    pub fn stack_20x20() -> Vtx
     {
-        outer_holes,
-        center_box
-    });
+        let mut result = Vtx::new();
+        result.mounting = VtxMounting::Stack;
+        result.width = 27.;
+        result.thickness = 5.;
+        result.hole_distance = 20.;
+        result.hole_diameter = 2.4;
+        result.hole_padding = 1.5;
+        result.connector = AntennaConnector::Mmcx;
+        result.mass = 5.;
+        result
+    }
 
-    let center_cutout = scad!(Scale2d(vec2(0.3, 0.3)); outer_shape.clone());
+    /**
+      VTX board with 30.5x30.5 mm M3 mounting holes
+    */
+    pub fn stack_30x30() -> Vtx
+    {
+        let mut result = Vtx::new();
+        result.mounting = VtxMounting::Stack;
+        result.width = 36.;
+        result.thickness = 6.;
+        result.hole_distance = 30.5;
+        result.hole_diameter = SCREW_DIAMETER;
+        result.connector = AntennaConnector::Sma;
+        result.mass = 8.;
+        result
+    }
 
-    let screwhole = put_at_hole(scad!(Circle(Diameter(hole_diameter))));
+    /**
+      Returns the screws that the VTX is held with. Holes narrower than the
+      M3 holes are for M2 screws
+    */
+    pub fn fastener(&self) -> Fastener
+    {
+        if self.hole_diameter < SCREW_DIAMETER { Fastener::m2() } else { Fastener::m3() }
+    }
 
-    let shape = scad!(Union;
+    /**
+      Returns the holes that the VTX is screwed on through, relative to the
+      center of the mount
+    */
+    pub fn hole_pattern(&self) -> HolePattern
     {
-        scad!(Difference;
+        let pattern = HolePattern::new("vtx", Some(self.fastener()), self.hole_diameter);
+        match self.mounting
         {
-            outer_shape,
-            screwhole,
-            center_cutout,
-        }),
-        centered_square(vec2(3., vtx_width), (true,true))
-    });
+            VtxMounting::Boxed => {
+                let x = self.width / 2. + self.hole_edge_distance + self.thickness;
+                pattern.with_hole("right", x, 0.)
+                    .with_hole("left", -x, 0.)
+            }
+            VtxMounting::Stack => {
+                let offset = self.hole_distance / 2.;
+                pattern.with_hole("back_right", offset, offset)
+                    .with_hole("front_right", -offset, offset)
+                    .with_hole("back_left", offset, -offset)
+                    .with_hole("front_left", -offset, -offset)
+            }
+        }
+    }
+
+    fn place_object_at_holes(&self, object: ScadObject) -> ScadObject
+    {
+        self.hole_pattern().place_object(object)
+    }
+
+    pub fn get_mount(&self) -> ScadObject
+    {
+        let outer_holes = self.place_object_at_holes(
+            scad!(Circle(Diameter(self.hole_diameter + self.hole_padding*2.)))
+        );
+
+        let center_box = centered_square(vec2(self.width, self.width), (true, true));
+
+        let outer_shape = scad!(Hull;
+        {
+            outer_holes,
+            center_box
+        });
+
+        let center_cutout = scad!(Scale2d(vec2(0.3, 0.3)); outer_shape.clone());
+
+        let hole_diameter = match self.threading
+        {
+            Threading::ThroughNut => self.hole_diameter,
+            threading => self.fastener().hole_diameter(threading),
+        };
+        let screwhole = self.place_object_at_holes(
+            scad!(Circle(Diameter(hole_diameter)))
+        );
+
+        let mut shape = scad!(Union;
+        {
+            scad!(Difference;
+            {
+                outer_shape,
+                screwhole,
+                center_cutout,
+            })
+        });
+
+        //Boxed VTXs need something to rest on in the middle
+        if self.mounting == VtxMounting::Boxed
+        {
+            shape.add_child(centered_square(vec2(3., self.width), (true,true)));
+        }
+
+        let extrude_params = LinExtrudeParams
+        {
+            height: self.mount_thickness,
+            .. Default::default()
+        };
+        let mount = scad!(LinearExtrude(extrude_params); shape);
+
+        match self.threading
+        {
+            Threading::ThroughNut => mount,
+            threading => scad!(Difference; {
+                mount,
+                self.hole_pattern().get_threaded_holes(threading, self.mount_thickness)
+            }),
+        }
+    }
+}
 
-    scad!(LinearExtrude(LinExtrudeParams{height: 1.5, .. Default::default()}); shape)
+/**
+  Turns an object that goes up from z = 0 upside down so that it goes down
+  from the top of something that is `height` tall
+*/
+fn from_above(object: ScadObject, height: f32) -> ScadObject
+{
+    scad!(Translate(vec3(0., 0., height)); {
+        scad!(Mirror(z_axis()); object)
+    })
 }
 
-fn get_m3_screw(length: f32) -> ScadObject
+/**
+  Returns the cut for an M3 screw that goes up through a part that is
+  `thickness` thick with its head sunk `depth` into the bottom of the part
+*/
+fn get_m3_screw(head: HeadRecess, depth: f32, thickness: f32) -> ScadObject
 {
-    let screw_padding = 0.5;
-    let head_padding = 1.;
-    let diameter = 3. + screw_padding;
-    let head_diameter = 6. + head_padding;
-    let head_height = 2.;
+    let fastener = Fastener::m3();
 
     scad!(Union;
     {
-        scad!(Cylinder(head_height, Diameter(head_diameter))),
-        scad!(Cylinder(length + head_height, Diameter(diameter))),
+        fastener.get_head_recess(head, depth, CUT_OVERSHOOT),
+        scad!(Translate(vec3(0., 0., -CUT_OVERSHOOT)); {
+            scad!(Cylinder(thickness + CUT_OVERSHOOT * 2., Diameter(fastener.clearance_diameter)))
+        }),
     })
 }
 
@@ -88,6 +315,7 @@ qstruct!(NazeBoard()
     hole_diameter: f32 = 3.,
     hole_distance: f32 = 30.5,
     hole_padding_radius: f32 = hole_diameter / 2. + 1.,
+    mass: f32 = 7.,
 });
 
 impl NazeBoard
@@ -117,22 +345,24 @@ impl NazeBoard
             )
     }
 
-    fn place_object_at_holes(&self, object: ScadObject) -> ScadObject
+    /**
+      Returns the holes for the screws that hold the board, relative to the
+      center of the board. They are as wide as the holes that the screws
+      need, not as the holes in the board
+    */
+    pub fn hole_pattern(&self) -> HolePattern
     {
-        let mut result = scad!(Union);
-        for x in vec!(-self.hole_distance / 2., self.hole_distance / 2.)
-        {
-            for y in vec!(-self.hole_distance / 2., self.hole_distance / 2.)
-            {
-                let translated = scad!(Translate(vec3(x, y, 0.));{
-                    object.clone()
-                });
-
-                result.add_child(translated);
-            }
-        }
+        let offset = self.hole_distance / 2.;
+        HolePattern::for_fastener("flight_controller", Fastener::m3())
+            .with_hole("front_left", -offset, -offset)
+            .with_hole("front_right", -offset, offset)
+            .with_hole("back_left", offset, -offset)
+            .with_hole("back_right", offset, offset)
+    }
 
-        result
+    fn place_object_at_holes(&self, object: ScadObject) -> ScadObject
+    {
+        self.hole_pattern().place_object(object)
     }
 
     fn get_width(&self) -> f32
@@ -186,6 +416,9 @@ qstruct!(BoardCamera()
     lens_diameter: f32 = 17.,
     lens_length: f32 = 24.,
     snowproof_padding_radus: f32 = 3.,
+    mass: f32 = 12.,
+    //Widest angle of the image in degrees
+    field_of_view: f32 = 120.,
 });
 
 impl BoardCamera
@@ -217,6 +450,30 @@ impl BoardCamera
 
         scad!(Cylinder(self.lens_length, Radius(total_radius)))
     }
+
+    /**
+      Returns a cone covering what the camera sees up to `length` mm in front
+      of the lens. The cone is in the same coordinates as the camera model
+    */
+    pub fn get_view_cone(&self, length: f32) -> ScadObject
+    {
+        let lens_front = self.thickness + self.lens_length;
+        let far_radius = self.lens_diameter / 2.
+            + length * (self.field_of_view / 2.).to_radians().tan();
+
+        //Thin discs at both ends of the cone
+        let near = scad!(Translate(vec3(0., 0., lens_front)); {
+            scad!(Cylinder(0.01, Diameter(self.lens_diameter)))
+        });
+        let far = scad!(Translate(vec3(0., 0., lens_front + length)); {
+            scad!(Cylinder(0.01, Radius(far_radius)))
+        });
+
+        scad!(Hull; {
+            near,
+            far
+        })
+    }
 }
 
 fn get_camera_water_seal(camera: &BoardCamera, tricopter_body: &TricopterBody) -> ScadObject
@@ -260,7 +517,8 @@ qstruct!(Esc()
 {
     width: f32 = 20.,
     length: f32 = 25.,
-    thickness: f32 = 4.
+    thickness: f32 = 4.,
+    mass: f32 = 7.,
 });
 
 impl Esc
@@ -287,30 +545,26 @@ qstruct!(EscStack()
 
 impl EscStack
 {
-    pub fn place_object_at_holes(&self, object: ScadObject) -> ScadObject
+    /**
+      Returns the holes that the screws holding the stack together go through
+    */
+    pub fn hole_pattern(&self) -> HolePattern
     {
-        let mut result = scad!(Union);
-
         let x_position = self.esc.width / 2. 
                             + self.screw_padding / 2.
                             + SCREW_DIAMETER / 2.;
         let y_position = self.esc.length / 4.;
 
-        let points = vec!(
-                vec3(x_position, y_position, 0.),
-                vec3(-x_position, y_position, 0.),
-                vec3(x_position, -y_position, 0.),
-                vec3(-x_position, -y_position, 0.),
-            );
-
-        for point in points
-        {
-            result.add_child(scad!(Translate(point);{
-                object.clone()
-            }));
-        }
+        HolePattern::for_fastener("esc_stack", Fastener::m3())
+            .with_hole("back_right", x_position, y_position)
+            .with_hole("front_right", -x_position, y_position)
+            .with_hole("back_left", x_position, -y_position)
+            .with_hole("front_left", -x_position, -y_position)
+    }
 
-        result
+    pub fn place_object_at_holes(&self, object: ScadObject) -> ScadObject
+    {
+        self.hole_pattern().place_object(object)
     }
 
     pub fn get_mid_section(&self) -> ScadObject
@@ -337,6 +591,212 @@ impl EscStack
 }
 
 
+/**
+  The side of the battery that the power leads come out of
+*/
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BatteryLeadExit
+{
+    Front,
+    Back,
+    Left,
+    Right,
+}
+
+impl BatteryLeadExit
+{
+    /**
+      Unit vector pointing out of the battery on the lead exit side. Front
+      is in the negative x direction and left is the negative y direction
+    */
+    pub fn direction(&self) -> na::Vector2<f32>
+    {
+        match *self
+        {
+            BatteryLeadExit::Front => vec2(-1., 0.),
+            BatteryLeadExit::Back => vec2(1., 0.),
+            BatteryLeadExit::Left => vec2(0., -1.),
+            BatteryLeadExit::Right => vec2(0., 1.),
+        }
+    }
+}
+
+qstruct!(Battery()
+{
+    cell_count: u32 = 3,
+    capacity: u32 = 1300,
+    length: f32 = 70.,
+    width: f32 = 30.,
+    height: f32 = 22.,
+    lead_exit: BatteryLeadExit = BatteryLeadExit::Front,
+    mass: f32 = 110.,
+});
+
+impl Battery
+{
+    pub fn lipo_3s_850() -> Battery
+    {
+        let mut result = Battery::new();
+        result.capacity = 850;
+        result.length = 58.;
+        result.width = 30.;
+        result.height = 20.;
+        result.mass = 75.;
+        result
+    }
+
+    pub fn lipo_3s_1300() -> Battery
+    {
+        Battery::new()
+    }
+
+    /**
+      Returns the batteries that there are models of
+    */
+    pub fn known() -> Vec<Battery>
+    {
+        vec!(Battery::lipo_3s_850(), Battery::lipo_3s_1300(), Battery::lipo_4s_1300())
+    }
+
+    pub fn lipo_4s_1300() -> Battery
+    {
+        let mut result = Battery::new();
+        result.cell_count = 4;
+        result.length = 75.;
+        result.width = 34.;
+        result.height = 30.;
+        result.mass = 150.;
+        result
+    }
+
+    /**
+      Half of the extent of the battery in the direction that the leads
+      come out of it
+    */
+    fn half_extent_towards_leads(&self) -> f32
+    {
+        match self.lead_exit
+        {
+            BatteryLeadExit::Front | BatteryLeadExit::Back => self.length / 2.,
+            BatteryLeadExit::Left | BatteryLeadExit::Right => self.width / 2.,
+        }
+    }
+}
+
+
+/**
+  The shape of the pockets that are cut into the body plates to make them
+  lighter
+*/
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LighteningPattern
+{
+    None,
+    //Triangles pointing alternately up and down, leaving a truss of ribs
+    Truss,
+    Honeycomb,
+    //Slots across the plates
+    Slots,
+}
+
+impl LighteningPattern
+{
+    /**
+      Returns a polygon with the corners at the specified angles around the
+      center
+    */
+    fn regular_polygon(center: na::Vector2<f32>, radius: f32, angles: &[f32]) -> ScadObject
+    {
+        let points = angles.iter()
+            .map(|angle| {
+                let radians = angle.to_radians();
+                center + vec2(radians.cos(), radians.sin()) * radius
+            })
+            .collect();
+        scad!(Polygon(PolygonParameters::new(points)))
+    }
+
+    /**
+      Returns pockets covering the area from `min` to `max` with `cell_size`
+      between the centers of neighbouring pockets and `rib_width` between the
+      pockets
+    */
+    pub fn get_pockets(
+        &self,
+        min: na::Vector2<f32>,
+        max: na::Vector2<f32>,
+        cell_size: f32,
+        rib_width: f32
+    ) -> ScadObject
+    {
+        let mut result = scad!(Union);
+        let size = max - min;
+
+        match *self
+        {
+            LighteningPattern::None => {}
+            LighteningPattern::Truss => {
+                //Inradius of a triangle with the full cell size, shrunk so
+                //that half of a rib is left on each side
+                let row_height = cell_size * 3f32.sqrt() / 2.;
+                let inradius = row_height / 3. - rib_width / 2.;
+                let rows = (size.y / row_height).ceil() as usize;
+                let columns = (size.x / (cell_size / 2.)).ceil() as usize + 1;
+
+                for row in 0..rows
+                {
+                    for column in 0..columns
+                    {
+                        let x = min.x + column as f32 * cell_size / 2.;
+                        let y = min.y + row as f32 * row_height;
+                        let shape = if (row + column) % 2 == 0
+                        {
+                            let center = vec2(x, y + row_height / 3.);
+                            Self::regular_polygon(center, inradius * 2., &[90., 210., 330.])
+                        }
+                        else
+                        {
+                            let center = vec2(x, y + row_height * 2. / 3.);
+                            Self::regular_polygon(center, inradius * 2., &[270., 30., 150.])
+                        };
+                        result.add_child(shape);
+                    }
+                }
+            }
+            LighteningPattern::Honeycomb => {
+                //The hexagons have flat sides facing their neighbours in the
+                //same row, and every other row is shifted half a cell
+                let row_height = cell_size * 3f32.sqrt() / 2.;
+                let radius = (cell_size - rib_width) / 3f32.sqrt();
+                let rows = (size.y / row_height).ceil() as usize + 1;
+                let columns = (size.x / cell_size).ceil() as usize + 1;
+
+                for row in 0..rows
+                {
+                    let shift = if row % 2 == 0 { 0. } else { cell_size / 2. };
+                    for column in 0..columns
+                    {
+                        let center = min + vec2(column as f32 * cell_size + shift, row as f32 * row_height);
+                        let angles = [30., 90., 150., 210., 270., 330.];
+                        result.add_child(Self::regular_polygon(center, radius, &angles));
+                    }
+                }
+            }
+            LighteningPattern::Slots => {
+                let columns = (size.x / cell_size).ceil() as usize + 1;
+                for column in 0..columns
+                {
+                    let x = min.x + column as f32 * cell_size;
+                    let slot = centered_square(vec2(cell_size - rib_width, size.y), (true, false));
+                    result.add_child(scad!(Translate2d(vec2(x, min.y)); slot));
+                }
+            }
+        }
+        result
+    }
+}
+
+
 fn get_body_section(inner_width: f32, outer_width: f32, length: f32) -> ScadObject
 {
     let points = vec!(
@@ -402,17 +862,93 @@ qstruct!(TricopterBody()
     camera_offset_from_top: f32 = 10.,
 
     mounting_screw_outline_radius: f32 = 3.,
+    //Smallest wall in mm around the screws that hold the plates and canopy
+    //together
+    min_screw_wall: f32 = 1.,
 
     canopy_max_height: f32 = 31.,
     screw_mount_height: f32 = 7.,
     canopy_bottom_min_height: f32 = screw_mount_height,
-    screwhead_diameter: f32 = 6.5,
 
     side_plate_arc_width: f32 = 36.,
     side_plate_arc_height: f32 = 19.,
     side_plate_thickness: f32 = 2.,
     side_plate_mount_length: f32 = 15.,
     side_plate_front_screw_top_offset: f32 = 7.,
+
+    vtx: Vtx = Vtx::new(),
+    camera: BoardCamera = BoardCamera::new(),
+    //Angle in degrees that the camera is tilted up from looking straight forward
+    camera_uptilt: f32 = 0.,
+    //Distance between the holes that the flight controller is mounted in,
+    //like 30.5 or 20
+    flight_controller_hole_distance: f32 = 30.5,
+
+    battery: Battery = Battery::new(),
+    //X position of the center of the battery
+    battery_position: f32 = 0.,
+    battery_strap_count: u32 = 1,
+    battery_strap_width: f32 = 25.,
+    battery_strap_padding: f32 = 2.,
+    battery_strap_slot_width: f32 = 4.,
+    battery_strap_clearance: f32 = 0.,
+    battery_wire_hole_inset: f32 = 10.,
+    battery_pad_thickness: f32 = 1.5,
+    battery_pad_lip_width: f32 = 1.5,
+    battery_pad_lip_height: f32 = 3.,
+
+    //Pockets cut into the plates to make them lighter
+    lightening: LighteningPattern = LighteningPattern::None,
+    //Distance between the centers of neighbouring pockets
+    lightening_cell_size: f32 = 12.,
+    //Thinnest rib that is left between the pockets and along the edges
+    lightening_rib_width: f32 = 2.,
+    //Solid material kept around the holes, the screw blocks and the arms
+    lightening_keepout: f32 = 3.,
+
+    //Finish of the edges along the top and bottom faces of the plates
+    plate_edge: EdgeFinish = EdgeFinish::Sharp,
+    plate_edge_size: f32 = 0.6,
+    //Radius that the outer corners of the plate outlines are rounded with
+    plate_corner_radius: f32 = 0.,
+    //Depth of the chamfers at the entries of the screw holes of the plates
+    screw_entry_chamfer: f32 = 0.,
+    //Radius that the front corners of the canopy are rounded with. The
+    //rounding eats into the wall so it has to stay below twice its thickness
+    canopy_front_fillet_radius: f32 = 0.,
+
+    //How the threads are made for the screws that hold the canopy and the
+    //plates together, in the bottom plate
+    plate_screw_threading: Threading = Threading::ThroughNut,
+    //How the thread is made for the screw at the front of each side plate
+    side_plate_threading: Threading = Threading::NutTrap,
+    //How the threads are made for the screws of the flight controller, in
+    //the top plate. With a nut the screws go in from below
+    flight_controller_threading: Threading = Threading::ThroughNut,
+    //How the threads are made for the screws that hold the front arms, the
+    //screw in the middle of the back block and the camera mount screws, in
+    //the bottom plate
+    arm_mount_threading: Threading = Threading::ThroughNut,
+    arm_stopper_threading: Threading = Threading::ThroughNut,
+    back_block_screw_threading: Threading = Threading::ThroughNut,
+    camera_mount_threading: Threading = Threading::ThroughNut,
+    //How the threads are made for the screws that hold the side plates to
+    //their mounts, in the side plates
+    side_plate_back_threading: Threading = Threading::ThroughNut,
+
+    //Heads of the screws that hold the canopy and their length under the
+    //head. The heads are sunk into the screw tabs of the canopy if the
+    //screws are too short to reach through both plates and the threading of
+    //the bottom plate. The back screws also go through the mount blocks
+    canopy_screw_head: HeadRecess = HeadRecess::Counterbore,
+    canopy_front_screw_length: f32 = 18.,
+    canopy_back_screw_length: f32 = 30.,
+    //Heads of the screws that hold the flight controller and their length.
+    //With nuts the heads are sunk into the bottom of the top plate deep
+    //enough for the screws to reach `flight_controller_screw_reach` above it
+    flight_controller_screw_head: HeadRecess = HeadRecess::Counterbore,
+    flight_controller_screw_length: f32 = 10.,
+    flight_controller_screw_reach: f32 = 8.,
 });
 
 
@@ -423,12 +959,6 @@ impl TricopterBody
     */
     pub fn get_body_bottom(&self) -> ScadObject
     {
-        //Parameters for extruding things to the height of the bottom plate
-        let low_extrude_params = LinExtrudeParams
-        {
-            height:self.height,
-            ..Default::default()
-        };
         //Parameters for extruding things to the height of the plate +
         //the height of the blocky parts
         let high_extrude_params = LinExtrudeParams
@@ -444,13 +974,18 @@ impl TricopterBody
             .. Default::default()
         };
 
-        //The body without things cut out
-        let body = scad!(Union;{
-            scad!(LinearExtrude(low_extrude_params);
-            {
+        let plate_outline = edges::finish_corners(
+            scad!(Union; {
                 self.get_body_shape(),
                 self.get_front_section()
             }),
+            EdgeFinish::Round,
+            self.plate_corner_radius
+        );
+
+        //The body without things cut out
+        let body = scad!(Union;{
+            edges::extrude_with_edges(plate_outline, self.height, self.plate_edge, self.plate_edge_size),
             scad!(LinearExtrude(high_extrude_params.clone());
             {
                 self.get_back_mount_block(),
@@ -473,27 +1008,29 @@ impl TricopterBody
             scad!(Translate(vec3(0., 0., self.height)); cutter)
         };
 
+        let hole_diameter = |threading| Fastener::m3().hole_diameter(threading);
+
         let camera_mount_screwholes = {
-            let x_offset = self.front_section_length * 13./16.;
-            let y_offset = 3.5;
-            let hole = scad!(Circle(Diameter(SCREW_DIAMETER)));
-            let shape = scad!(Translate2d(vec2(-x_offset, 0.)); {
-                scad!(Translate2d(vec2(0., y_offset)); hole.clone()),
-                scad!(Translate2d(vec2(0., -y_offset)); hole.clone())
-            });
+            let hole = scad!(Circle(Diameter(hole_diameter(self.camera_mount_threading))));
+            let shape = self.camera_mount_screw_pattern().place_object(hole);
             scad!(LinearExtrude(high_extrude_params.clone()); shape)
         };
 
+        let plate_screw_diameter = hole_diameter(self.plate_screw_threading);
         let front_screwholes = scad!(LinearExtrude(high_extrude_params); {
-            self.get_front_screwholes(Diameter(SCREW_DIAMETER))
+            self.get_front_screwholes(Diameter(plate_screw_diameter))
         });
 
         //Cutting out things like holes
-        let with_holes = scad!(Difference;
+        let mut with_holes = scad!(Difference;
         {
             body
-            , self.get_front_arm_screw_holes()
-            , self.get_back_screwholes()
+            , self.get_front_arm_screw_holes(
+                        hole_diameter(self.arm_mount_threading),
+                        hole_diameter(self.arm_stopper_threading))
+            , self.get_back_screwholes(
+                        hole_diameter(self.back_block_screw_threading),
+                        plate_screw_diameter)
             , camera_box_cutout
             , camera_mount_screwholes
             , self.get_battery_strap_holes()
@@ -501,6 +1038,18 @@ impl TricopterBody
                         self.get_back_block_cable_hole())
             , front_screwholes
         });
+        if self.lightening != LighteningPattern::None
+        {
+            with_holes.add_child(self.get_lightening_pockets());
+        }
+        if self.screw_entry_chamfer > 0.
+        {
+            with_holes.add_child(self.get_screw_entry_chamfers());
+        }
+        for threads in self.get_bottom_plate_threads()
+        {
+            with_holes.add_child(threads);
+        }
 
         scad!(Intersection;
         {
@@ -520,19 +1069,13 @@ impl TricopterBody
             ..Default::default()
         };
 
+        let outline = scad!(Difference; {
+            edges::finish_corners(self.get_top_plate_outline(), EdgeFinish::Round, self.plate_corner_radius),
+            self.get_front_screwholes(Diameter(SCREW_DIAMETER))
+        });
         let body = scad!(Union;
         {
-            scad!(LinearExtrude(linear_extrude.clone());
-            {
-                scad!(Difference; {
-                    scad!(Union; {
-                        self.get_body_shape(),
-                        self.get_mid_section_outline(),
-                        self.get_back_screw_tab_outline()
-                    }),
-                    self.get_front_screwholes(Diameter(SCREW_DIAMETER))
-                })
-            }),
+            edges::extrude_with_edges(outline, self.height, self.plate_edge, self.plate_edge_size),
         });
 
         let camera_box = scad!(LinearExtrude(linear_extrude.clone());
@@ -540,21 +1083,44 @@ impl TricopterBody
                 self.get_camera_box_bottom_cutout_outline()
             });
 
+        let flight_controller_screw = match self.flight_controller_threading
+        {
+            Threading::ThroughNut => {
+                let depth = head_recess_depth(
+                    self.flight_controller_screw_length,
+                    self.height,
+                    self.flight_controller_screw_reach
+                );
+                get_m3_screw(self.flight_controller_screw_head, depth, self.height)
+            }
+            threading => {
+                from_above(Fastener::m3().get_threaded_hole(threading, self.height), self.height)
+            }
+        };
         let screwholes = scad!(Union;
         {
-            NazeBoard::new().place_object_at_holes(get_m3_screw(self.height)),
+            self.flight_controller_mount().place_object_at_holes(flight_controller_screw),
         });
 
-        let with_holes = scad!(Difference;
+        let mut with_holes = scad!(Difference;
         {
             body,
-            self.get_front_arm_screw_holes(),
-            self.get_back_screwholes(),
+            self.get_front_arm_screw_holes(SCREW_DIAMETER, SCREW_DIAMETER),
+            self.get_back_screwholes(SCREW_DIAMETER, SCREW_DIAMETER),
             self.get_top_plate_motor_wire_hole(),
             self.get_battery_wire_hole(),
             screwholes,
             camera_box,
         });
+        if self.lightening != LighteningPattern::None
+        {
+            with_holes.add_child(self.get_lightening_pockets());
+        }
+        //The screws go into the top plate from above
+        if self.screw_entry_chamfer > 0.
+        {
+            with_holes.add_child(from_above(self.get_screw_entry_chamfers(), self.height));
+        }
 
         let full_shape = scad!(Intersection;{
             with_holes,
@@ -565,18 +1131,126 @@ impl TricopterBody
     }
 
     /**
-      Function for getting the 2d outline of the body. Does not include
-      the cutoff to 14cm on the sides that is done by self.get_side_bounds()
+      Returns the outline of the parts of the plates that are kept solid: the
+      arm channels, the blocks and tabs that the screws go through, the camera
+      box and the material around every hole
     */
-    fn get_body_shape(&self) -> ScadObject
+    fn get_lightening_keepout(&self) -> ScadObject
     {
-        let back_segment = get_body_section(
-                self.inner_width,
-                self.back_outer_width,
-                self.radius
-            );
-        let side_segments = get_body_section(
-                self.inner_width,
+        let keepout = self.lightening_keepout;
+        let mut result = scad!(Union);
+
+        let arm_channel = centered_square(vec2(self.radius, self.arm_width + keepout * 2.), (false, true));
+        for angle in &ARM_ANGLES
+        {
+            result.add_child(scad!(Rotate(*angle, z_axis()); arm_channel.clone()));
+        }
+
+        for pattern in self.hole_patterns()
+        {
+            result.add_child(pattern.place_object(scad!(Circle(Radius(pattern.diameter / 2. + keepout)))));
+        }
+
+        //The strap holes are as long as the straps are wide
+        let strap_hole = centered_square(
+            vec2(
+                self.battery_strap_width + self.battery_strap_padding + keepout * 2.,
+                self.battery_strap_slot_width + keepout * 2.
+            ),
+            (true, true)
+        );
+        result.add_child(self.place_object_at_battery_strap_slots(strap_hole));
+
+        //The battery wire hole has zip tie holes on both sides and a channel
+        //out to the edge of the plate in the direction of the leads
+        let wire_hole = self.battery_wire_hole_position();
+        let direction = self.battery.lead_exit.direction();
+        let wire_hole_radius = BATTERY_WIRE_HOLE_RADIUS * 2. + SCREW_DIAMETER / 2. + keepout;
+        let channel_end = wire_hole + direction * 100.;
+        result.add_child(scad!(Hull; {
+            scad!(Translate2d(wire_hole); scad!(Circle(Radius(wire_hole_radius)))),
+            scad!(Translate2d(channel_end); scad!(Circle(Radius(wire_hole_radius))))
+        }));
+
+        result.add_child(scad!(Offset(OffsetType::Radius(keepout), false); {
+            self.get_back_mount_block(),
+            self.get_back_screw_tab_outline(),
+            self.get_front_screw_tab_outline(),
+            self.get_camera_box_outline()
+        }));
+        result
+    }
+
+    /**
+      Returns the 2d outline of the pockets that are cut through the plates
+      to make them lighter. The pockets stay inside the mid section, a rib
+      width from its edges and away from everything that has to stay solid
+    */
+    fn get_lightening_pocket_outline(&self) -> ScadObject
+    {
+        let rib_width = self.lightening_rib_width;
+        let half_width = self.center_width.max(self.front_section_width).max(self.back_outer_width) / 2.;
+        let min = vec2(-self.front_section_length, -half_width);
+        let max = vec2(self.radius, half_width);
+        let pockets = self.lightening.get_pockets(min, max, self.lightening_cell_size, rib_width);
+
+        let allowed = scad!(Difference; {
+            scad!(Offset(OffsetType::Radius(-rib_width), false); self.get_mid_section_outline()),
+            self.get_lightening_keepout()
+        });
+
+        scad!(Intersection; {
+            allowed,
+            pockets
+        })
+    }
+
+    /**
+      Returns the lightening pockets extruded through a plate
+    */
+    fn get_lightening_pockets(&self) -> ScadObject
+    {
+        let extrude_params = LinExtrudeParams{height: self.height, ..Default::default()};
+        scad!(LinearExtrude(extrude_params); self.get_lightening_pocket_outline())
+    }
+
+    /**
+      Returns the chamfers at the entries of the screws that hold the plates
+      and the canopy together, at the bottom face of a plate
+    */
+    fn get_screw_entry_chamfers(&self) -> ScadObject
+    {
+        scad!(Union; {
+            edges::screw_entry_chamfers(&self.front_screw_pattern(), self.screw_entry_chamfer),
+            edges::screw_entry_chamfers(&self.back_screw_pattern(), self.screw_entry_chamfer)
+        })
+    }
+
+    /**
+      Returns the 2d outline of the top plate without any holes
+    */
+    fn get_top_plate_outline(&self) -> ScadObject
+    {
+        scad!(Union; {
+            self.get_body_shape(),
+            self.get_mid_section_outline(),
+            self.get_back_screw_tab_outline()
+        })
+    }
+
+    /**
+      Function for getting the 2d outline of the body. Does not include
+      the cutoff to 14cm on the sides that is done by self.get_side_bounds()
+    */
+    fn get_body_shape(&self) -> ScadObject
+    {
+        let back_segment = get_body_section(
+                self.inner_width,
+                self.back_outer_width,
+                self.radius
+            );
+        let side_segments = get_body_section(
+                self.inner_width,
                 self.outer_width,
                 self.radius
             );
@@ -638,51 +1312,102 @@ impl TricopterBody
 
       (The arm mount and arm blocker)
      */
-    fn get_front_arm_screw_holes(&self) -> ScadObject
+    fn get_front_arm_screw_holes(&self, mount_diameter: f32, stopper_diameter: f32) -> ScadObject
     {
-        //The distance from the center to the point of the stopping screws
-        let stopper_screw_distance = self.radius - 8.;
-
-        //Distance from the center to the mount point of the arms
-        let mount_screw_offset = self.radius - 25.;
-
-        let mount_hole = scad!(Translate(vec3(mount_screw_offset, 0., 0.));{
-            scad!(Cylinder(self.height, Diameter(SCREW_DIAMETER)))
-        });
-
-        let stopper_hole = scad!(Translate(vec3(stopper_screw_distance, self.arm_width / 2. + SCREW_DIAMETER / 2., 0.));{
-            scad!(Cylinder(self.height, Diameter(SCREW_DIAMETER)))
-        });
-
-        let rotated = scad!(Rotate(120., vec3(0., 0., 1.));
-        {
-            mount_hole,
-            stopper_hole
-        });
+        let cylinder = |diameter| scad!(Cylinder(self.height, Diameter(diameter)));
 
         scad!(Union;
         {
-            rotated.clone(),
-            scad!(Mirror(vec3(0., 1., 0.));{rotated})
+            self.front_arm_mount_pattern().place_object(cylinder(mount_diameter)),
+            self.front_arm_stopper_pattern().place_object(cylinder(stopper_diameter))
         })
     }
 
     /**
-      Returns cylinders for the back screwholes
+      Returns cylinders for the back screwholes. The hole in the middle of
+      the back block and the holes for the screws that hold the canopy have
+      the specified diameters
      */
-    fn get_back_screwholes(&self) -> ScadObject
+    fn get_back_screwholes(&self, center_diameter: f32, diameter: f32) -> ScadObject
     {
-        //The holes should be well in the mount block
-        let center_x_offset = self.radius * 7. / 8.;
-
-        let cylinder = scad!(Cylinder(self.get_bottom_total_height(), Diameter(SCREW_DIAMETER)));
+        let cylinder = |diameter| {
+            scad!(Cylinder(self.get_bottom_total_height(), Diameter(diameter)))
+        };
 
         scad!(Union; {
-            scad!(Translate(vec3(center_x_offset, 0., 0.)); cylinder.clone()),
-            self.place_object_at_back_mount_locations(cylinder)
+            self.back_block_center_screw_pattern().place_object(cylinder(center_diameter)),
+            self.place_object_at_back_mount_locations(cylinder(diameter))
         })
     }
 
+    /**
+      Returns the cuts for the threads of the screws that go into the bottom
+      plate, for the joints that are not held with nuts. The screws come from
+      above, through the plate and through the mount blocks at the back
+    */
+    fn get_bottom_plate_threads(&self) -> Vec<ScadObject>
+    {
+        let block_height = self.get_bottom_total_height();
+        let joints = vec!(
+            (self.front_screw_pattern(), self.plate_screw_threading, self.height),
+            (self.back_screw_pattern(), self.plate_screw_threading, block_height),
+            (self.front_arm_mount_pattern(), self.arm_mount_threading, self.height),
+            (self.front_arm_stopper_pattern(), self.arm_stopper_threading, self.height),
+            (self.back_block_center_screw_pattern(), self.back_block_screw_threading, block_height),
+            (self.camera_mount_screw_pattern(), self.camera_mount_threading, self.height),
+        );
+
+        joints.into_iter()
+            .filter(|&(_, threading, _)| threading != Threading::ThroughNut)
+            .map(|(pattern, threading, depth)| {
+                from_above(pattern.get_threaded_holes(threading, depth), depth)
+            })
+            .collect()
+    }
+
+    /**
+      Returns the threaded joints of the body with the number of fasteners
+      in each
+    */
+    pub fn threaded_joints(&self) -> Vec<ThreadedJoint>
+    {
+        let pattern_joint = |pattern: HolePattern, threading| ThreadedJoint{
+            name: pattern.name.clone(),
+            fastener: pattern.fastener.unwrap_or_else(Fastener::m3),
+            threading,
+            count: pattern.holes.len(),
+        };
+
+        //Both side plates are bolted to their mounts at the back
+        let side_plate_back = self.side_plate_back_screw_pattern();
+        let side_plate_back = ThreadedJoint{
+            count: side_plate_back.holes.len() * 2,
+            .. pattern_joint(side_plate_back, self.side_plate_back_threading)
+        };
+
+        vec!(
+            pattern_joint(self.front_screw_pattern(), self.plate_screw_threading),
+            pattern_joint(self.back_screw_pattern(), self.plate_screw_threading),
+            pattern_joint(
+                self.flight_controller_mount().hole_pattern(),
+                self.flight_controller_threading
+            ),
+            //One screw at the front of each side plate
+            ThreadedJoint{
+                name: "side_plate_front".to_string(),
+                fastener: Fastener::m3(),
+                threading: self.side_plate_threading,
+                count: 2,
+            },
+            side_plate_back,
+            pattern_joint(self.front_arm_mount_pattern(), self.arm_mount_threading),
+            pattern_joint(self.front_arm_stopper_pattern(), self.arm_stopper_threading),
+            pattern_joint(self.back_block_center_screw_pattern(), self.back_block_screw_threading),
+            pattern_joint(self.camera_mount_screw_pattern(), self.camera_mount_threading),
+            pattern_joint(self.vtx.hole_pattern(), self.vtx.threading),
+        )
+    }
+
     /**
       Returns screwholes centered around the x-axis with a radius that fits
       within the back block.
@@ -768,21 +1493,23 @@ impl TricopterBody
      */
     fn get_top_plate_canopy_edges(&self) -> ScadObject
     {
-        //Function for doing the linear extrusion
-        fn extrusion_function(outline: ScadObject, height: f32) -> ScadObject
-        {
-            let linear_extrude_parameters = LinExtrudeParams{
-                height: height,
-                ..Default::default()
-            };
-
-            scad!(LinearExtrude(linear_extrude_parameters);
-            {
-                outline
-            })
+        let linear_extrude_parameters = LinExtrudeParams{
+            height: self.edge_height,
+            ..Default::default()
         };
 
+        scad!(LinearExtrude(linear_extrude_parameters);
+        {
+            self.get_top_plate_canopy_edge_outline()
+        })
+    }
 
+    /**
+      Returns the 2d outline of the edge that goes inside the edge of the
+      canopy
+     */
+    fn get_top_plate_canopy_edge_outline(&self) -> ScadObject
+    {
         let outer_radius_offset =
                 - self.canopy_thickness
                 + self.edge_thickness
@@ -790,22 +1517,14 @@ impl TricopterBody
 
         let inner_radius_offset = -self.canopy_thickness;
 
-        let outer = {
-            let polygon = scad!(Offset(OffsetType::Radius(outer_radius_offset), false);
-            {
-                self.get_mid_section_outline()
-            });
-
-            extrusion_function(polygon, self.edge_height)
-        };
-        let inner = {
-            let polygon = scad!(Offset(OffsetType::Radius(inner_radius_offset), false);
-            {
-                self.get_mid_section_outline()
-            });
-
-            extrusion_function(polygon, self.edge_height)
-        };
+        let outer = scad!(Offset(OffsetType::Radius(outer_radius_offset), false);
+        {
+            self.get_mid_section_outline()
+        });
+        let inner = scad!(Offset(OffsetType::Radius(inner_radius_offset), false);
+        {
+            self.get_mid_section_outline()
+        });
 
         scad!(Difference;{
             outer,
@@ -876,7 +1595,10 @@ impl TricopterBody
             );
 
         let x_end = -(self.front_section_length);
-        let y_start = (self.front_section_width / 2.) - self.camera_spot_side_width;
+        //The camera board stands in the hole so it is never made narrower
+        //than the board
+        let y_start = ((self.front_section_width / 2.) - self.camera_spot_side_width)
+            .max(self.camera.width / 2. + self.camera_box_edge_padding);
         let y_end = -y_start;
 
         let points = vec!(
@@ -890,29 +1612,28 @@ impl TricopterBody
     }
 
     /**
-      Translate an object to the location of the hole where the motor wires
-      come out.
-
-      This is the small hole that goes through the back block
+      Returns the holes where the motor wires come out, one on each side of
+      the back arm
      */
-    fn place_object_at_motor_wire_holes(&self, object: ScadObject) -> ScadObject
+    pub fn motor_wire_hole_pattern(&self) -> HolePattern
     {
         let padding = 1.5;
         let hole_x = self.radius * 3. / 4.;
         let hole_y = self.arm_width / 2. + self.motor_wire_hole_radius + padding;
 
-        let translated = scad!(Translate(vec3(hole_x, hole_y, 0.)); object);
+        HolePattern::new("motor_wire_holes", None, self.motor_wire_hole_radius * 2.)
+            .with_symmetric_holes("wire", hole_x, hole_y)
+    }
 
-        let mirrored = scad!(Mirror(vec3(0., 1., 0.));
-        {
-            translated.clone()
-        });
+    /**
+      Translate an object to the location of the hole where the motor wires
+      come out.
 
-        scad!(Union;
-        {
-            translated,
-            mirrored
-        })
+      This is the small hole that goes through the back block
+     */
+    fn place_object_at_motor_wire_holes(&self, object: ScadObject) -> ScadObject
+    {
+        self.motor_wire_hole_pattern().place_object(object)
     }
 
     /**
@@ -936,13 +1657,27 @@ impl TricopterBody
     }
 
     /**
-      Returns a hole where the battery wires can go through in front of the
-      flight controller
+      Returns the center of the hole that the battery wires go through
+     */
+    fn battery_wire_hole_position(&self) -> na::Vector2<f32>
+    {
+        let battery = &self.battery;
+        let distance = battery.half_extent_towards_leads() - self.battery_wire_hole_inset;
+        vec2(self.battery_position, 0.) + battery.lead_exit.direction() * distance
+    }
+
+    /**
+      Returns a hole where the battery wires can go through. The hole is placed
+      above the end of the battery where the leads come out and the channel
+      leading to it points the same way as the leads.
      */
     fn get_battery_wire_hole(&self) -> ScadObject
     {
-        let radius = 4.;
-        let position = vec3(-self.radius / 3., 0., 0.);
+        let radius = BATTERY_WIRE_HOLE_RADIUS;
+        let direction = self.battery.lead_exit.direction();
+        let position = self.battery_wire_hole_position();
+        //The channel is built pointing towards negative x
+        let angle = (-direction.y).atan2(-direction.x).to_degrees();
         
         let hole = scad!(Cylinder(self.height, Radius(radius)));
         let cube = scad!(Translate(vec3(-100., -radius, 0.)); {
@@ -957,24 +1692,35 @@ impl TricopterBody
             })
         };
 
-        scad!(Translate(position); hole, cube, zip_tie_holes)
+        scad!(Translate(vec3(position.x, position.y, 0.));
+        {
+            scad!(Rotate(angle, z_axis()); hole, cube, zip_tie_holes)
+        })
     }
 
     /**
-      Returns a cylinder that cuts a hole for the camera
-
-      z_offset is the offset in the z axis from the bottom of the top part of
-      the frame
+      Returns the middle of the front of the camera lens in the coordinates
+      of the canopy. The lens looks out through the lens hole from there
      */
-    fn get_camera_lens_hole(&self, z_offset: f32) -> ScadObject
+    pub fn camera_lens_front(&self) -> na::Vector3<f32>
     {
-        let camera_board = BoardCamera::new();
-        let offset = -(self.front_section_length - camera_board.lens_length / 2.);
+        vec3(-(self.front_section_length + self.camera.lens_length / 2.), 0., self.edge_height)
+    }
 
-        let hole = camera_board.get_lens_hole();
+    /**
+      Returns a cylinder that cuts a hole for the camera in the canopy
+     */
+    fn get_camera_lens_hole(&self) -> ScadObject
+    {
+        let hole = self.camera.get_lens_hole();
         let rotated = scad!(Rotate(-90., vec3(0., 1., 0.)); hole);
 
-        scad!(Translate(vec3(offset, 0., z_offset)); rotated)
+        //The camera is tilted around the front of the lens
+        scad!(Translate(self.camera_lens_front()); {
+            scad!(Rotate(self.camera_uptilt, y_axis()); {
+                scad!(Translate(vec3(self.camera.lens_length, 0., 0.)); rotated)
+            })
+        })
     }
 
     /**
@@ -988,57 +1734,143 @@ impl TricopterBody
         centered_cube(vec3(10000., width, 1000.), (true, true, false))
     }
 
+    /**
+      Returns the x positions of the battery straps. The straps are spread
+      evenly along the length of the battery
+    */
+    fn get_battery_strap_positions(&self) -> Vec<f32>
+    {
+        let count = self.battery_strap_count.max(1);
+        let length = self.battery.length;
+        let start = self.battery_position - length / 2.;
+
+        (0..count)
+            .map(|i| start + length * (i as f32 + 0.5) / count as f32)
+            .collect()
+    }
+
+    /**
+      Returns the slots on both sides of the battery that each strap goes
+      through. The holes are at the middle of the slots and the diameter is
+      the width of the slots
+    */
+    pub fn battery_strap_slot_pattern(&self) -> HolePattern
+    {
+        let y_separation = self.battery.width / 2.
+            + self.battery_strap_clearance
+            + self.battery_strap_slot_width / 2.;
+
+        let mut result = HolePattern::new("battery_strap_slots", None, self.battery_strap_slot_width);
+        for (i, x) in self.get_battery_strap_positions().into_iter().enumerate()
+        {
+            result = result.with_symmetric_holes(&format!("strap_{}", i), x, y_separation);
+        }
+        result
+    }
+
+    /**
+      Translate an object to the slots on both sides of the battery that
+      each strap goes through
+    */
+    fn place_object_at_battery_strap_slots(&self, object: ScadObject) -> ScadObject
+    {
+        self.battery_strap_slot_pattern().place_object(object)
+    }
+
     /**
       Returns cubes where the battery straps should go
     */
     fn get_battery_strap_holes(&self) -> ScadObject
     {
-        let y_separation = 17.;
-        let x_size = 27.;
-        let y_size = 4.;
+        let x_size = self.battery_strap_width + self.battery_strap_padding;
+        let y_size = self.battery_strap_slot_width;
 
         let cube = 
             centered_cube(vec3(x_size, y_size, self.height), (true,true,false));
 
-        let translated = scad!(Translate(vec3(0., y_separation, 0.)); cube);
-
-        scad!(Union;
-        {
-            translated.clone(),
-            scad!(Mirror(vec3(0., 1., 0.)); translated)
-        })
+        self.place_object_at_battery_strap_slots(cube)
     }
 
     /**
-      Positions an object at the location of the 2 mounting screw holes in the
-      front
+      Returns a pad that goes between the battery and the bottom plate to
+      keep the battery from sliding around. Printed in a flexible material.
+
+      The pad has a lip around the edges with notches where the straps go
+      over it and an opening for the battery leads.
     */
-    fn place_object_at_front_mount_locations(&self, object: ScadObject)
-            -> ScadObject
+    fn get_battery_pad(&self) -> ScadObject
     {
-        let offset_from_side = self.mounting_screw_outline_radius;
-        let x_offset = -(self.front_section_length * 11. / 16.);
-        let y_separation = self.front_section_width / 2. + offset_from_side;
+        let battery = &self.battery;
+        let lip_width = self.battery_pad_lip_width;
+        let total_height = self.battery_pad_thickness + self.battery_pad_lip_height;
+
+        let outer_size = vec3(
+            battery.length + lip_width * 2.,
+            battery.width + lip_width * 2.,
+            total_height
+        );
+        let outer = centered_cube(outer_size, (true, true, false));
+
+        let inner = {
+            let size = vec3(battery.length, battery.width, total_height);
+            let shape = centered_cube(size, (true, true, false));
+            scad!(Translate(vec3(0., 0., self.battery_pad_thickness)); shape)
+        };
+
+        let lead_opening = {
+            let direction = battery.lead_exit.direction();
+            let size = vec3(battery.width, battery.width, total_height);
+            let shape = centered_cube(size, (true, true, false));
+            let offset = direction * battery.half_extent_towards_leads();
+            scad!(Translate(vec3(offset.x, offset.y, self.battery_pad_thickness)); shape)
+        };
+
+        let strap_notches = {
+            let x_size = self.battery_strap_width + self.battery_strap_padding;
+            let size = vec3(x_size, outer_size.y, total_height);
+            let mut result = scad!(Union);
+            for x in self.get_battery_strap_positions()
+            {
+                let shape = centered_cube(size, (true, true, false));
+                let translated = scad!(
+                    Translate(vec3(x - self.battery_position, 0., self.battery_pad_thickness));
+                    shape
+                );
+                result.add_child(translated);
+            }
+            result
+        };
 
-        scad!(Translate(vec3(x_offset, 0., 0.));
+        let pad = scad!(Difference;
         {
-            scad!(Translate(vec3(0., y_separation, 0.)); object.clone()),
-            scad!(Translate(vec3(0., -y_separation, 0.)); object.clone()),
-        })
+            outer,
+            inner,
+            lead_opening,
+            strap_notches
+        });
+
+        scad!(Translate(vec3(self.battery_position, 0., 0.)); pad)
     }
 
-    fn place_object_at_back_mount_locations(&self, object: ScadObject)
-            -> ScadObject
+    /**
+      Returns the 2 screws that hold the back of the canopy and the top plate
+      to the back mount block. The plates and the canopy all cut their holes
+      at this pattern
+    */
+    pub fn back_screw_pattern(&self) -> HolePattern
     {
         let offset_from_side = self.mounting_screw_outline_radius;
         let x_offset = (self.radius * 7.5/8.) - self.mounting_screw_outline_radius;
         let y_separation = 2.*self.outer_width / 3. + offset_from_side;
 
-        scad!(Translate(vec3(x_offset, 0., 0.));
-        {
-            scad!(Translate(vec3(0., y_separation, 0.)); object.clone()),
-            scad!(Translate(vec3(0., -y_separation, 0.)); object.clone()),
-        })
+        HolePattern::for_fastener("back_screws", Fastener::m3())
+            .with_symmetric_holes("back_screw", x_offset, y_separation)
+    }
+
+    fn place_object_at_back_mount_locations(&self, object: ScadObject)
+            -> ScadObject
+    {
+        self.back_screw_pattern().place_object(object)
     }
 
     /**
@@ -1049,82 +1881,215 @@ impl TricopterBody
     {
         let outline_radius = self.mounting_screw_outline_radius;
         let outline_circle = scad!(Circle(Radius(outline_radius)));
-        let outline_circles = 
-            self.place_object_at_front_mount_locations(outline_circle);
 
-        scad!(Hull;
-        {
-            outline_circles
-        })
+        //The tabs are not joined like the back ones since the camera sits
+        //between them
+        self.front_screw_pattern().place_object(outline_circle)
     }
 
-    fn get_front_screwholes(&self, size: CircleType) -> ScadObject {
-        let screwhole = scad!(Circle(size));
+    /**
+      Returns the 2 screws that hold the front of the canopy and the top plate
+      to the bottom plate. The plates and the canopy all cut their holes at
+      this pattern
+    */
+    pub fn front_screw_pattern(&self) -> HolePattern
+    {
         let offset_from_side = self.mounting_screw_outline_radius;
         let x_offset = -(self.front_section_length * 13. / 16.);
         let y_separation = self.front_section_width / 2. - offset_from_side;
 
-        scad!(Translate(vec3(x_offset, 0., 0.));
-        {
-            scad!(Translate(vec3(0., y_separation, 0.)); screwhole.clone()),
-            scad!(Translate(vec3(0., -y_separation, 0.)); screwhole.clone()),
-        })
+        HolePattern::for_fastener("front_screws", Fastener::m3())
+            .with_symmetric_holes("front_screw", x_offset, y_separation)
     }
 
     /**
-      Returns the outline of the mounting screws for the back section
+      Returns the screws that the front arms are mounted on, one in the
+      middle of each arm channel
     */
-    fn get_back_screw_tab_outline(&self) -> ScadObject
+    pub fn front_arm_mount_pattern(&self) -> HolePattern
     {
-        let outline_radius = self.mounting_screw_outline_radius;
-        let outline_circle = scad!(Circle(Radius(outline_radius)));
-        let outline_circles = 
-            self.place_object_at_back_mount_locations(outline_circle);
-
-        scad!(Difference; {
-            scad!(Hull;
-            {
-                outline_circles
-            }),
-            centered_square(vec2(10000., self.arm_width + self.arm_padding), (false, true))
-        })
+        self.front_arm_pattern("front_arm_mounts", "front_arm_mount", self.radius - 25., 0.)
     }
 
-    fn circle_at_back_screwholes(&self, size: CircleType) -> ScadObject {
-        let outline_radius = self.mounting_screw_outline_radius;
-
-        let x_end = self.radius + outline_radius;
+    /**
+      Returns the screws beside the ends of the front arms that stop the
+      arms from turning
+    */
+    pub fn front_arm_stopper_pattern(&self) -> HolePattern
+    {
+        let y_offset = self.arm_width / 2. + SCREW_DIAMETER / 2.;
+        self.front_arm_pattern("front_arm_stoppers", "front_arm_stopper", self.radius - 8., y_offset)
+    }
 
-        let hole = {
-            let circle = scad!(Circle(size));
+    /**
+      Returns a pattern with a hole at `x`, `y` along the front arm on the
+      right side and the mirrored hole on the left arm
+    */
+    fn front_arm_pattern(&self, name: &str, hole_name: &str, x: f32, y: f32) -> HolePattern
+    {
+        let (sin, cos) = ARM_ANGLES[1].to_radians().sin_cos();
 
-            scad!(Translate2d(vec2(x_end, 0.)); circle)
-        };
+        HolePattern::for_fastener(name, Fastener::m3())
+            .with_symmetric_holes(hole_name, x * cos - y * sin, x * sin + y * cos)
+    }
 
-        scad!(Difference;
-        {
-            self.place_object_at_back_screwholes(hole)
-        })
+    /**
+      Returns the screw in the middle of the back block. It sits well in the
+      mount block
+    */
+    pub fn back_block_center_screw_pattern(&self) -> HolePattern
+    {
+        HolePattern::for_fastener("back_block_center", Fastener::m3())
+            .with_hole("back_block_center", self.radius * 7. / 8., 0.)
     }
 
-    fn place_object_at_back_screwholes(&self, object: ScadObject) -> ScadObject {
-        let y_separation = self.outer_width * 7. / 8.;
+    /**
+      Returns the 2 screws that the camera mount is held with
+    */
+    pub fn camera_mount_screw_pattern(&self) -> HolePattern
+    {
+        let x_offset = -(self.front_section_length * 13. / 16.);
 
-        scad!(Union;
-        {
-            scad!(Translate2d(vec2(0., y_separation / 2.)); object.clone()),
-            scad!(Translate2d(vec2(0., -y_separation / 2.)); object)
-        })
+        HolePattern::for_fastener("camera_mount", Fastener::m3())
+            .with_symmetric_holes("camera_mount_screw", x_offset, 3.5)
     }
 
-
     /**
-      Returns the hole for routing cables thorugh the back section of the bottom
-      body section
+      Returns the 2 screws that hold a side plate to its mount, in the
+      coordinates of the side plate outline. The holes are a tight fit for
+      bolts
     */
-    fn get_back_block_cable_hole(&self) -> ScadObject
+    pub fn side_plate_back_screw_pattern(&self) -> HolePattern
     {
-        let height = self.get_bottom_total_height() * 3. / 8.;
+        let back_length = self.radius * self.back_block_length_factor;
+        let back_height = self.side_plate_arc_height;
+        let x_offset = -(back_length - self.side_plate_mount_length / 2.);
+        let diameter = match self.side_plate_back_threading
+        {
+            Threading::ThroughNut => 3.2,
+            threading => Fastener::m3().hole_diameter(threading),
+        };
+
+        HolePattern::new("side_plate_back", Some(Fastener::m3()), diameter)
+            .with_hole("low", x_offset, back_height / 3.)
+            .with_hole("high", x_offset, 3. * back_height / 4.)
+    }
+
+    /**
+      Returns the flight controller that the holes of the top plate are made
+      for
+    */
+    pub fn flight_controller_mount(&self) -> NazeBoard
+    {
+        let mut result = NazeBoard::new();
+        result.hole_distance = self.flight_controller_hole_distance;
+        result
+    }
+
+    /**
+      Returns the holes that go through the body plates, relative to the
+      bottom of the body
+    */
+    pub fn hole_patterns(&self) -> Vec<HolePattern>
+    {
+        vec!(
+            self.front_screw_pattern(),
+            self.back_screw_pattern(),
+            self.motor_wire_hole_pattern(),
+            self.flight_controller_mount().hole_pattern(),
+            self.battery_strap_slot_pattern(),
+        )
+    }
+
+    /**
+      Returns the interfaces between the parts of the body that are screwed
+      together. The canopy is screwed through the top plate into the bottom
+      plate
+    */
+    pub fn mating_interfaces(&self) -> Vec<MatingInterface>
+    {
+        let wall = self.min_screw_wall;
+
+        //The screws go through the plate at the front and through the back
+        //blocks at the back
+        let bottom_outline = scad!(Union; {
+            self.get_body_shape(),
+            self.get_front_section(),
+            self.get_back_mount_block(),
+            self.get_back_screw_tab_outline()
+        });
+
+        //All the parts cut their holes at the same patterns. The interfaces
+        //check that every part has room for them
+        let front = self.front_screw_pattern();
+        let back = self.back_screw_pattern();
+        let (front_depth, back_depth) = self.canopy_screw_head_depths(self.screw_mount_height);
+
+        vec!(
+            MatingInterface::new("bottom_to_top", "body_bottom", "body_top")
+                .with_feature("bottom_front_material", Feature::Boss(
+                    front.threaded(self.plate_screw_threading), bottom_outline.clone(), wall
+                ))
+                .with_feature("bottom_back_material", Feature::Boss(
+                    back.threaded(self.plate_screw_threading), bottom_outline, wall
+                ))
+                .with_feature("top_front_material", Feature::Boss(
+                    front.clone(), self.get_top_plate_outline(), wall
+                ))
+                .with_feature("top_back_material", Feature::Boss(
+                    back.clone(), self.get_top_plate_outline(), wall
+                )),
+            MatingInterface::new("top_to_canopy", "body_top", "canopy")
+                .with_feature("front_screw_length", Feature::SunkHeads(
+                    front.clone(), front_depth, self.screw_mount_height
+                ))
+                .with_feature("back_screw_length", Feature::SunkHeads(
+                    back.clone(), back_depth, self.screw_mount_height
+                ))
+                .with_feature("front_tabs", Feature::Boss(
+                    front, self.get_front_screw_tab_outline(), wall
+                ))
+                .with_feature("back_tabs", Feature::Boss(
+                    back, self.get_back_screw_tab_outline(), wall
+                ))
+                .with_feature("canopy_edge", Feature::Lip(
+                    self.get_top_plate_canopy_edge_outline(),
+                    self.canopy_edge_cutout(),
+                    self.edge_padding
+                )),
+        )
+    }
+
+    fn get_front_screwholes(&self, size: CircleType) -> ScadObject {
+        self.front_screw_pattern().place_object(scad!(Circle(size)))
+    }
+
+    /**
+      Returns the outline of the mounting screws for the back section
+    */
+    fn get_back_screw_tab_outline(&self) -> ScadObject
+    {
+        let outline_radius = self.mounting_screw_outline_radius;
+        let outline_circle = scad!(Circle(Radius(outline_radius)));
+        let outline_circles = 
+            self.place_object_at_back_mount_locations(outline_circle);
+
+        scad!(Difference; {
+            scad!(Hull;
+            {
+                outline_circles
+            }),
+            centered_square(vec2(10000., self.arm_width + self.arm_padding), (false, true))
+        })
+    }
+
+    /**
+      Returns the hole for routing cables thorugh the back section of the bottom
+      body section
+    */
+    fn get_back_block_cable_hole(&self) -> ScadObject
+    {
+        let height = self.get_bottom_total_height() * 3. / 8.;
         let radius = Radius(self.motor_wire_hole_radius);
         let bottom_length = self.radius * self.back_block_length_factor / 2.;
 
@@ -1329,69 +2294,93 @@ impl TricopterBody
         })
     }
 
-    fn get_canopy_screwholes(&self, screw_length: f32) -> ScadObject {
-        let screwhole_extrude_params = LinExtrudeParams{
-            height: screw_length
-            , .. Default::default()
+    /**
+      Returns how deep the heads of the front and back canopy screws have to
+      be sunk into tabs that are `tab_height` thick. The screws go down
+      through the top plate and into the bottom plate as far as its
+      threading needs, at the back through the mount blocks
+    */
+    fn canopy_screw_head_depths(&self, tab_height: f32) -> (f32, f32)
+    {
+        let fastener = Fastener::m3();
+        let depth = |length, bottom_thickness| {
+            let reach = self.height
+                + fastener.thread_reach(self.plate_screw_threading, bottom_thickness);
+            head_recess_depth(length, tab_height, reach)
         };
-        let screwhead_extrude_params = LinExtrudeParams{
-            height: self.canopy_max_height
+
+        (
+            depth(self.canopy_front_screw_length, self.height),
+            depth(self.canopy_back_screw_length, self.get_bottom_total_height())
+        )
+    }
+
+    fn get_canopy_screwholes(&self, tab_height: f32) -> ScadObject {
+        let screwhole_extrude_params = LinExtrudeParams{
+            height: tab_height
             , .. Default::default()
         };
 
-        let head_holes = scad!(LinearExtrude(screwhead_extrude_params); {
-            self.get_front_screwholes(Diameter(self.screwhead_diameter)),
-            self.circle_at_back_screwholes(Diameter(self.screwhead_diameter))
+        //The recesses reach up through the canopy so that the screwdriver
+        //gets to the heads
+        let fastener = Fastener::m3();
+        let (front_depth, back_depth) = self.canopy_screw_head_depths(tab_height);
+        let recess = |depth| scad!(Mirror(z_axis()); {
+            fastener.get_head_recess(self.canopy_screw_head, depth, self.canopy_max_height)
+        });
+        let front_screws = self.front_screw_pattern();
+        let back_screws = self.back_screw_pattern();
+        let head_holes = scad!(Union; {
+            front_screws.place_object(recess(front_depth)),
+            back_screws.place_object(recess(back_depth))
         });
 
         let holes = scad!(LinearExtrude(screwhole_extrude_params); {
-            self.get_front_screwholes(Diameter(SCREW_DIAMETER)),
-            self.circle_at_back_screwholes(Diameter(SCREW_DIAMETER))
+            front_screws.get_circles(),
+            back_screws.get_circles()
         });
         scad!(Union; {
             scad!(Translate(vec3(0., 0., -self.edge_height)); holes),
-            scad!(Translate(vec3(0., 0., screw_length - self.edge_height)); {
+            scad!(Translate(vec3(0., 0., tab_height - self.edge_height)); {
                 head_holes
             })
         })
     }
 
-    fn get_front_fillet(&self, height: f32) -> ScadObject
+    /**
+      Returns the corners that are cut off the front of the canopy to round
+      them. `offset` is how far the outside of the canopy is offset from the
+      mid section
+    */
+    fn get_front_fillet(&self, offset: f32, height: f32) -> ScadObject
     {
-        let radius = self.front_section_corner_radius;
+        let radius = self.canopy_front_fillet_radius;
 
         let centering = (false, true);
-        let main_size = vec2(self.radius, self.front_section_width);
+        let main_size = vec2(self.radius, self.front_section_width + offset * 2.);
 
         let main_square = centered_square(main_size, centering);
-        let small_square = scad!(Offset(OffsetType::Delta(-radius), false);
-                    main_square.clone());
-        let rounded_square = scad!(Offset(OffsetType::Radius(radius), false);
-                    small_square);
+        let rounded_square = edges::finish_corners(main_square.clone(), EdgeFinish::Round, radius);
 
         let corners = scad!(Difference;
         {
-            main_square.clone(),
+            main_square,
             rounded_square
         });
 
+        //Only the corners at the front are cut
         let cutoff_square = scad!(Translate2d(vec2(self.radius / 2., 0.));
         {
-            centered_square(vec2(self.radius/2., self.front_section_width),
-                    centering)
+            centered_square(vec2(self.radius/2., main_size.y), centering)
         });
 
         let outline = scad!(Difference;
         {
-            scad!(Intersection;
-            {
-                main_square
-                , corners
-            })
+            corners
             , cutoff_square
         });
 
-        let translated = scad!(Translate2d(vec2(-self.front_section_length, 0.));
+        let translated = scad!(Translate2d(vec2(-self.front_section_length - offset, 0.));
         {
             outline
         });
@@ -1407,9 +2396,10 @@ impl TricopterBody
 
     fn get_vtx_connector_hole(&self) -> ScadObject
     {
-        let screw_section_diameter = 6.;
-        let screw_section_length = 2.5;
-        let outer_diameter = 8.;
+        let connector = self.vtx.connector;
+        let screw_section_diameter = connector.panel_diameter();
+        let screw_section_length = connector.panel_length();
+        let outer_diameter = connector.clearance_diameter();
         let outer_length = self.canopy_thickness - screw_section_length + 5.;
 
         let z_offset = self.canopy_max_height / 2.;
@@ -1460,23 +2450,34 @@ impl TricopterBody
             self.get_mid_section_outline()
         });
 
-        let body = scad!(Union;
+        let shell = scad!(Difference;
         {
-            self.get_canopy_outside(extra_offset)
-            , self.get_canopy_screw_tabs(self.screw_mount_height)
-            , self.extrude_canopy_edge(canopy_edge, 3.)
+            scad!(Union;
+            {
+                self.get_canopy_outside(extra_offset)
+                , self.extrude_canopy_edge(canopy_edge, 3.)
+            })
+            , self.get_canopy_outside(-3.)
+            , self.extrude_canopy_edge(self.canopy_edge_cutout(), 0.)
         });
 
-        let camera_offset = self.edge_height;
-        scad!(Difference;
+        //The front tabs are inside the canopy so they are added after it
+        //has been hollowed out
+        let mut result = scad!(Difference;
         {
-            body
-            , self.get_canopy_outside(-3.)
-            , self.get_camera_lens_hole(camera_offset)
-            , self.extrude_canopy_edge(self.canopy_edge_cutout(), 0.)
+            scad!(Union;
+            {
+                shell
+                , self.get_canopy_screw_tabs(self.screw_mount_height)
+            })
+            , self.get_camera_lens_hole()
             , self.get_canopy_screwholes(self.screw_mount_height)
-            // , self.get_front_fillet(self.canopy_max_height)
-        })
+        });
+        if self.canopy_front_fillet_radius > 0.
+        {
+            result.add_child(self.get_front_fillet(extra_offset, self.canopy_max_height));
+        }
+        result
     }
 
     fn get_side_plate_mount(&self) -> ScadObject {
@@ -1600,14 +2601,7 @@ impl TricopterBody
         }));
 
         let holes = {
-            let hole_shape = scad!(Circle(Diameter(screw_diameter)));
-
-            // Screwholes
-            let back_x_offset = back_length - arch_length / 2.;
-            let back_low_pos = vec2(-back_x_offset, back_height/3.);
-            let back_high_pos = vec2(-back_x_offset, 3.*back_height/4.);
-            let back_low = scad!(Translate2d(back_low_pos); hole_shape.clone());
-            let back_high = scad!(Translate2d(back_high_pos); hole_shape.clone());
+            let back_screwholes = self.side_plate_back_screw_pattern().get_circles();
 
             let fc_hole = {
                 let y_offset = 14.;
@@ -1620,8 +2614,7 @@ impl TricopterBody
             };
 
             scad!(Union; {
-                back_low,
-                back_high,
+                back_screwholes,
                 fc_hole
             })
         };
@@ -1653,7 +2646,7 @@ impl TricopterBody
                 - y_padding;
             let extra_height = 2.;
             let inner_nut_offset = 1.5;
-            let inner_nut_width = 5.3;
+            let inner_nut_width = Fastener::m3().nut_width;
             let outer_nut_width = inner_nut_width + 5.;
 
             let hole = scad!(Cylinder(100., Diameter(screw_diameter)));
@@ -1673,10 +2666,15 @@ impl TricopterBody
                 })
             };
 
-            let cutout = scad!(Union; {
-                nut_inner,
-                hole
-            });
+            //The screw goes in from the side away from the nut boss
+            let cutout = match self.side_plate_threading
+            {
+                Threading::NutTrap => scad!(Union; {
+                    nut_inner,
+                    hole
+                }),
+                threading => Fastener::m3().get_threaded_hole(threading, thickness + extra_height),
+            };
 
             (position(cutout), position(nut_outer))
         };
@@ -1701,14 +2699,22 @@ impl TricopterBody
             scad!(LinearExtrude(extrude_params); groove_outline)
         };
 
-        scad!(Difference; {
+        let mut result = scad!(Difference; {
             scad!(Union; {
                 outer_extruded,
                 front_nut_outer
             }),
             grooves,
             front_nut_cut
-        })
+        });
+        if self.side_plate_back_threading != Threading::ThroughNut
+        {
+            result.add_child(
+                self.side_plate_back_screw_pattern()
+                    .get_threaded_holes(self.side_plate_back_threading, thickness)
+            );
+        }
+        result
     }
 
     fn side_plate_front_bracket(&self) -> ScadObject {
@@ -1755,9 +2761,25 @@ qstruct!(ServoMount() {
     side_thickness: f32 = 2.,
     prop_clearance: f32 = 10.,
     top_offset: f32 = 7.,
+    servo_mass: f32 = 9.,
+    //How far the servo can tilt the motor to each side, in degrees
+    servo_tilt_range: f32 = 40.,
 });
 
 impl ServoMount {
+    /**
+      Returns the screws that come with the servo. They cut their own thread
+      into the tabs of the mount
+    */
+    pub fn threaded_joint(&self) -> ThreadedJoint {
+        ThreadedJoint {
+            name: "servo_tabs".to_string(),
+            fastener: Fastener::m2(),
+            threading: Threading::SelfTapping,
+            count: 2,
+        }
+    }
+
     fn full(&self) -> ScadObject {
         let extrude_params = LinExtrudeParams {
             height: self.servo_depth,
@@ -1959,23 +2981,94 @@ fn get_camera_cushion() -> ScadObject
     })
 }
 
-use std::io::prelude::*;
-use std::fs::OpenOptions;
-fn add_text_to_history_file(content: &str, history_file: &str)
+/**
+  Writes the code of the file with repeated parts defined once as modules.
+  Returns the code that was written
+*/
+fn write_scad_file(sfile: &ScadFile, path: &str) -> String
+{
+    let code = match scad_modules::with_modules(&sfile.get_code())
+    {
+        Ok(code) => code,
+        Err(e) => {
+            println!("Failed to find repeated parts of the code: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(e) = std::fs::write(path, &code)
+    {
+        println!("Failed to write {}: {}", path, e);
+        std::process::exit(1);
+    }
+    code
+}
+
+/**
+  Stores the generated code in the history together with the code of every
+  printed part, so that the parts can be compared between entries even if
+  they are not in the generated code
+*/
+fn record_history(assembly: &Assembly, code: &str)
+{
+    let parts = assembly.get_print_parts().iter()
+        .map(|part| (part.name.clone(), history::part_code(&part.object)))
+        .collect::<Vec<_>>();
+    let parameters = parameters::ParameterSet::of(assembly);
+    match history::record(&history::history_directory(), &parts, &parameters, code)
+    {
+        Ok(entry) => println!("Stored as history entry {}", entry.id),
+        Err(e) => {
+            println!("Failed to store the design in the history: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/**
+  Lists the history, shows the parameters of an entry or writes the code of
+  an entry back to a file
+*/
+fn history_command(args: &[String])
 {
-    let mut target_file = OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open(history_file).unwrap();
+    let directory = history::history_directory();
+    let arg = |i: usize| args.get(i).map(|arg| arg.as_str());
 
-    let file_divider = "##__start_of_new_file__##\n";
+    let result = match (arg(0), arg(1))
+    {
+        (None, _) | (Some("list"), _) => history::list(&directory).map(|entries| {
+            for entry in entries
+            {
+                println!("{}", entry);
+            }
+        }),
+        (Some("show"), Some(id)) => history::find(&directory, id).and_then(|entry| {
+            let parameters = history::load_parameters(&directory, &entry)?;
+            println!("{}\n", entry);
+            print!("{}", parameters.to_text());
+            Ok(())
+        }),
+        (Some("emit"), Some(id)) => history::find(&directory, id).and_then(|entry| {
+            let path = arg(2).unwrap_or("out.scad");
+            std::fs::write(path, history::load_code(&directory, &entry)?)?;
+            println!("Wrote history entry {} to {}", entry.id, path);
+            Ok(())
+        }),
+        _ => {
+            println!("Usage: history [list | show <id> | emit <id> [file]]");
+            std::process::exit(1);
+        }
+    };
 
-    target_file.write_all(file_divider.as_bytes()).unwrap();
-    target_file.write_all(content.as_bytes()).unwrap();
+    if let Err(e) = result
+    {
+        println!("{}", e);
+        std::process::exit(1);
+    }
 }
 
 
-fn test_esc_stack(sfile: &mut ScadFile)
+fn test_esc_stack() -> ScadObject
 {
     let esc_rotation = 90.;
     let esc = add_named_color(
@@ -1994,45 +3087,536 @@ fn test_esc_stack(sfile: &mut ScadFile)
     let x_pos = 40.;
     let z_offset = 6.;
     let z_pos = 25.;
+    let mut result = scad!(Union);
     for i in 0..3
     {
-        sfile.add_object(
+        result.add_child(
                 scad!(Translate(vec3(x_pos, 0., z_pos + (i as f32) * z_offset)); esc.clone())
             );
-        sfile.add_object(
+        result.add_child(
                 scad!(Translate(vec3(x_pos, 0., z_pos + (i as f32) * z_offset + 3.)); holder.clone())
             );
     }
+    result
+}
+
+
+fn print_mass_model(assembly: &Assembly)
+{
+    match MassModel::new(assembly, 1.)
+    {
+        Ok(model) => print!("{}", model),
+        Err(e) => {
+            println!("Failed to estimate the mass: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+
+/**
+  Prints the inertia of the craft in the format requested on the command line
+*/
+fn print_inertia(assembly: &Assembly, format: Option<&str>)
+{
+    let model = match MassModel::new(assembly, 1.)
+    {
+        Ok(model) => model,
+        Err(e) => {
+            println!("Failed to estimate the mass: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let report = inertia::InertiaReport::new(&model);
+
+    match format
+    {
+        None | Some("json") => print!("{}", report.to_json()),
+        Some("urdf") => print!("{}", report.to_urdf_inertial()),
+        Some("sdf") => print!("{}", report.to_sdf_inertial()),
+        Some("yaml") => print!("{}", report.to_yaml()),
+        Some(other) => {
+            println!("Unknown inertia format '{}', expected json, urdf, sdf or yaml", other);
+            std::process::exit(1);
+        }
+    }
+}
+
+
+fn place_battery(assembly: &mut Assembly)
+{
+    match balance::place_battery(assembly, 2.)
+    {
+        Ok(placement) => print!("{}", placement),
+        Err(e) => {
+            println!("Failed to place the battery: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+
+/**
+  Prints the largest propeller that fits and exits with an error if the
+  current propellers hit anything
+*/
+fn print_prop_clearance(assembly: &Assembly)
+{
+    match props::PropClearance::new(assembly)
+    {
+        Ok(clearance) => {
+            print!("{}", clearance);
+            if !clearance.intersections().is_empty()
+            {
+                std::process::exit(1);
+            }
+        }
+        Err(e) => {
+            println!("Failed to check the propeller clearance: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+
+/**
+  Prints the parts that are in view of the camera. With `overlay` the view
+  is also drawn on top of the assembly in out.scad
+*/
+fn check_camera_view(assembly: &Assembly, overlay: bool)
+{
+    match camera_view::CameraView::new(assembly)
+    {
+        Ok(view) => print!("{}", view),
+        Err(e) => {
+            println!("Failed to check the camera view: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    if overlay
+    {
+        let mut sfile = ScadFile::new();
+        sfile.set_detail(20);
+        sfile.add_object(camera_view::get_overlay(assembly));
+        write_scad_file(&sfile, "out.scad");
+    }
+}
+
+
+/**
+  Prints the parts of the assembly that overlap and exits with an error
+  if there are any
+*/
+fn check_interference(assembly: &Assembly)
+{
+    match interference::check_assembly(assembly, 1.)
+    {
+        Ok(ref interferences) if interferences.is_empty() => println!("No parts overlap"),
+        Ok(interferences) => {
+            for interference in interferences
+            {
+                println!("{}", interference);
+            }
+            std::process::exit(1);
+        }
+        Err(e) => {
+            println!("Failed to check for interference: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+
+fn check_wall_thickness(assembly: &Assembly)
+{
+    match wall_thickness::WallThicknessReport::new(assembly)
+    {
+        Ok(report) => print!("{}", report),
+        Err(e) => {
+            println!("Failed to check the wall thickness: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+
+fn check_overhangs(assembly: &Assembly)
+{
+    match overhang::OverhangReport::new(assembly)
+    {
+        Ok(report) => print!("{}", report),
+        Err(e) => {
+            println!("Failed to check the overhangs: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+
+/**
+  Rewrites the golden files that the parts are tested against
+*/
+fn bless_golden_files(assembly: &Assembly)
+{
+    match golden::bless(assembly, &golden::golden_directory())
+    {
+        Ok(ref changed) if changed.is_empty() => println!("The golden files are up to date"),
+        Ok(changed) => println!("Updated the golden files of {}", changed.join(", ")),
+        Err(e) => {
+            println!("Failed to write the golden files: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/**
+  Exits without generating anything if parts that are fastened together
+  don't agree on where they meet
+*/
+fn check_mating_interfaces(assembly: &Assembly)
+{
+    match mating::check_all(&assembly.body.mating_interfaces())
+    {
+        Ok(ref disagreements) if disagreements.is_empty() => {}
+        Ok(disagreements) => {
+            println!("Parts that are fastened together don't match:");
+            for disagreement in disagreements
+            {
+                println!("    {}", disagreement);
+            }
+            std::process::exit(1);
+        }
+        Err(e) => {
+            println!("Failed to check the mating interfaces: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+
+/**
+  Prints every hole of the body with the fastener that goes through it. The
+  holes of the VTX mount are relative to the center of the mount
+*/
+fn print_holes(assembly: &Assembly)
+{
+    for pattern in assembly.body.hole_patterns()
+    {
+        print!("{}", pattern);
+    }
+    print!("{}", assembly.body.vtx.hole_pattern());
+}
+
+
+/**
+  Prints the printed parts and the hardware that one craft needs
+*/
+fn print_bom(assembly: &Assembly)
+{
+    print!("{}", bom::Bom::of(assembly));
+}
+
+
+/**
+  Lists the parameters that differ between two versions of the design and
+  the printed parts that each change affects. The versions are history
+  entries, parameter files or `current`
+*/
+fn print_diff(args: &[String])
+{
+    if args.is_empty() || args.len() > 2
+    {
+        println!("Usage: diff <old version> [new version]");
+        std::process::exit(1);
+    }
+    let new_version = args.get(1).map(|arg| arg.as_str()).unwrap_or("current");
+
+    let result = design_diff::load_version(&args[0])
+        .and_then(|old| Ok((old, design_diff::load_version(new_version)?)))
+        .and_then(|(old, new)| design_diff::diff(&old, &new));
+
+    match result
+    {
+        Ok(ref changes) if changes.is_empty() => println!("The versions have the same parameters"),
+        Ok(changes) => {
+            for change in changes
+            {
+                print!("{}", change);
+            }
+        }
+        Err(e) => {
+            println!("Failed to compare the versions: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+
+/**
+  Writes the geometry that changed in a part between two versions to
+  out.scad, with added material in green and removed material in red
+*/
+fn write_geometry_diff(args: &[String])
+{
+    if args.len() < 2 || args.len() > 3
+    {
+        println!("Usage: diff-part <part> <old version> [new version]");
+        std::process::exit(1);
+    }
+    let part = &args[0];
+    let new_version = args.get(2).map(|arg| arg.as_str()).unwrap_or("current");
+
+    let result = design_diff::load_part_code(&args[1], part)
+        .and_then(|old| Ok((old, design_diff::load_part_code(new_version, part)?)))
+        .and_then(|(old, new)| Ok(design_diff::GeometryDiff::new(&old, &new)?));
+
+    let diff = match result
+    {
+        Ok(diff) => diff,
+        Err(e) => {
+            println!("Failed to compare the versions of {}: {}", part, e);
+            std::process::exit(1);
+        }
+    };
+
+    match diff.volumes(1.)
+    {
+        Ok((added, removed)) => {
+            println!("{} gains {:.1} mm^3 and loses {:.1} mm^3", part, added, removed)
+        }
+        Err(e) => println!("Failed to measure the changes: {}", e),
+    }
+
+    let code = match scad_modules::with_modules(&diff.code())
+    {
+        Ok(code) => code,
+        Err(e) => {
+            println!("Failed to find repeated parts of the code: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = std::fs::write("out.scad", code)
+    {
+        println!("Failed to write out.scad: {}", e);
+        std::process::exit(1);
+    }
+}
+
+
+/**
+  Generates the variants of a sweep file into a directory and prints which
+  of them can't be built or printed
+*/
+fn generate_sweep(args: &[String])
+{
+    if args.is_empty() || args.len() > 2
+    {
+        println!("Usage: sweep <sweep file> [directory]");
+        std::process::exit(1);
+    }
+    let directory = args.get(1).map(|arg| arg.as_str()).unwrap_or("variants");
+
+    let result = std::fs::read_to_string(&args[0])
+        .map_err(sweep::SweepError::from)
+        .and_then(|text| sweep::Sweep::parse(&text))
+        .and_then(|sweep| sweep::generate(&sweep, std::path::Path::new(directory)));
+
+    match result
+    {
+        Ok(results) => {
+            for result in &results
+            {
+                print!("{}", result);
+            }
+            let failed = results.iter()
+                .filter(|result| !result.validation.is_empty() || !result.bed_fit.is_empty())
+                .count();
+            println!("Wrote {} variants to {}, {} of them have problems", results.len(), directory, failed);
+        }
+        Err(e) => {
+            println!("Failed to generate the sweep: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+
+/**
+  Solves the parameters of the frame from a target file and prints the ones
+  that differ from the default frame. The parameters are written to a file
+  if one is given, unless the targets conflict
+*/
+fn solve_targets(args: &[String])
+{
+    if args.is_empty() || args.len() > 2
+    {
+        println!("Usage: solve <target file> [parameter file]");
+        std::process::exit(1);
+    }
+
+    let result = std::fs::read_to_string(&args[0])
+        .map_err(|e| e.to_string())
+        .and_then(|text| targets::Targets::parse(&text).map_err(|e| e.to_string()))
+        .and_then(|targets| targets::solve(&targets).map_err(|e| e.to_string()));
+
+    let solution = match result
+    {
+        Ok(solution) => solution,
+        Err(e) => {
+            println!("Failed to solve the targets: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let parameters = parameters::ParameterSet::of(&solution.assembly);
+    let changes = design_diff::changed_parameters(
+        &parameters::ParameterSet::of(&Assembly::new()),
+        &parameters
+    );
+    for (name, old, new) in changes
+    {
+        println!("{}: {} -> {}", name, old.unwrap_or_default(), new.unwrap_or_default());
+    }
+
+    if !solution.conflicts.is_empty()
+    {
+        println!("The targets conflict:");
+        for conflict in solution.conflicts
+        {
+            println!("    {}", conflict);
+        }
+        std::process::exit(1);
+    }
+
+    if let Some(path) = args.get(1)
+    {
+        if let Err(e) = std::fs::write(path, parameters.to_text())
+        {
+            println!("Failed to write {}: {}", path, e);
+            std::process::exit(1);
+        }
+        println!("Wrote the parameters to {}", path);
+    }
+}
+
+
+/**
+  Prints the mass that lightening pockets save on the body plates, for one
+  pattern or for all of them
+*/
+fn print_lightening(assembly: &mut Assembly, pattern: Option<&str>)
+{
+    use parameters::ParameterValue;
+
+    let patterns = match pattern
+    {
+        Some(name) => match LighteningPattern::from_parameter(name)
+        {
+            Some(pattern) => vec!(pattern),
+            None => {
+                println!("Usage: lighten [Truss | Honeycomb | Slots]");
+                std::process::exit(1);
+            }
+        },
+        None => vec!(LighteningPattern::Truss, LighteningPattern::Honeycomb, LighteningPattern::Slots),
+    };
+
+    for pattern in patterns
+    {
+        match lightening::LighteningReport::new(assembly, pattern)
+        {
+            Ok(report) => print!("{}", report),
+            Err(e) => {
+                println!("Failed to weigh the plates: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+
+/**
+  Writes URDF and SDF models of the craft with meshes to a directory
+*/
+fn export_simulation_model(assembly: &Assembly, directory: &str)
+{
+    match simulation::export(assembly, std::path::Path::new(directory))
+    {
+        Ok(()) => println!("Wrote the simulation model to {}", directory),
+        Err(e) => {
+            println!("Failed to export the simulation model: {}", e);
+            std::process::exit(1);
+        }
+    }
 }
 
 
 fn main() 
 {
-    let mut sfile = ScadFile::new();
-    sfile.set_detail(20);
+    let mut assembly = Assembly::new();
+
+    let args = std::env::args().collect::<Vec<_>>();
+    match args.get(1).map(|arg| arg.as_str())
+    {
+        Some("mass") => return print_mass_model(&assembly),
+        Some("inertia") => {
+            return print_inertia(&assembly, args.get(2).map(|arg| arg.as_str()))
+        }
+        //Moves the battery straps to where the battery balances the craft
+        Some("balance") => place_battery(&mut assembly),
+        Some("props") => return print_prop_clearance(&assembly),
+        Some("interference") => return check_interference(&assembly),
+        Some("walls") => return check_wall_thickness(&assembly),
+        Some("overhangs") => return check_overhangs(&assembly),
+        Some("bless") => return bless_golden_files(&assembly),
+        Some("holes") => return print_holes(&assembly),
+        Some("bom") => return print_bom(&assembly),
+        Some("history") => return history_command(&args[2..]),
+        Some("diff") => return print_diff(&args[2..]),
+        Some("diff-part") => return write_geometry_diff(&args[2..]),
+        Some("sweep") => return generate_sweep(&args[2..]),
+        Some("solve") => return solve_targets(&args[2..]),
+        Some("lighten") => {
+            return print_lightening(&mut assembly, args.get(2).map(|arg| arg.as_str()))
+        }
+        Some("view") => {
+            return check_camera_view(&assembly, args.get(2).is_some_and(|arg| arg == "overlay"))
+        }
+        Some("urdf") => {
+            let directory = args.get(2).map(|arg| arg.as_str()).unwrap_or("sim");
+            return export_simulation_model(&assembly, directory)
+        }
+        _ => {}
+    }
 
-    sfile.add_object(TricopterBody::new().get_body_bottom());
-    // sfile.add_object(scad!(Translate(vec3(0., 0., 30.)); TricopterBody::new().get_body_top()));
-    //sfile.add_object(get_vtx_mount());
-    //sfile.add_object(EscStack::new().get_mid_section());
-    //sfile.add_object(get_camera_cushion());
-    // sfile.add_object(scad!(Translate(vec3(0., 0., 35.)); TricopterBody::new().get_canopy()));
-    //sfile.add_object(NazeBoard::new().get_board());
-    //sfile.add_object(scad!(Translate(vec3(0., 0., 27.));
-    //                       add_named_color("brown", DysEsc::new().get_board())));
-    //sfile.add_object(get_camera_water_seal(&BoardCamera::new(), &TricopterBody::new()));
-    // sfile.add_object(TricopterBody::new().get_side_plate_mount());
-    // sfile.add_object(TricopterBody::new().side_plate_shape());
-    // sfile.add_object(TricopterBody::new().side_plate_front_bracket());
-    // sfile.add_object(ServoMount::new().flex_holder());
-    /*
-    sfile.add_object(
+    check_mating_interfaces(&assembly);
+
+    //The objects that are written to the file
+    let parts = vec!(
+        ("body_bottom", assembly.body.get_body_bottom()),
+        // ("body_top", scad!(Translate(vec3(0., 0., 30.)); TricopterBody::new().get_body_top())),
+        //("vtx_mount", Vtx::new().get_mount()),
+        //("esc_stack_mid_section", EscStack::new().get_mid_section()),
+        //("camera_cushion", get_camera_cushion()),
+        // ("canopy", scad!(Translate(vec3(0., 0., 35.)); TricopterBody::new().get_canopy())),
+        //("flight_controller", NazeBoard::new().get_board()),
+        //("esc", scad!(Translate(vec3(0., 0., 27.));
+        //                       add_named_color("brown", DysEsc::new().get_board()))),
+        //("camera_water_seal", get_camera_water_seal(&BoardCamera::new(), &TricopterBody::new())),
+        // ("side_plate_mount", TricopterBody::new().get_side_plate_mount()),
+        // ("side_plate", TricopterBody::new().side_plate_shape()),
+        // ("side_plate_front_bracket", TricopterBody::new().side_plate_front_bracket()),
+        // ("servo_flex_holder", ServoMount::new().flex_holder()),
+        // ("battery_pad", TricopterBody::new().get_battery_pad()),
+        /*
+        ("flight_controller",
             add_named_color(
                 "steelblue",
                 scad!(Translate(vec3(0., 0., 30.)); NazeBoard::new().get_board())
             )
-        );
-    sfile.add_object(
+        ),
+        ("camera",
             add_named_color(
                 "dimgray",
                 scad!(Translate(vec3(-40., 0., 30.)); 
@@ -2043,13 +3627,374 @@ fn main()
                     })
                 })
             )
-        );
+        ),
+        ("esc_stack", test_esc_stack()),
+        */
+    );
+
+    let mut sfile = ScadFile::new();
+    sfile.set_detail(20);
+    for (_, object) in &parts
+    {
+        sfile.add_object(object.clone());
+    }
+
+    let code = write_scad_file(&sfile, "out.scad");
 
-    test_esc_stack(&mut sfile);
+    record_history(&assembly, &code);
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use evaluate::{BoundingBox, Shape, Solid, Vec3};
+
+    //Number of random bodies that every invariant is checked on
+    const BODY_COUNT: usize = 16;
+    //Step in mm used when looking along the axis of a hole
+    const HOLE_STEP: f32 = 0.25;
+
+    /**
+      Small xorshift generator so that the random bodies are the same on
+      every run
     */
+    struct Random(u32);
 
+    impl Random
+    {
+        fn next(&mut self) -> f32
+        {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 17;
+            self.0 ^= self.0 << 5;
+            self.0 as f32 / u32::MAX as f32
+        }
+
+        fn range(&mut self, min: f32, max: f32) -> f32
+        {
+            min + (max - min) * self.next()
+        }
+    }
+
+    /**
+      Returns bodies with their main dimensions picked at random within the
+      ranges that the frame is designed for
+    */
+    fn random_bodies() -> Vec<TricopterBody>
+    {
+        let mut random = Random(0x2545_f491);
+        (0..BODY_COUNT).map(|_| {
+            let mut body = TricopterBody::new();
+            body.radius = random.range(65., 85.);
+            body.height = random.range(3., 5.);
+            body.outer_width = random.range(20., 26.);
+            body.back_outer_width = random.range(30., 36.);
+            body.inner_width = random.range(46., 54.);
+            body.center_width = body.inner_width + random.range(4., 8.);
+            body.front_section_width = body.inner_width - 6.;
+            body.front_section_length = random.range(50., 65.);
+            body.arm_width = random.range(8., 12.);
+            body.arm_padding = random.range(0., 0.5);
+            body.motor_wire_hole_radius = random.range(3., 4.5);
+            body.mounting_screw_outline_radius = random.range(3., 3.5);
+            body
+        }).collect()
+    }
+
+    /**
+      Returns the randomised parameters of a body for failure messages
+    */
+    fn describe(body: &TricopterBody) -> String
+    {
+        format!(
+            "radius {}, height {}, outer_width {}, back_outer_width {}, inner_width {}, \
+             center_width {}, front_section_length {}, arm_width {}, arm_padding {}, \
+             motor_wire_hole_radius {}, mounting_screw_outline_radius {}",
+            body.radius, body.height, body.outer_width, body.back_outer_width,
+            body.inner_width, body.center_width, body.front_section_length,
+            body.arm_width, body.arm_padding, body.motor_wire_hole_radius,
+            body.mounting_screw_outline_radius
+        )
+    }
+
+    /**
+      Returns true if there is no material along the vertical line through
+      the point, and there is material around it. That is, the solid has a
+      hole at the point
+    */
+    fn has_hole_at(solid: &Solid, point: &na::Vector2<f32>) -> bool
+    {
+        let bounds = solid.bounds();
+        let wall_radius = SCREW_DIAMETER / 2. + 0.5;
+
+        let mut surrounded = false;
+        let mut z = bounds.min.z + HOLE_STEP / 2.;
+        while z < bounds.max.z
+        {
+            if solid.distance(&Vec3::new(point.x, point.y, z)) < 0.
+            {
+                return false;
+            }
+
+            surrounded |= (0..8).any(|i| {
+                let angle = (i as f32 * 45.).to_radians();
+                let wall = Vec3::new(
+                    point.x + wall_radius * angle.cos(),
+                    point.y + wall_radius * angle.sin(),
+                    z
+                );
+                solid.distance(&wall) < 0.
+            });
+            z += HOLE_STEP;
+        }
+        surrounded
+    }
+
+    #[test]
+    fn canopy_screwholes_line_up()
+    {
+        for body in random_bodies()
+        {
+            let mut assembly = Assembly::new();
+            assembly.body = body;
+            let body = &assembly.body;
+            let parts = assembly.get_parts();
+
+            //All the parts cut their holes at the same patterns
+            let patterns = [body.front_screw_pattern(), body.back_screw_pattern()];
+
+            for name in &["body_bottom", "body_top", "canopy"]
+            {
+                let part = parts.iter().find(|part| part.name == *name).unwrap();
+                let solid = Solid::from_object(&part.object).unwrap();
+
+                for pattern in &patterns
+                {
+                    for hole in pattern.positions_2d()
+                    {
+                        assert!(
+                            has_hole_at(&solid, &hole),
+                            "{} has no screwhole at {:?} of {} with {}",
+                            name, hole, pattern.name, describe(body)
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn arm_screwholes_go_through_both_plates()
+    {
+        let mut body = TricopterBody::new();
+        body.arm_mount_threading = Threading::SelfTapping;
+        body.arm_stopper_threading = Threading::HeatSetInsert;
+
+        let patterns = [
+            body.front_arm_mount_pattern(),
+            body.front_arm_stopper_pattern(),
+            body.back_block_center_screw_pattern(),
+        ];
+        for (name, object) in vec!(("body_bottom", body.get_body_bottom()), ("body_top", body.get_body_top()))
+        {
+            let solid = Solid::from_object(&object).unwrap();
+            for pattern in &patterns
+            {
+                for hole in pattern.positions_2d()
+                {
+                    assert!(
+                        has_hole_at(&solid, &hole),
+                        "{} has no screwhole at {:?} of {}", name, hole, pattern.name
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn flight_controller_holes_are_inside_mid_section()
+    {
+        for body in random_bodies()
+        {
+            let holes = body.flight_controller_mount().hole_pattern();
+            let outline = Shape::from_object(&body.get_mid_section_outline()).unwrap();
+            for hole in holes.positions_2d()
+            {
+                assert!(
+                    outline.distance(&hole) <= -holes.diameter / 2.,
+                    "Flight controller hole at {:?} is outside the mid section with {}",
+                    hole, describe(&body)
+                );
+            }
+        }
+    }
 
-    sfile.write_to_file(String::from("out.scad"));
+    #[test]
+    fn flight_controller_holes_are_as_wide_as_the_cut_holes()
+    {
+        let body = TricopterBody::new();
+        let top = Solid::from_object(&body.get_body_top()).unwrap();
+        let holes = body.flight_controller_mount().hole_pattern();
+        for hole in holes.positions_2d()
+        {
+            //Above the screw heads that are sunk into the bottom of the plate
+            let at = |radius| Vec3::new(hole.x + radius, hole.y, body.height * 3. / 4.);
+            assert!(!top.contains(&at(holes.diameter / 2. - 0.05)), "{:?}", hole);
+            assert!(top.contains(&at(holes.diameter / 2. + 0.05)), "{:?}", hole);
+        }
+    }
 
-    add_text_to_history_file(&sfile.get_code(), "frame_history.scad");
+    #[test]
+    fn mating_interfaces_agree()
+    {
+        for body in random_bodies()
+        {
+            let disagreements = mating::check_all(&body.mating_interfaces()).unwrap();
+            assert!(
+                disagreements.is_empty(),
+                "{:?} with {}", disagreements, describe(&body)
+            );
+        }
+    }
+
+    #[test]
+    fn canopy_has_material_around_its_screws()
+    {
+        let body = TricopterBody::new();
+        let canopy = Solid::from_object(&body.get_canopy()).unwrap();
+        for pattern in [body.front_screw_pattern(), body.back_screw_pattern()]
+        {
+            for hole in pattern.positions_2d()
+            {
+                let z = -body.edge_height + body.screw_mount_height / 2.;
+                let center = Vec3::new(hole.x, hole.y, z);
+                let wall = center + Vec3::new(pattern.diameter / 2. + body.min_screw_wall / 2., 0., 0.);
+                assert!(!canopy.contains(&center), "{} {:?}", pattern.name, hole);
+                assert!(canopy.contains(&wall), "{} {:?}", pattern.name, hole);
+            }
+        }
+    }
+
+    #[test]
+    fn short_canopy_screws_are_reported()
+    {
+        let mut body = TricopterBody::new();
+        let disagreements = |body: &TricopterBody| {
+            mating::check_all(&body.mating_interfaces()).unwrap().into_iter()
+                .map(|disagreement| disagreement.feature)
+                .collect::<Vec<_>>()
+        };
+        assert!(disagreements(&body).is_empty());
+
+        //Long enough for the front, which only goes through the plates
+        body.canopy_back_screw_length = body.canopy_front_screw_length;
+        assert_eq!(disagreements(&body), vec!("back_screw_length".to_string()));
+
+        //Self-tapping screws only have to reach a bit into the mount blocks
+        body.canopy_back_screw_length = 20.;
+        assert!(!disagreements(&body).is_empty());
+        body.plate_screw_threading = Threading::SelfTapping;
+        assert!(disagreements(&body).is_empty());
+    }
+
+    #[test]
+    fn drifted_canopy_tabs_are_caught()
+    {
+        let body = TricopterBody::new();
+
+        //The joined front tabs the canopy had while the screws moved to 13/16
+        let old_tab_x = -body.front_section_length * 11. / 16.;
+        let old_tab_y = body.front_section_width / 2. + body.mounting_screw_outline_radius;
+        let old_tab_circle = scad!(Circle(Radius(body.mounting_screw_outline_radius)));
+        let old_tabs = scad!(Hull; {
+            scad!(Translate2d(vec2(old_tab_x, old_tab_y)); old_tab_circle.clone()),
+            scad!(Translate2d(vec2(old_tab_x, -old_tab_y)); old_tab_circle)
+        });
+        let interface = MatingInterface::new("top_to_canopy", "body_top", "canopy")
+            .with_feature("front_tabs", Feature::Boss(
+                body.front_screw_pattern(), old_tabs, body.min_screw_wall
+            ));
+        assert!(!interface.check().unwrap().is_empty());
+
+        //The back holes of the canopy were behind the body, outside the
+        //back mount tabs
+        let old_back = HolePattern::for_fastener("canopy_back_screws", Fastener::m3())
+            .with_symmetric_holes(
+                "back_screw",
+                body.radius + body.mounting_screw_outline_radius,
+                body.outer_width * 7. / 16.
+            );
+        let interface = MatingInterface::new("top_to_canopy", "body_top", "canopy")
+            .with_feature("back_tabs", Feature::Boss(
+                old_back, body.get_back_screw_tab_outline(), body.min_screw_wall
+            ));
+        assert_eq!(interface.check().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn vtx_mounts_have_holes_at_their_pattern()
+    {
+        for vtx in [Vtx::boxed(), Vtx::stack_20x20(), Vtx::stack_30x30()]
+        {
+            let mount = Solid::from_object(&vtx.get_mount()).unwrap();
+            let pattern = vtx.hole_pattern();
+
+            for hole in pattern.positions_2d()
+            {
+                let center = Vec3::new(hole.x, hole.y, vtx.mount_thickness / 2.);
+                let wall = center + Vec3::new(pattern.diameter / 2. + 0.2, 0., 0.);
+                assert!(!mount.contains(&center), "{:?} {:?}", vtx.mounting, hole);
+                assert!(mount.contains(&wall), "{:?} {:?}", vtx.mounting, hole);
+            }
+        }
+    }
+
+    #[test]
+    fn battery_strap_slots_go_through_the_bottom_plate()
+    {
+        let mut body = TricopterBody::new();
+        body.battery_strap_count = 2;
+
+        let bottom = Solid::from_object(&body.get_body_bottom()).unwrap();
+        let slots = body.battery_strap_slot_pattern();
+        assert_eq!(slots.holes.len(), 4);
+        for slot in slots.positions_2d()
+        {
+            let center = Vec3::new(slot.x, slot.y, body.height / 2.);
+            let beside = center + Vec3::new(0., slot.y.signum() * (slots.diameter / 2. + 0.5), 0.);
+            assert!(!bottom.contains(&center), "{:?}", slot);
+            assert!(bottom.contains(&beside), "{:?}", slot);
+        }
+    }
+
+    #[test]
+    fn motor_wire_holes_are_outside_arm_channel()
+    {
+        for body in random_bodies()
+        {
+            //The slot in the back mount block that the arm lies in
+            let channel_width = body.arm_width + body.arm_padding;
+            let channel = BoundingBox::new(
+                Vec3::new(body.radius * body.back_block_length_factor, -channel_width / 2., -100.),
+                Vec3::new(body.radius, channel_width / 2., 100.)
+            );
+
+            let cuts = vec!(
+                ("body_bottom", body.place_object_at_motor_wire_holes(body.get_back_block_cable_hole())),
+                ("body_top", body.get_top_plate_motor_wire_hole()),
+            );
+            for (name, cut) in cuts
+            {
+                let cut = Solid::from_object(&cut).unwrap();
+                let inside = cut.sample_points_in(&channel, 0.1);
+                assert!(
+                    inside.is_empty(),
+                    "The motor wire hole in {} cuts into the arm channel at {:?} with {}",
+                    name, inside[0], describe(&body)
+                );
+            }
+        }
+    }
 }
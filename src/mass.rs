@@ -0,0 +1,247 @@
+/*!
+  Mass model of the assembled craft.
+
+  Printed parts get their mass from their sampled volume and the density of
+  the material. Components have a known mass that is spread evenly over
+  their geometry.
+*/
+
+use na;
+
+use assembly::{Assembly, AssemblyPart, PartKind};
+use evaluate::{Solid, EvalError, Vec3};
+
+use std::fmt;
+
+/**
+  Mass properties of a single part
+*/
+#[derive(Clone, PartialEq, Debug)]
+pub struct PartMass
+{
+    pub name: String,
+    //Mass in grams
+    pub mass: f32,
+    pub center_of_mass: Vec3,
+    //Integral of r * r^T dm relative to the origin, in g*mm^2
+    pub second_moment: na::Matrix3<f32>,
+}
+
+impl PartMass
+{
+    fn from_part(part: &AssemblyPart, assembly: &Assembly, resolution: f32)
+        -> Result<PartMass, EvalError>
+    {
+        let solid = Solid::from_object(&part.object)?;
+        let properties = solid.volume_properties(resolution);
+
+        let mass = match part.kind
+        {
+            PartKind::Printed(material) => {
+                properties.volume * material.density() * assembly.fill_factor
+            }
+            PartKind::Component(mass) => mass,
+        };
+
+        //Components that are too small to be hit by the samples are treated
+        //as point masses in the middle of their bounding box
+        if properties.volume == 0.
+        {
+            let center = solid.bounds().center();
+            return Ok(PartMass{
+                name: part.name.clone(),
+                mass,
+                center_of_mass: center,
+                second_moment: center * center.transpose() * mass,
+            });
+        }
+
+        Ok(PartMass{
+            name: part.name.clone(),
+            mass,
+            center_of_mass: properties.centroid,
+            second_moment: properties.second_moment * (mass / properties.volume),
+        })
+    }
+}
+
+/**
+  Mass properties of all the parts in an assembly
+*/
+#[derive(Clone, PartialEq, Debug)]
+pub struct MassModel
+{
+    pub parts: Vec<PartMass>,
+}
+
+impl MassModel
+{
+    /**
+      Estimates the mass of every part in the assembly. `resolution` is the
+      size of the cells in mm that the printed parts are sampled with
+    */
+    pub fn new(assembly: &Assembly, resolution: f32) -> Result<MassModel, EvalError>
+    {
+        MassModel::of_parts(&assembly.get_parts(), assembly, resolution)
+    }
+
+    /**
+      Estimates the mass of some of the parts of an assembly
+    */
+    pub fn of_parts(parts: &[AssemblyPart], assembly: &Assembly, resolution: f32)
+        -> Result<MassModel, EvalError>
+    {
+        let parts = parts.iter()
+            .map(|part| PartMass::from_part(part, assembly, resolution))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(MassModel{parts})
+    }
+
+    /**
+      Returns a model of only the parts that `include` returns true for
+    */
+    pub fn subset<F>(&self, include: F) -> MassModel
+        where F: Fn(&str) -> bool
+    {
+        MassModel{
+            parts: self.parts.iter()
+                .filter(|part| include(&part.name))
+                .cloned()
+                .collect()
+        }
+    }
+
+    pub fn total_mass(&self) -> f32
+    {
+        self.parts.iter().map(|part| part.mass).sum()
+    }
+
+    /**
+      Returns the center of gravity relative to the center of the body shape
+    */
+    pub fn center_of_mass(&self) -> Vec3
+    {
+        let total = self.total_mass();
+        if total == 0.
+        {
+            return Vec3::new(0., 0., 0.);
+        }
+
+        self.parts.iter()
+            .fold(Vec3::new(0., 0., 0.), |acc, part| acc + part.center_of_mass * part.mass)
+            / total
+    }
+
+    /**
+      Returns the moment of inertia tensor around the center of gravity
+      in g*mm^2
+    */
+    pub fn inertia_tensor(&self) -> na::Matrix3<f32>
+    {
+        let center = self.center_of_mass();
+        let second_moment = self.parts.iter()
+            .fold(na::Matrix3::zeros(), |acc, part| acc + part.second_moment);
+
+        //Move the second moment to the center of gravity
+        let centered = second_moment - center * center.transpose() * self.total_mass();
+
+        na::Matrix3::identity() * centered.trace() - centered
+    }
+
+    /**
+      Returns the mass of the parts whose names start with the prefix
+    */
+    pub fn mass_of(&self, prefix: &str) -> f32
+    {
+        self.parts.iter()
+            .filter(|part| part.name.starts_with(prefix))
+            .map(|part| part.mass)
+            .sum()
+    }
+}
+
+impl fmt::Display for MassModel
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        writeln!(f, "{:<20} {:>9} {:>24}", "part", "mass (g)", "center of mass (mm)")?;
+        for part in &self.parts
+        {
+            let c = part.center_of_mass;
+            writeln!(
+                f,
+                "{:<20} {:>9.1} {:>7.1} {:>7.1} {:>7.1}",
+                part.name, part.mass, c.x, c.y, c.z
+            )?;
+        }
+
+        let center = self.center_of_mass();
+        writeln!(f)?;
+        writeln!(f, "Total mass: {:.1} g", self.total_mass())?;
+        writeln!(
+            f,
+            "Center of gravity relative to the body center: x {:.1} y {:.1} z {:.1} mm",
+            center.x, center.y, center.z
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use scad::*;
+    use assembly::Material;
+
+    fn part(name: &str, kind: PartKind, object: ScadObject) -> PartMass
+    {
+        let part = AssemblyPart{name: name.to_string(), kind, object};
+        PartMass::from_part(&part, &Assembly::new(), 0.5).unwrap()
+    }
+
+    #[test]
+    fn printed_box_matches_its_analytic_mass()
+    {
+        let assembly = Assembly::new();
+        let model = MassModel{parts: vec!(part(
+            "box",
+            PartKind::Printed(Material::Pla),
+            scad!(Translate(vec3(10., 0., 5.)); scad!(Cube(vec3(20., 10., 4.))))
+        ))};
+
+        let mass = 20. * 10. * 4. * Material::Pla.density() * assembly.fill_factor;
+        assert!((model.total_mass() - mass).abs() < 1e-3 * mass);
+        assert!((model.center_of_mass() - Vec3::new(20., 5., 7.)).norm() < 1e-3);
+
+        //Second moments of a solid box around its center
+        let box_part = &model.parts[0];
+        let center = box_part.center_of_mass;
+        let central = box_part.second_moment - center * center.transpose() * mass;
+        let expected = Vec3::new(20. * 20., 10. * 10., 4. * 4.) * mass / 12.;
+        for i in 0..3
+        {
+            assert!((central[(i, i)] - expected[i]).abs() < 2e-2 * expected[i], "{}", central);
+        }
+    }
+
+    #[test]
+    fn parts_are_combined_around_their_common_center()
+    {
+        let model = MassModel{parts: vec!(
+            part("cylinder", PartKind::Component(30.), {
+                scad!(Translate(vec3(0., 0., -20.)); scad!(Cylinder(10., Radius(5.))))
+            }),
+            part("cube", PartKind::Component(10.), {
+                scad!(Translate(vec3(38., -2., -2.)); scad!(Cube(vec3(4., 4., 4.))))
+            }),
+        )};
+
+        assert_eq!(model.total_mass(), 40.);
+        //The cylinder is centered at (0, 0, -15) and the cube at (40, 0, 0)
+        let expected = Vec3::new(10. * 40., 0., 30. * -15.) / 40.;
+        assert!((model.center_of_mass() - expected).norm() < 0.05, "{}", model.center_of_mass());
+        assert_eq!(model.mass_of("cyl"), 30.);
+    }
+}
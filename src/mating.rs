@@ -0,0 +1,235 @@
+/*!
+  Interfaces between parts that are screwed or fitted together.
+
+  Parts that are screwed together cut their holes from the same hole
+  pattern. An interface checks that the outlines of the parts leave material
+  around those holes and that lips fit in the openings they go into. The
+  checks run before the parts are generated, so a change to an outline that
+  the holes or the part it mates with don't follow is caught before anything
+  is printed.
+*/
+
+use scad::*;
+
+use evaluate::{sample_grid, EvalError, Shape, Vec2};
+use hole_pattern::HolePattern;
+
+use std::fmt;
+
+//Size in mm of the cells that outlines are compared with
+const RESOLUTION: f32 = 0.25;
+
+pub enum Feature
+{
+    //An outline of the part that has the holes which must surround them
+    //with at least the specified wall in mm, like a screw tab
+    Boss(HolePattern, ScadObject, f32),
+    //Holes whose screw heads have to be sunk the first value in mm into a
+    //part that is the second value thick
+    SunkHeads(HolePattern, f32, f32),
+    //A 2d outline of a lip of the first part that must fit inside an opening
+    //in the second part with at least the specified clearance in mm
+    Lip(ScadObject, ScadObject, f32),
+}
+
+pub struct MatingInterface
+{
+    pub name: String,
+    pub first: String,
+    pub second: String,
+    pub features: Vec<(String, Feature)>,
+}
+
+/**
+  A feature of an interface where the two parts don't agree
+*/
+#[derive(Clone, PartialEq, Debug)]
+pub struct Disagreement
+{
+    pub interface: String,
+    pub feature: String,
+    pub reason: String,
+}
+
+impl fmt::Display for Disagreement
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "{}, {}: {}", self.interface, self.feature, self.reason)
+    }
+}
+
+fn check_sunk_heads(holes: &HolePattern, depth: f32, thickness: f32) -> Vec<String>
+{
+    if depth > thickness
+    {
+        vec!(format!(
+            "the screws of {} have to be sunk {:.2} mm into a {:.2} mm part, they are {:.2} mm too short",
+            holes.name, depth, thickness, depth - thickness
+        ))
+    }
+    else
+    {
+        vec!()
+    }
+}
+
+fn check_boss(holes: &HolePattern, outline: &ScadObject, wall: f32) -> Result<Vec<String>, EvalError>
+{
+    let shape = Shape::from_object(outline)?;
+    let required = holes.diameter / 2. + wall;
+
+    Ok(holes.holes.iter()
+        .filter_map(|hole| {
+            let position = Vec2::new(hole.position.x, hole.position.y);
+            let available = -shape.distance(&position);
+            if available < required
+            {
+                Some(format!(
+                    "{} at ({:.2}, {:.2}) has {:.2} mm of material around its center, {:.2} mm is needed",
+                    hole.name, position.x, position.y, available.max(0.), required
+                ))
+            }
+            else
+            {
+                None
+            }
+        })
+        .collect())
+}
+
+fn check_lip(lip: &ScadObject, opening: &ScadObject, clearance: f32) -> Result<Vec<String>, EvalError>
+{
+    let lip = Shape::from_object(lip)?;
+    let opening = Shape::from_object(opening)?;
+
+    //The smallest distance from the lip to the edge of the opening
+    let smallest = sample_grid(&lip.bounds(), RESOLUTION).iter()
+        .map(|point| Vec2::new(point.x, point.y))
+        .filter(|point| lip.contains(point))
+        .map(|point| -opening.distance(&point))
+        .fold(f32::INFINITY, f32::min);
+
+    if smallest < clearance
+    {
+        Ok(vec!(format!(
+            "the lip is {:.2} mm from the edge of the opening, {:.2} mm is needed",
+            smallest, clearance
+        )))
+    }
+    else
+    {
+        Ok(vec!())
+    }
+}
+
+impl MatingInterface
+{
+    pub fn new(name: &str, first: &str, second: &str) -> MatingInterface
+    {
+        MatingInterface{
+            name: name.to_string(),
+            first: first.to_string(),
+            second: second.to_string(),
+            features: vec!(),
+        }
+    }
+
+    pub fn with_feature(mut self, name: &str, feature: Feature) -> MatingInterface
+    {
+        self.features.push((name.to_string(), feature));
+        self
+    }
+
+    /**
+      Returns the features where the two sides of the interface disagree
+    */
+    pub fn check(&self) -> Result<Vec<Disagreement>, EvalError>
+    {
+        let mut result = vec!();
+        for (name, feature) in &self.features
+        {
+            let reasons = match *feature
+            {
+                Feature::SunkHeads(ref holes, depth, thickness) => {
+                    check_sunk_heads(holes, depth, thickness)
+                }
+                Feature::Boss(ref holes, ref outline, wall) => check_boss(holes, outline, wall)?,
+                Feature::Lip(ref lip, ref opening, clearance) => check_lip(lip, opening, clearance)?,
+            };
+
+            result.extend(reasons.into_iter().map(|reason| Disagreement{
+                interface: self.name.clone(),
+                feature: name.clone(),
+                reason,
+            }));
+        }
+        Ok(result)
+    }
+}
+
+/**
+  Returns the disagreements of all the interfaces
+*/
+pub fn check_all(interfaces: &[MatingInterface]) -> Result<Vec<Disagreement>, EvalError>
+{
+    let mut result = vec!();
+    for interface in interfaces
+    {
+        result.append(&mut interface.check()?);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use hole_pattern::Fastener;
+
+    fn pair(x: f32) -> HolePattern
+    {
+        HolePattern::for_fastener("pair", Fastener::m3()).with_symmetric_holes("hole", x, 10.)
+    }
+
+    #[test]
+    fn heads_must_not_be_sunk_through_the_part()
+    {
+        let interface = MatingInterface::new("test", "a", "b")
+            .with_feature("flush", Feature::SunkHeads(pair(0.), 0., 5.))
+            .with_feature("sunk", Feature::SunkHeads(pair(0.), 5., 5.))
+            .with_feature("through", Feature::SunkHeads(pair(0.), 5.5, 5.));
+
+        let result = interface.check().unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].feature, "through");
+    }
+
+    #[test]
+    fn holes_must_be_inside_boss()
+    {
+        let tab = scad!(Translate(vec3(0., 10., 0.)); scad!(Circle(Radius(3.))));
+        let interface = MatingInterface::new("test", "a", "b")
+            .with_feature("tab", Feature::Boss(pair(0.), tab, 1.));
+
+        let result = interface.check().unwrap();
+        assert_eq!(result.len(), 1);
+        assert!(result[0].reason.starts_with("hole_left"));
+    }
+
+    #[test]
+    fn lip_must_fit_in_opening()
+    {
+        let lip = scad!(Square(vec2(10., 10.)));
+        let opening = |size| scad!(Translate(vec3(-1., -1., 0.)); scad!(Square(vec2(size, size))));
+
+        let fits = MatingInterface::new("test", "a", "b")
+            .with_feature("lip", Feature::Lip(lip.clone(), opening(12.), 0.5));
+        let too_tight = MatingInterface::new("test", "a", "b")
+            .with_feature("lip", Feature::Lip(lip, opening(11.), 0.5));
+
+        assert!(fits.check().unwrap().is_empty());
+        assert_eq!(too_tight.check().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,389 @@
+/*!
+  Conversion of evaluated solids to triangle meshes and STL files.
+
+  Meshes are created by marching tetrahedra over the signed distance of the
+  solid, so features smaller than the resolution are lost. Problems found on
+  the triangles of a mesh can be grouped into regions of the surface.
+*/
+
+use evaluate::{Solid, Vec3};
+
+use std::collections::hash_map::{Entry, HashMap};
+use std::fs::File;
+use std::io::{self, Write, BufWriter};
+use std::path::Path;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Triangle
+{
+    pub vertices: [Vec3; 3],
+}
+
+impl Triangle
+{
+    pub fn normal(&self) -> Vec3
+    {
+        let [a, b, c] = self.vertices;
+        let normal = (b - a).cross(&(c - a));
+        let length = normal.norm();
+        if length == 0. { normal } else { normal / length }
+    }
+
+    pub fn area(&self) -> f32
+    {
+        let [a, b, c] = self.vertices;
+        (b - a).cross(&(c - a)).norm() / 2.
+    }
+
+    fn flipped(&self) -> Triangle
+    {
+        let [a, b, c] = self.vertices;
+        Triangle{vertices: [a, c, b]}
+    }
+}
+
+//Size in mm of the cubes that points on a surface are grouped by
+const REGION_SIZE: f32 = 5.;
+//Regions with less surface than this in mm^2 are left out, they are
+//usually sharp edges where the surface direction is not well defined
+const MIN_REGION_AREA: f32 = 1.;
+
+//Corners of a grid cell
+const CUBE_CORNERS: [(usize, usize, usize); 8] = [
+    (0, 0, 0), (1, 0, 0), (1, 1, 0), (0, 1, 0),
+    (0, 0, 1), (1, 0, 1), (1, 1, 1), (0, 1, 1),
+];
+
+//Split of a cell into tetrahedra around the diagonal from corner 0 to 6
+const TETRAHEDRA: [[usize; 4]; 6] = [
+    [0, 5, 1, 6],
+    [0, 1, 2, 6],
+    [0, 2, 3, 6],
+    [0, 3, 7, 6],
+    [0, 7, 4, 6],
+    [0, 4, 5, 6],
+];
+
+fn interpolate(a: &(Vec3, f32), b: &(Vec3, f32)) -> Vec3
+{
+    let (position_a, value_a) = *a;
+    let (position_b, value_b) = *b;
+    if value_a == value_b
+    {
+        return (position_a + position_b) / 2.;
+    }
+    position_a + (position_b - position_a) * (value_a / (value_a - value_b))
+}
+
+/**
+  Returns the triangles where the surface crosses a tetrahedron, oriented
+  with the normals pointing out of the solid
+*/
+fn triangulate_tetrahedron(corners: [(Vec3, f32); 4], result: &mut Vec<Triangle>)
+{
+    let (inside, outside): (Vec<_>, Vec<_>) = corners.iter().partition(|c| c.1 < 0.);
+
+    let triangles = match (inside.len(), outside.len())
+    {
+        (1, 3) => vec!(Triangle{vertices: [
+            interpolate(inside[0], outside[0]),
+            interpolate(inside[0], outside[1]),
+            interpolate(inside[0], outside[2]),
+        ]}),
+        (3, 1) => vec!(Triangle{vertices: [
+            interpolate(outside[0], inside[0]),
+            interpolate(outside[0], inside[1]),
+            interpolate(outside[0], inside[2]),
+        ]}),
+        (2, 2) => {
+            let a = interpolate(inside[0], outside[0]);
+            let b = interpolate(inside[0], outside[1]);
+            let c = interpolate(inside[1], outside[1]);
+            let d = interpolate(inside[1], outside[0]);
+            vec!(Triangle{vertices: [a, b, c]}, Triangle{vertices: [a, c, d]})
+        }
+        _ => vec!()
+    };
+
+    let mean = |points: &Vec<&(Vec3, f32)>| {
+        points.iter().fold(Vec3::new(0., 0., 0.), |acc, p| acc + p.0) / points.len() as f32
+    };
+    if triangles.is_empty()
+    {
+        return;
+    }
+    let outwards = mean(&outside) - mean(&inside);
+
+    for triangle in triangles
+    {
+        //Corners that are on the surface give triangles without any area
+        let normal = triangle.normal();
+        if normal.norm() == 0.
+        {
+            continue;
+        }
+
+        if normal.dot(&outwards) < 0.
+        {
+            result.push(triangle.flipped());
+        }
+        else
+        {
+            result.push(triangle);
+        }
+    }
+}
+
+/**
+  Creates a triangle mesh of the surface of the solid using a grid with
+  the specified cell size
+*/
+pub fn triangulate(solid: &Solid, resolution: f32) -> Vec<Triangle>
+{
+    let bounds = solid.bounds().expanded(resolution);
+    if bounds.is_empty()
+    {
+        return vec!();
+    }
+
+    let size = bounds.size();
+    let counts = [
+        (size.x / resolution).ceil() as usize + 1,
+        (size.y / resolution).ceil() as usize + 1,
+        (size.z / resolution).ceil() as usize + 1,
+    ];
+    let index = |x: usize, y: usize, z: usize| (x * counts[1] + y) * counts[2] + z;
+
+    let mut values = Vec::with_capacity(counts[0] * counts[1] * counts[2]);
+    for x in 0..counts[0]
+    {
+        for y in 0..counts[1]
+        {
+            for z in 0..counts[2]
+            {
+                let point = bounds.min + Vec3::new(x as f32, y as f32, z as f32) * resolution;
+                //Empty solids have infinite distance which breaks interpolation
+                let distance = solid.distance(&point).min(resolution * 10.);
+                values.push((point, distance));
+            }
+        }
+    }
+
+    let mut result = vec!();
+    for x in 0..counts[0] - 1
+    {
+        for y in 0..counts[1] - 1
+        {
+            for z in 0..counts[2] - 1
+            {
+                let corners = CUBE_CORNERS.iter()
+                    .map(|&(dx, dy, dz)| values[index(x + dx, y + dy, z + dz)])
+                    .collect::<Vec<_>>();
+
+                //Skip cells that are entirely inside or outside
+                let inside = corners.iter().filter(|c| c.1 < 0.).count();
+                if inside == 0 || inside == 8
+                {
+                    continue;
+                }
+
+                for tetrahedron in &TETRAHEDRA
+                {
+                    let tetrahedron_corners = [
+                        corners[tetrahedron[0]],
+                        corners[tetrahedron[1]],
+                        corners[tetrahedron[2]],
+                        corners[tetrahedron[3]],
+                    ];
+                    triangulate_tetrahedron(tetrahedron_corners, &mut result);
+                }
+            }
+        }
+    }
+    result
+}
+
+/**
+  Writes triangles to a binary STL file
+*/
+pub fn write_stl(path: &Path, triangles: &[Triangle]) -> io::Result<()>
+{
+    let mut file = BufWriter::new(File::create(path)?);
+
+    let mut header = [0u8; 80];
+    let title = b"tricopter";
+    header[..title.len()].copy_from_slice(title);
+    file.write_all(&header)?;
+    file.write_all(&(triangles.len() as u32).to_le_bytes())?;
+
+    for triangle in triangles
+    {
+        let normal = triangle.normal();
+        let mut floats = vec!(normal.x, normal.y, normal.z);
+        for vertex in &triangle.vertices
+        {
+            floats.extend_from_slice(&[vertex.x, vertex.y, vertex.z]);
+        }
+        for value in floats
+        {
+            file.write_all(&value.to_bits().to_le_bytes())?;
+        }
+        file.write_all(&[0u8, 0u8])?;
+    }
+    file.flush()
+}
+
+/**
+  Estimates the enclosed volume of a closed mesh. Used to check that
+  meshes are consistent with the sampled solids
+*/
+pub fn mesh_volume(triangles: &[Triangle]) -> f32
+{
+    triangles.iter()
+        .map(|triangle| {
+            let [a, b, c] = triangle.vertices;
+            a.dot(&b.cross(&c)) / 6.
+        })
+        .sum()
+}
+
+/**
+  A part of a surface with the worst problem found on it
+*/
+#[derive(Clone, PartialEq, Debug)]
+pub struct SurfaceRegion<T>
+{
+    //Position of the worst point of the region
+    pub position: Vec3,
+    pub worst: T,
+    //Surface area in mm^2 that has the problem
+    pub area: f32,
+}
+
+/**
+  Groups problems found on the triangles of a surface by the cube of
+  `REGION_SIZE` that they are in, keeping the worst problem of every cube
+*/
+pub struct SurfaceRegions<T>
+{
+    //Returns true if the first problem is worse than the second
+    is_worse: fn(&T, &T) -> bool,
+    regions: HashMap<(i32, i32, i32), SurfaceRegion<T>>,
+}
+
+impl<T> SurfaceRegions<T>
+{
+    pub fn new(is_worse: fn(&T, &T) -> bool) -> SurfaceRegions<T>
+    {
+        SurfaceRegions{is_worse, regions: HashMap::new()}
+    }
+
+    /**
+      Adds a problem at a point on the surface that covers `area` mm^2
+    */
+    pub fn add(&mut self, position: Vec3, area: f32, problem: T)
+    {
+        let key = (
+            (position.x / REGION_SIZE).floor() as i32,
+            (position.y / REGION_SIZE).floor() as i32,
+            (position.z / REGION_SIZE).floor() as i32,
+        );
+
+        let is_worse = self.is_worse;
+        match self.regions.entry(key)
+        {
+            Entry::Occupied(mut entry) => {
+                let region = entry.get_mut();
+                region.area += area;
+                if is_worse(&problem, &region.worst)
+                {
+                    region.worst = problem;
+                    region.position = position;
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(SurfaceRegion{position, worst: problem, area});
+            }
+        }
+    }
+
+    /**
+      Returns the regions that are large enough to be more than an edge
+    */
+    pub fn into_regions(self) -> Vec<SurfaceRegion<T>>
+    {
+        self.regions.into_values()
+            .filter(|region| region.area >= MIN_REGION_AREA)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn mesh_of_a_cube_has_its_volume()
+    {
+        let solid = Solid::from_code("translate([5,-3,2])cube([10,20,30]);").unwrap();
+        let triangles = triangulate(&solid, 1.);
+
+        let volume = mesh_volume(&triangles);
+        assert!((volume - 6000.).abs() < 6000. * 0.02, "{}", volume);
+
+        //All the normals point away from the middle of the cube
+        let center = Vec3::new(10., 7., 17.);
+        for triangle in &triangles
+        {
+            let [a, b, c] = triangle.vertices;
+            let middle = (a + b + c) / 3.;
+            assert!(triangle.normal().dot(&(middle - center)) > 0., "{:?}", triangle);
+        }
+    }
+
+    #[test]
+    fn empty_solids_have_no_triangles()
+    {
+        let solid = Solid::from_code("union(){}").unwrap();
+        assert!(triangulate(&solid, 1.).is_empty());
+    }
+
+    #[test]
+    fn regions_keep_their_worst_problem()
+    {
+        let mut regions = SurfaceRegions::new(|a: &f32, b: &f32| a < b);
+        regions.add(Vec3::new(1., 1., 1.), 0.6, 3.);
+        regions.add(Vec3::new(2., 1., 1.), 0.6, 2.);
+        regions.add(Vec3::new(3., 1., 1.), 0.6, 4.);
+        //Too small to be reported on its own
+        regions.add(Vec3::new(12., 1., 1.), 0.6, 1.);
+
+        let found = regions.into_regions();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].worst, 2.);
+        assert_eq!(found[0].position, Vec3::new(2., 1., 1.));
+        assert!((found[0].area - 1.8).abs() < 1e-5);
+    }
+
+    #[test]
+    fn stl_files_have_a_record_per_triangle()
+    {
+        let solid = Solid::from_code("cube(4);").unwrap();
+        let triangles = triangulate(&solid, 1.);
+        let path = env::temp_dir().join(format!("tricopter_mesh_test_{}.stl", process::id()));
+
+        write_stl(&path, &triangles).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(bytes.len(), 84 + 50 * triangles.len());
+        let mut count = [0u8; 4];
+        count.copy_from_slice(&bytes[80..84]);
+        assert_eq!(u32::from_le_bytes(count) as usize, triangles.len());
+    }
+}
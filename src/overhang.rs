@@ -0,0 +1,326 @@
+/*!
+  Detection of overhangs and bridges that need support when printing.
+
+  Every part is rotated to its print orientation, placed on the print bed
+  and its surface is meshed.
+  Surfaces that face down more steeply than the allowed overhang angle need
+  support, unless they are flat and span a gap short enough to be bridged.
+  Bridge lengths are found by looking for walls just below the surface in
+  a few horizontal directions.
+*/
+
+use assembly::{Assembly, PrintPart};
+use evaluate::{EvalError, Solid, Vec3};
+use mesh::{self, SurfaceRegions};
+
+use std::fmt;
+
+//Size in mm of the cells used to mesh the parts
+const RESOLUTION: f32 = 1.;
+//Surfaces this close to horizontal in degrees can be bridged
+const BRIDGE_ANGLE_TOLERANCE: f32 = 5.;
+//Number of horizontal directions that bridges are measured along
+const BRIDGE_DIRECTIONS: usize = 8;
+//How far below a surface the walls that hold up a bridge are looked for
+const BRIDGE_PROBE_DEPTH: f32 = 0.2;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ProblemKind
+{
+    //A surface steeper than the allowed overhang angle, with the angle from
+    //vertical in degrees
+    Overhang(f32),
+    //A flat surface held up at both ends by walls that are too far apart,
+    //with the length of the bridge in mm
+    Bridge(f32),
+}
+
+/**
+  An area of a part that needs support
+*/
+#[derive(Clone, PartialEq, Debug)]
+pub struct SupportRegion
+{
+    pub part: String,
+    pub kind: ProblemKind,
+    //Position on the print bed of the worst point of the region, with the
+    //bed at z = 0
+    pub position: Vec3,
+    //Area in mm^2 that needs support
+    pub area: f32,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct OverhangReport
+{
+    pub max_overhang_angle: f32,
+    pub max_bridge_length: f32,
+    pub regions: Vec<SupportRegion>,
+}
+
+/**
+  Returns the distance from a point outside the solid to the solid along a
+  direction, or None if nothing is hit within `max_distance`
+*/
+fn distance_to_wall(solid: &Solid, start: &Vec3, direction: &Vec3, max_distance: f32) -> Option<f32>
+{
+    let mut travelled = 0.;
+    while travelled < max_distance
+    {
+        let distance = solid.distance(&(start + direction * travelled));
+        if distance <= 0.
+        {
+            return Some(travelled);
+        }
+        travelled += distance.max(RESOLUTION / 20.);
+    }
+    None
+}
+
+/**
+  Returns the length of the shortest bridge that can hold up the surface
+  at the point, or None if there is no wall to hold it up in any direction
+*/
+fn bridge_length(solid: &Solid, point: &Vec3, max_distance: f32) -> Option<f32>
+{
+    let start = point - Vec3::new(0., 0., BRIDGE_PROBE_DEPTH);
+
+    (0..BRIDGE_DIRECTIONS / 2)
+        .filter_map(|i| {
+            let angle = (i as f32 * 360. / BRIDGE_DIRECTIONS as f32).to_radians();
+            let direction = Vec3::new(angle.cos(), angle.sin(), 0.);
+            let forward = distance_to_wall(solid, &start, &direction, max_distance)?;
+            let backward = distance_to_wall(solid, &start, &-direction, max_distance)?;
+            Some(forward + backward)
+        })
+        .fold(None, |acc: Option<f32>, length| Some(acc.map_or(length, |acc| acc.min(length))))
+}
+
+/**
+  Returns the regions of a part in print orientation that need support
+*/
+pub fn find_support_regions(
+    name: &str,
+    solid: &Solid,
+    max_overhang_angle: f32,
+    max_bridge_length: f32
+) -> Vec<SupportRegion>
+{
+    let bed_height = solid.bounds().min.z;
+    let mut regions = SurfaceRegions::new(|a: &ProblemKind, b: &ProblemKind| {
+        severity(a) > severity(b)
+    });
+
+    for triangle in mesh::triangulate(solid, RESOLUTION)
+    {
+        let [a, b, c] = triangle.vertices;
+        let center = (a + b + c) / 3.;
+        let normal = solid.normal(&center);
+        let point = center - normal * solid.distance(&center);
+
+        //Surfaces on the bed are held up by it
+        if point.z - bed_height < RESOLUTION / 2.
+        {
+            continue;
+        }
+
+        let angle = (-normal.z).clamp(-1., 1.).asin().to_degrees();
+        if angle <= max_overhang_angle
+        {
+            continue;
+        }
+
+        let kind = if angle >= 90. - BRIDGE_ANGLE_TOLERANCE
+        {
+            match bridge_length(solid, &point, max_bridge_length * 2.)
+            {
+                Some(length) if length <= max_bridge_length => continue,
+                Some(length) => ProblemKind::Bridge(length),
+                None => ProblemKind::Overhang(angle),
+            }
+        }
+        else
+        {
+            ProblemKind::Overhang(angle)
+        };
+
+        regions.add(point, triangle.area(), kind);
+    }
+
+    let mut result = regions.into_regions().into_iter()
+        .map(|region| SupportRegion{
+            part: name.to_string(),
+            kind: region.worst,
+            position: region.position,
+            area: region.area,
+        })
+        .collect::<Vec<_>>();
+    result.sort_by(|a, b| {
+        a.position.z.total_cmp(&b.position.z)
+            .then(a.position.x.total_cmp(&b.position.x))
+            .then(a.position.y.total_cmp(&b.position.y))
+    });
+    result
+}
+
+/**
+  Orders problems so that the worst one of a region is reported. Overhangs
+  are worse than bridges since they can't be printed at all without support
+*/
+fn severity(kind: &ProblemKind) -> f32
+{
+    match *kind
+    {
+        ProblemKind::Overhang(angle) => 1000. + angle,
+        ProblemKind::Bridge(length) => length,
+    }
+}
+
+impl OverhangReport
+{
+    pub fn new(assembly: &Assembly) -> Result<OverhangReport, EvalError>
+    {
+        let mut regions = vec!();
+        for part in assembly.get_print_parts()
+        {
+            regions.append(&mut OverhangReport::check_part(assembly, &part)?);
+        }
+
+        Ok(OverhangReport{
+            max_overhang_angle: assembly.max_overhang_angle,
+            max_bridge_length: assembly.max_bridge_length,
+            regions,
+        })
+    }
+
+    fn check_part(assembly: &Assembly, part: &PrintPart) -> Result<Vec<SupportRegion>, EvalError>
+    {
+        let solid = Solid::from_object(&part.on_bed()?)?;
+        Ok(find_support_regions(
+            &part.name,
+            &solid,
+            assembly.max_overhang_angle,
+            assembly.max_bridge_length
+        ))
+    }
+
+    /**
+      Returns the names of the parts that need support
+    */
+    pub fn parts_needing_support(&self) -> Vec<&str>
+    {
+        let mut result = self.regions.iter()
+            .map(|region| region.part.as_str())
+            .collect::<Vec<_>>();
+        result.dedup();
+        result
+    }
+}
+
+impl fmt::Display for OverhangReport
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        writeln!(f, "Maximum overhang angle: {:.0} degrees", self.max_overhang_angle)?;
+        writeln!(f, "Maximum bridge length: {:.1} mm", self.max_bridge_length)?;
+        if self.regions.is_empty()
+        {
+            return writeln!(f, "No part needs support");
+        }
+
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<26} {:<18} {:>26} {:>12}",
+            "part", "problem", "position (mm)", "area (mm^2)"
+        )?;
+        for region in &self.regions
+        {
+            let problem = match region.kind
+            {
+                ProblemKind::Overhang(angle) => format!("overhang {:.0} deg", angle),
+                ProblemKind::Bridge(length) => format!("bridge {:.1} mm", length),
+            };
+            let p = region.position;
+            writeln!(
+                f,
+                "{:<26} {:<18} {:>8.1} {:>8.1} {:>8.1} {:>12.1}",
+                region.part, problem, p.x, p.y, p.z, region.area
+            )?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "Parts that need support: {}", self.parts_needing_support().join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn regions(code: &str) -> Vec<SupportRegion>
+    {
+        find_support_regions("part", &Solid::from_code(code).unwrap(), 45., 10.)
+    }
+
+    #[test]
+    fn cubes_need_no_support()
+    {
+        assert_eq!(regions("cube([20, 20, 10]);"), vec!());
+    }
+
+    #[test]
+    fn arms_of_a_t_shape_overhang()
+    {
+        //A 4 mm stem holding up a 20 mm bar
+        let found = regions(
+            "union(){translate([8, 0, 0]) cube([4, 4, 10]); translate([0, 0, 10]) cube([20, 4, 4]);}"
+        );
+
+        assert!(!found.is_empty());
+        for region in &found
+        {
+            match region.kind
+            {
+                ProblemKind::Overhang(angle) => assert!((angle - 90.).abs() < 1., "{:?}", region),
+                ProblemKind::Bridge(_) => panic!("{:?}", region),
+            }
+            assert!((region.position.z - 10.).abs() < 0.5, "{:?}", region);
+        }
+        let area = found.iter().map(|region| region.area).sum::<f32>();
+        assert!((area - 2. * 8. * 4.).abs() < 8., "{}", area);
+    }
+
+    #[test]
+    fn short_bridges_need_no_support()
+    {
+        let bridge = |span: f32| regions(&format!(
+            "difference(){{cube([{}, 4, 10]); translate([2, -1, -1]) cube([{}, 6, 6]);}}",
+            span + 4., span
+        ));
+
+        assert_eq!(bridge(8.), vec!());
+        let long = bridge(20.);
+        assert!(!long.is_empty());
+        for region in &long
+        {
+            match region.kind
+            {
+                ProblemKind::Bridge(length) => assert!((length - 20.).abs() < 1., "{}", length),
+                kind => panic!("{:?}", kind),
+            }
+        }
+    }
+
+    #[test]
+    fn parts_are_placed_on_the_bed()
+    {
+        let assembly = Assembly::new();
+        for part in assembly.get_print_parts()
+        {
+            let bounds = Solid::from_object(&part.on_bed().unwrap()).unwrap().bounds();
+            assert!(bounds.min.norm() < 1e-3, "{} {:?}", part.name, bounds.min);
+        }
+    }
+}
@@ -0,0 +1,352 @@
+/*!
+  Access to the parameters of the assembly by name.
+
+  Every parameter struct lists its fields here so that the whole set of
+  parameters can be written to a file, read back and compared, and single
+  parameters can be changed by name.
+*/
+
+use assembly::{Assembly, Material, Motor};
+use edges::EdgeFinish;
+use hole_pattern::{HeadRecess, Threading};
+use {
+    AntennaConnector,
+    Battery,
+    BatteryLeadExit,
+    BoardCamera,
+    Esc,
+    LighteningPattern,
+    NazeBoard,
+    ServoMount,
+    TricopterBody,
+    Vtx,
+    VtxMounting,
+};
+
+use std::fmt;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ParameterError
+{
+    Unknown(String),
+    //The name of the parameter and the value that could not be parsed
+    InvalidValue(String, String),
+    //A line of a parameter file that is not `name = value`
+    Syntax(usize),
+}
+
+impl fmt::Display for ParameterError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self
+        {
+            ParameterError::Unknown(ref name) => write!(f, "There is no parameter called {}", name),
+            ParameterError::InvalidValue(ref name, ref value) => {
+                write!(f, "{} is not a valid value for {}", value, name)
+            }
+            ParameterError::Syntax(line) => write!(f, "Line {} is not on the form name = value", line),
+        }
+    }
+}
+
+/**
+  A value that can be written as the text of a parameter and parsed back
+*/
+pub trait ParameterValue: Sized
+{
+    fn to_parameter(&self) -> String;
+    fn from_parameter(text: &str) -> Option<Self>;
+}
+
+macro_rules! parsed_parameter {
+    ($($type:ty),*) => {
+        $(
+            impl ParameterValue for $type
+            {
+                fn to_parameter(&self) -> String
+                {
+                    self.to_string()
+                }
+                fn from_parameter(text: &str) -> Option<$type>
+                {
+                    text.parse().ok()
+                }
+            }
+        )*
+    }
+}
+
+macro_rules! enum_parameter {
+    ($type:ident { $($variant:ident),* }) => {
+        impl ParameterValue for $type
+        {
+            fn to_parameter(&self) -> String
+            {
+                format!("{:?}", self)
+            }
+            fn from_parameter(text: &str) -> Option<$type>
+            {
+                $(
+                    if text == stringify!($variant)
+                    {
+                        return Some($type::$variant);
+                    }
+                )*
+                None
+            }
+        }
+    }
+}
+
+parsed_parameter!(f32, u32, bool);
+enum_parameter!(Material { Pla, Petg, Abs, Tpu });
+enum_parameter!(VtxMounting { Boxed, Stack });
+enum_parameter!(AntennaConnector { Sma, RpSma, Mmcx, UflPigtail });
+enum_parameter!(BatteryLeadExit { Front, Back, Left, Right });
+enum_parameter!(LighteningPattern { None, Truss, Honeycomb, Slots });
+enum_parameter!(EdgeFinish { Sharp, Round, Chamfer });
+enum_parameter!(Threading { ThroughNut, NutTrap, SelfTapping, HeatSetInsert });
+enum_parameter!(HeadRecess { Counterbore, Countersink, ButtonHead });
+
+/**
+  A struct with named parameters. Parameters of nested structs are named
+  `<field>.<parameter>`
+*/
+pub trait Parameters
+{
+    fn add_parameters(&self, prefix: &str, result: &mut ParameterSet);
+
+    /**
+      Sets a parameter from its text. Returns false if there is no parameter
+      with the name
+    */
+    fn set_parameter(&mut self, name: &str, value: &str) -> Result<bool, ParameterError>;
+}
+
+macro_rules! parameters {
+    ($type:ident { $($field:ident),* $(,)* } $(, $group:ident)*) => {
+        impl Parameters for $type
+        {
+            fn add_parameters(&self, prefix: &str, result: &mut ParameterSet)
+            {
+                $(
+                    result.values.push((
+                        format!("{}{}", prefix, stringify!($field)),
+                        self.$field.to_parameter()
+                    ));
+                )*
+                $(
+                    self.$group.add_parameters(
+                        &format!("{}{}.", prefix, stringify!($group)),
+                        result
+                    );
+                )*
+            }
+
+            fn set_parameter(&mut self, name: &str, value: &str) -> Result<bool, ParameterError>
+            {
+                $(
+                    if name == stringify!($field)
+                    {
+                        self.$field = ParameterValue::from_parameter(value).ok_or_else(|| {
+                            ParameterError::InvalidValue(name.to_string(), value.to_string())
+                        })?;
+                        return Ok(true);
+                    }
+                )*
+                $(
+                    let prefix = concat!(stringify!($group), ".");
+                    if name.starts_with(prefix)
+                    {
+                        return self.$group.set_parameter(&name[prefix.len()..], value);
+                    }
+                )*
+                Ok(false)
+            }
+        }
+    }
+}
+
+parameters!(Vtx {
+    mounting, width, thickness, hole_edge_distance, hole_distance, hole_diameter,
+    hole_padding, mount_thickness, threading, connector, mass,
+});
+
+parameters!(BoardCamera {
+    width, thickness, lens_diameter, lens_length, snowproof_padding_radus, mass,
+    field_of_view,
+});
+
+parameters!(Battery {
+    cell_count, capacity, length, width, height, lead_exit, mass,
+});
+
+parameters!(TricopterBody {
+    radius, top_height, height, outer_width, back_outer_width, inner_width,
+    center_width, back_block_length_factor, arm_width, arm_padding, front_block_x,
+    front_section_width, front_section_length, front_section_corner_radius,
+    canopy_thickness, edge_thickness, edge_padding, edge_height, camera_box_length,
+    camera_box_edge_width, camera_box_edge_padding, camera_spot_side_width,
+    motor_wire_hole_radius, camera_offset_from_top, mounting_screw_outline_radius,
+    min_screw_wall, canopy_max_height, screw_mount_height, canopy_bottom_min_height,
+    side_plate_arc_width, side_plate_arc_height, side_plate_thickness,
+    side_plate_mount_length, side_plate_front_screw_top_offset,
+    camera_uptilt, flight_controller_hole_distance, battery_position, battery_strap_count,
+    battery_strap_width, battery_strap_padding, battery_strap_slot_width,
+    battery_strap_clearance, battery_wire_hole_inset, battery_pad_thickness,
+    battery_pad_lip_width, battery_pad_lip_height, lightening, lightening_cell_size,
+    lightening_rib_width, lightening_keepout, plate_edge, plate_edge_size, plate_corner_radius,
+    screw_entry_chamfer, canopy_front_fillet_radius, plate_screw_threading,
+    side_plate_threading, flight_controller_threading, arm_mount_threading,
+    arm_stopper_threading, back_block_screw_threading, camera_mount_threading,
+    side_plate_back_threading, canopy_screw_head,
+    canopy_front_screw_length, canopy_back_screw_length,
+    flight_controller_screw_head, flight_controller_screw_length, flight_controller_screw_reach,
+}, vtx, camera, battery);
+
+parameters!(ServoMount {
+    servo_width, servo_depth, servo_height, servo_tab_height, boom_height, boom_width,
+    side_thickness, prop_clearance, top_offset, servo_mass, servo_tilt_range,
+});
+
+parameters!(NazeBoard { hole_diameter, hole_distance, hole_padding_radius, mass });
+
+parameters!(Esc { width, length, thickness, mass });
+
+parameters!(Motor { diameter, height, mass });
+
+parameters!(Assembly {
+    arm_length, back_arm_length, arm_start, arm_mass_per_length,
+    flight_controller_height, esc_stack_x, esc_stack_height, esc_stack_spacing, vtx_x,
+    vtx_height, prop_diameter, prop_offset, prop_thickness, prop_tip_clearance,
+    center_of_mass_offset, material, pad_material, fill_factor, nozzle_width,
+    min_wall_lines, max_overhang_angle, max_bridge_length, bed_width, bed_depth, bed_height,
+}, body, servo_mount, flight_controller, esc, motor);
+
+/**
+  The values of all the parameters of an assembly in text form
+*/
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParameterSet
+{
+    pub values: Vec<(String, String)>,
+}
+
+impl ParameterSet
+{
+    pub fn of(assembly: &Assembly) -> ParameterSet
+    {
+        let mut result = ParameterSet{values: vec!()};
+        assembly.add_parameters("", &mut result);
+        result
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str>
+    {
+        self.values.iter()
+            .find(|&&(ref parameter, _)| parameter == name)
+            .map(|&(_, ref value)| value.as_str())
+    }
+
+    /**
+      Sets all the parameters of the assembly to the values in the set
+    */
+    pub fn apply(&self, assembly: &mut Assembly) -> Result<(), ParameterError>
+    {
+        for &(ref name, ref value) in &self.values
+        {
+            set_parameter(assembly, name, value)?;
+        }
+        Ok(())
+    }
+
+    /**
+      Parses parameters written by `to_text`. Empty lines and lines starting
+      with # are ignored
+    */
+    pub fn parse(text: &str) -> Result<ParameterSet, ParameterError>
+    {
+        let mut values = vec!();
+        for (i, line) in text.lines().enumerate()
+        {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#')
+            {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=');
+            match (parts.next(), parts.next())
+            {
+                (Some(name), Some(value)) => {
+                    values.push((name.trim().to_string(), value.trim().to_string()))
+                }
+                _ => return Err(ParameterError::Syntax(i + 1)),
+            }
+        }
+        Ok(ParameterSet{values})
+    }
+
+    pub fn to_text(&self) -> String
+    {
+        self.values.iter()
+            .map(|&(ref name, ref value)| format!("{} = {}\n", name, value))
+            .collect()
+    }
+}
+
+/**
+  Sets a parameter of the assembly by name, like `body.radius`
+*/
+pub fn set_parameter(assembly: &mut Assembly, name: &str, value: &str) -> Result<(), ParameterError>
+{
+    match assembly.set_parameter(name, value)
+    {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(ParameterError::Unknown(name.to_string())),
+        //Nested structs only know the last part of the name
+        Err(ParameterError::InvalidValue(_, value)) => {
+            Err(ParameterError::InvalidValue(name.to_string(), value))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn parameters_survive_text()
+    {
+        let mut assembly = Assembly::new();
+        set_parameter(&mut assembly, "body.radius", "80.5").unwrap();
+        set_parameter(&mut assembly, "body.battery.lead_exit", "Left").unwrap();
+        set_parameter(&mut assembly, "material", "Abs").unwrap();
+
+        let parameters = ParameterSet::parse(&ParameterSet::of(&assembly).to_text()).unwrap();
+        let mut copy = Assembly::new();
+        parameters.apply(&mut copy).unwrap();
+
+        assert_eq!(copy.body.radius, 80.5);
+        assert_eq!(copy.body.battery.lead_exit, BatteryLeadExit::Left);
+        assert_eq!(copy.material, Material::Abs);
+        assert_eq!(ParameterSet::of(&copy), parameters);
+    }
+
+    #[test]
+    fn bad_parameters_are_errors()
+    {
+        let mut assembly = Assembly::new();
+        assert_eq!(
+            set_parameter(&mut assembly, "body.diameter", "1"),
+            Err(ParameterError::Unknown("body.diameter".to_string()))
+        );
+        assert_eq!(
+            set_parameter(&mut assembly, "body.radius", "wide"),
+            Err(ParameterError::InvalidValue("body.radius".to_string(), "wide".to_string()))
+        );
+    }
+}
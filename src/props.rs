@@ -0,0 +1,227 @@
+/*!
+  Checks that the propellers have room to spin.
+
+  Every propeller is modeled as a disk around its motor. The largest disk
+  that fits is limited by the other propellers, the parts of the body that
+  reach up to the propellers and the field of view of the camera. The tail
+  propeller is checked with the servo in the middle position.
+
+  On the default frame the field of view of the camera is the limit that
+  binds: the front propellers reach into the view long before they get
+  close to the body or to each other. Narrowing the view or tilting the
+  camera gives room for larger propellers.
+*/
+
+use assembly::Assembly;
+use camera_view::VIEW_DISTANCE;
+use evaluate::{BoundingBox, EvalError, Solid, Vec3};
+
+use std::fmt;
+
+//Size in mm of the cells used when looking for obstacles
+const RESOLUTION: f32 = 1.;
+
+/**
+  The largest propeller that fits on a motor without touching an obstacle
+*/
+#[derive(Clone, PartialEq, Debug)]
+pub struct PropLimit
+{
+    pub motor: usize,
+    pub obstacle: String,
+    pub max_diameter: f32,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct PropClearance
+{
+    //Diameter of the propellers of the assembly
+    pub prop_diameter: f32,
+    pub limits: Vec<PropLimit>,
+}
+
+/**
+  Returns the parts that the propellers must stay clear of
+*/
+fn get_obstacles(assembly: &Assembly) -> Result<Vec<(String, Solid)>, EvalError>
+{
+    let mut parts = assembly.get_parts().into_iter()
+        .filter(|part| part.name == "canopy")
+        .collect::<Vec<_>>();
+    parts.append(&mut assembly.get_side_plates());
+
+    let mut result = parts.iter()
+        .map(|part| Ok((part.name.clone(), Solid::from_object(&part.object)?)))
+        .collect::<Result<Vec<_>, EvalError>>()?;
+
+    result.push((
+        "camera_view".to_string(),
+        Solid::from_object(&assembly.get_camera_view(VIEW_DISTANCE))?
+    ));
+    Ok(result)
+}
+
+impl PropClearance
+{
+    pub fn new(assembly: &Assembly) -> Result<PropClearance, EvalError>
+    {
+        let positions = assembly.motor_positions();
+        let clearance = assembly.prop_tip_clearance;
+        let mut limits = vec!();
+
+        //The propellers all have the same size so two of them can be at most
+        //as large as the distance between their motors
+        for i in 0..positions.len()
+        {
+            for j in i + 1..positions.len()
+            {
+                let distance = (positions[i] - positions[j]).norm();
+                limits.push(PropLimit{
+                    motor: i,
+                    obstacle: format!("prop_{}", j),
+                    max_diameter: distance - clearance,
+                });
+            }
+        }
+
+        let half_thickness = assembly.prop_thickness / 2.;
+        //Obstacles further away than this are not limiting compared to the
+        //other propellers
+        let search_radius = assembly.prop_diameter;
+
+        for (name, solid) in get_obstacles(assembly)?
+        {
+            for (i, position) in positions.iter().enumerate()
+            {
+                let z = assembly.prop_center_height(i);
+                let bounds = BoundingBox::new(
+                    Vec3::new(position.x - search_radius, position.y - search_radius, z - half_thickness),
+                    Vec3::new(position.x + search_radius, position.y + search_radius, z + half_thickness)
+                );
+
+                let closest = solid.sample_points_in(&bounds, RESOLUTION).iter()
+                    .map(|point| (point.x - position.x).hypot(point.y - position.y))
+                    .fold(None, |acc: Option<f32>, distance| {
+                        Some(acc.map_or(distance, |acc| acc.min(distance)))
+                    });
+
+                if let Some(distance) = closest
+                {
+                    limits.push(PropLimit{
+                        motor: i,
+                        obstacle: name.clone(),
+                        max_diameter: (distance - clearance) * 2.,
+                    });
+                }
+            }
+        }
+
+        Ok(PropClearance{prop_diameter: assembly.prop_diameter, limits})
+    }
+
+    /**
+      Returns the largest propeller that fits on all the motors
+    */
+    pub fn max_prop_diameter(&self) -> f32
+    {
+        self.binding_limit().map_or(f32::INFINITY, |limit| limit.max_diameter)
+    }
+
+    /**
+      Returns the limit that sets the largest propeller that fits
+    */
+    pub fn binding_limit(&self) -> Option<&PropLimit>
+    {
+        self.limits.iter()
+            .fold(None, |acc: Option<&PropLimit>, limit| match acc
+            {
+                Some(acc) if acc.max_diameter <= limit.max_diameter => Some(acc),
+                _ => Some(limit),
+            })
+    }
+
+    /**
+      Returns the obstacles that the current propellers hit
+    */
+    pub fn intersections(&self) -> Vec<&PropLimit>
+    {
+        self.limits.iter()
+            .filter(|limit| limit.max_diameter < self.prop_diameter)
+            .collect()
+    }
+}
+
+impl fmt::Display for PropClearance
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        writeln!(f, "{:<8} {:<20} {:>18}", "motor", "obstacle", "max diameter (mm)")?;
+        for limit in &self.limits
+        {
+            writeln!(f, "{:<8} {:<20} {:>18.1}", limit.motor, limit.obstacle, limit.max_diameter)?;
+        }
+
+        writeln!(f)?;
+        writeln!(f, "Propeller diameter: {:.1} mm", self.prop_diameter)?;
+        writeln!(f, "Largest propeller that fits: {:.1} mm", self.max_prop_diameter())?;
+        if let Some(limit) = self.binding_limit()
+        {
+            writeln!(f, "Limited by {} on motor {}", limit.obstacle, limit.motor)?;
+        }
+        for limit in self.intersections()
+        {
+            writeln!(
+                f,
+                "Warning: the propeller on motor {} hits {}",
+                limit.motor, limit.obstacle
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn camera_view_limits_the_default_propellers()
+    {
+        let clearance = PropClearance::new(&Assembly::new()).unwrap();
+
+        let limit = clearance.binding_limit().unwrap();
+        assert_eq!(limit.obstacle, "camera_view");
+        assert!(clearance.max_prop_diameter() < clearance.prop_diameter);
+        let intersections = clearance.intersections();
+        assert!(!intersections.is_empty());
+        assert!(intersections.iter().all(|limit| limit.obstacle == "camera_view"), "{}", clearance);
+    }
+
+    #[test]
+    fn propellers_are_limited_by_the_distance_between_motors()
+    {
+        let assembly = Assembly::new();
+        let clearance = PropClearance::new(&assembly).unwrap();
+
+        let positions = assembly.motor_positions();
+        let distance = (positions[0] - positions[1]).norm();
+        let limit = clearance.limits.iter()
+            .find(|limit| limit.motor == 0 && limit.obstacle == "prop_1")
+            .unwrap();
+        assert!((limit.max_diameter - (distance - assembly.prop_tip_clearance)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn narrower_view_fits_larger_propellers()
+    {
+        let mut assembly = Assembly::new();
+        let default = PropClearance::new(&assembly).unwrap().max_prop_diameter();
+
+        assembly.body.camera.field_of_view = 60.;
+        let clearance = PropClearance::new(&assembly).unwrap();
+
+        assert!(clearance.max_prop_diameter() > default);
+        assert!(clearance.intersections().is_empty(), "{}", clearance);
+    }
+}
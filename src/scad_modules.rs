@@ -0,0 +1,275 @@
+/*!
+  Shrinks generated OpenSCAD code by defining repeated subtrees once.
+
+  Helpers like `get_mid_section_outline` are used many times by a part, and
+  every use is written out in full by the scad crate. Here every subtree is
+  given an id from its header and the ids of its children, so identical
+  subtrees get the same id. Subtrees that are used more than once and are
+  large enough to be worth it become modules that the uses call instead.
+*/
+
+use scad_tree::{self, Node, ParseError, Statement};
+
+use std::collections::HashMap;
+
+//Subtrees with fewer nodes than this are written out where they are used
+const MIN_MODULE_SIZE: usize = 4;
+
+struct Entry
+{
+    node: Node,
+    ids: Ids,
+    size: usize,
+    //Number of times that the subtree is a top level object
+    roots: usize,
+}
+
+/**
+  The ids of a node and its children, in the same shape as the node
+*/
+#[derive(Clone)]
+struct Ids
+{
+    id: usize,
+    children: Vec<Ids>,
+}
+
+/**
+  Identical subtrees of the code
+*/
+struct SubtreeTable
+{
+    entries: Vec<Entry>,
+    ids: HashMap<(String, Vec<usize>), usize>,
+}
+
+impl SubtreeTable
+{
+    fn new() -> SubtreeTable
+    {
+        SubtreeTable{entries: vec!(), ids: HashMap::new()}
+    }
+
+    /**
+      Adds the node and all its children, returns their ids
+    */
+    fn add(&mut self, node: &Node) -> Ids
+    {
+        let children = node.children.iter()
+            .map(|child| self.add(child))
+            .collect::<Vec<_>>();
+        let child_ids = children.iter().map(|child| child.id).collect::<Vec<_>>();
+
+        let size = 1 + child_ids.iter().map(|&child| self.entries[child].size).sum::<usize>();
+        let key = (node.header(), child_ids);
+
+        let entries = &mut self.entries;
+        let id = *self.ids.entry(key).or_insert_with(|| {
+            entries.push(Entry{
+                node: node.clone(),
+                ids: Ids{id: entries.len(), children: children.clone()},
+                size,
+                roots: 0,
+            });
+            entries.len() - 1
+        });
+        Ids{id, children}
+    }
+
+    /**
+      Returns the ids of the subtrees that become modules.
+
+      A subtree is counted once for every time that it is written out. The
+      body of a module is written once however many times the module is
+      called, so subtrees that are only used inside a repeated module are
+      not repeated in the code
+    */
+    fn modules(&self) -> Vec<usize>
+    {
+        //Parents are larger than their children so their uses are known
+        //before the uses of the children are counted
+        let mut order = (0..self.entries.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&id| ::std::cmp::Reverse(self.entries[id].size));
+
+        let mut uses = self.entries.iter().map(|entry| entry.roots).collect::<Vec<_>>();
+        let mut result = vec!();
+        for id in order
+        {
+            let entry = &self.entries[id];
+            let written = if uses[id] > 1 && entry.size >= MIN_MODULE_SIZE
+            {
+                result.push(id);
+                1
+            }
+            else
+            {
+                uses[id]
+            };
+            for child in &entry.ids.children
+            {
+                uses[child.id] += written;
+            }
+        }
+        result.sort();
+        result
+    }
+}
+
+struct Writer
+{
+    table: SubtreeTable,
+    names: HashMap<usize, String>,
+}
+
+impl Writer
+{
+    /**
+      Returns the node with the subtrees that are modules replaced by calls
+    */
+    fn with_calls(&self, node: &Node, ids: &Ids) -> Node
+    {
+        let mut result = node.clone();
+        result.children = node.children.iter()
+            .zip(ids.children.iter())
+            .map(|(child, child_ids)| match self.names.get(&child_ids.id)
+            {
+                Some(name) => Node{
+                    name: name.clone(),
+                    arguments: vec!(),
+                    children: vec!(),
+                    modifier: None,
+                },
+                None => self.with_calls(child, child_ids),
+            })
+            .collect();
+        result
+    }
+
+    fn write_modules(&self, target: &mut String)
+    {
+        let mut ids = self.names.keys().cloned().collect::<Vec<_>>();
+        ids.sort();
+        for id in ids
+        {
+            let entry = &self.table.entries[id];
+            target.push_str(&format!("module {}()\n{{\n", self.names[&id]));
+            self.with_calls(&entry.node, &entry.ids).write_code(1, target);
+            target.push_str("}\n\n");
+        }
+    }
+}
+
+/**
+  Returns the code with every repeated subtree replaced by a call to a
+  module that is defined once
+*/
+pub fn with_modules(code: &str) -> Result<String, ParseError>
+{
+    let statements = scad_tree::parse(code)?;
+
+    let mut table = SubtreeTable::new();
+    let mut roots = vec!();
+    for statement in &statements
+    {
+        if let Statement::Module(ref node) = *statement
+        {
+            let ids = table.add(node);
+            table.entries[ids.id].roots += 1;
+            roots.push(ids);
+        }
+    }
+
+    //Modules are named after the first module of the subtree and numbered
+    //in the order that they were first used
+    let mut names = HashMap::new();
+    for id in table.modules()
+    {
+        let name = format!("{}_{}", table.entries[id].node.name, names.len());
+        names.insert(id, name);
+    }
+    let writer = Writer{table, names};
+
+    let mut result = String::new();
+    for statement in &statements
+    {
+        if let Statement::Assignment(ref name, ref value) = *statement
+        {
+            result.push_str(&format!("{}={};\n", name, value));
+        }
+    }
+    result.push('\n');
+    writer.write_modules(&mut result);
+
+    //The top level objects are written out even when they are identical
+    //since each of them is a separate object in the file
+    let nodes = statements.iter().filter_map(|statement| match *statement
+    {
+        Statement::Module(ref node) => Some(node),
+        _ => None,
+    });
+    for (node, ids) in nodes.zip(roots.iter())
+    {
+        writer.with_calls(node, ids).write_code(0, &mut result);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use scad::*;
+
+    fn shared() -> ScadObject
+    {
+        scad!(Translate(vec3(1., 2., 3.)); {
+            scad!(Rotate(45., vec3(0., 0., 1.)); {
+                scad!(Cube(vec3(1., 2., 3.)))
+            }),
+            scad!(Sphere(Radius(2.)))
+        })
+    }
+
+    #[test]
+    fn repeated_subtrees_become_modules()
+    {
+        let object = scad!(Union; {
+            shared(),
+            scad!(Translate(vec3(10., 0., 0.)); shared())
+        });
+
+        let code = with_modules(&object.get_code()).unwrap();
+        assert_eq!(code.matches("module ").count(), 1);
+        assert_eq!(code.matches("rotate(").count(), 1);
+    }
+
+    #[test]
+    fn subtrees_inside_a_single_module_stay_in_it()
+    {
+        //The translate is large enough to be a module but is only written
+        //in the body of the union module
+        let repeated = || scad!(Union; {
+            shared(),
+            scad!(Cube(vec3(5., 5., 5.)))
+        });
+        let object = scad!(Difference; {
+            repeated(),
+            scad!(Translate(vec3(10., 0., 0.)); repeated())
+        });
+
+        let code = with_modules(&object.get_code()).unwrap();
+        assert_eq!(code.matches("module ").count(), 1, "{}", code);
+        assert_eq!(code.matches("translate([1,2,3])").count(), 1, "{}", code);
+    }
+
+    #[test]
+    fn calls_replace_the_subtrees()
+    {
+        let code = "$fn=20;\nunion()\n{\n\ttranslate([1,0,0])\n\t{\n\t\tunion()\n\t\t{\n\t\t\tcube([1,1,1]);\n\t\t\tsphere(r=1);\n\t\t}\n\t\tcube([2,2,2]);\n\t}\n\ttranslate([1,0,0])\n\t{\n\t\tunion()\n\t\t{\n\t\t\tcube([1,1,1]);\n\t\t\tsphere(r=1);\n\t\t}\n\t\tcube([2,2,2]);\n\t}\n}\n";
+
+        let expected = "$fn=20;\n\nmodule translate_0()\n{\n\ttranslate([1,0,0])\n\t{\n\t\tunion()\n\t\t{\n\t\t\tcube([1,1,1]);\n\t\t\tsphere(r=1);\n\t\t}\n\t\tcube([2,2,2]);\n\t}\n}\n\nunion()\n{\n\ttranslate_0();\n\ttranslate_0();\n}\n";
+
+        assert_eq!(with_modules(code).unwrap(), expected);
+    }
+}
@@ -0,0 +1,598 @@
+/*!
+  A tree representation of generated OpenSCAD code.
+
+  `ScadObject` does not expose its contents, so anything that needs to look
+  inside the generated geometry parses the code that it emits instead.
+*/
+
+use std::fmt;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value
+{
+    Number(f32),
+    Bool(bool),
+    Str(String),
+    Vector(Vec<Value>),
+    Undef,
+}
+
+impl Value
+{
+    pub fn as_number(&self) -> Option<f32>
+    {
+        match *self
+        {
+            Value::Number(value) => Some(value),
+            _ => None
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool>
+    {
+        match *self
+        {
+            Value::Bool(value) => Some(value),
+            _ => None
+        }
+    }
+
+    /**
+      Returns the numbers in a vector value. Returns None if this is not a
+      vector or if any of the elements are not numbers
+    */
+    pub fn as_numbers(&self) -> Option<Vec<f32>>
+    {
+        match *self
+        {
+            Value::Vector(ref values) => values.iter().map(|v| v.as_number()).collect(),
+            _ => None
+        }
+    }
+}
+
+impl fmt::Display for Value
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self
+        {
+            Value::Number(value) => write!(f, "{}", value),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Str(ref value) => write!(f, "{:?}", value),
+            Value::Vector(ref values) => {
+                write!(f, "[")?;
+                for (i, value) in values.iter().enumerate()
+                {
+                    if i != 0
+                    {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, "]")
+            }
+            Value::Undef => write!(f, "undef"),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Argument
+{
+    pub name: Option<String>,
+    pub value: Value,
+}
+
+impl fmt::Display for Argument
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match self.name
+        {
+            Some(ref name) => write!(f, "{}={}", name, self.value),
+            None => write!(f, "{}", self.value),
+        }
+    }
+}
+
+/**
+  A single statement in the code. Either a module instantiation like
+  `translate([1,2,3]) {...}` or an assignment like `$fn=20;`
+*/
+#[derive(Clone, PartialEq, Debug)]
+pub enum Statement
+{
+    Module(Node),
+    Assignment(String, Value),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Node
+{
+    pub name: String,
+    pub arguments: Vec<Argument>,
+    pub children: Vec<Node>,
+    //Modifier characters like ! or # that were in front of the module
+    pub modifier: Option<char>,
+}
+
+impl Node
+{
+    /**
+      Returns the value of an argument which can either be passed by name
+      or as the `position`th positional argument
+    */
+    pub fn argument(&self, name: &str, position: usize) -> Option<&Value>
+    {
+        let named = self.arguments.iter()
+            .find(|arg| arg.name.as_ref().map(|n| n == name).unwrap_or(false));
+
+        match named
+        {
+            Some(arg) => Some(&arg.value),
+            None => self.arguments.iter()
+                .filter(|arg| arg.name.is_none())
+                .nth(position)
+                .map(|arg| &arg.value)
+        }
+    }
+
+    pub fn named_argument(&self, name: &str) -> Option<&Value>
+    {
+        self.arguments.iter()
+            .find(|arg| arg.name.as_ref().map(|n| n == name).unwrap_or(false))
+            .map(|arg| &arg.value)
+    }
+
+    /**
+      Returns the code for the module call without the children
+    */
+    pub fn header(&self) -> String
+    {
+        let arguments = self.arguments.iter()
+            .map(|arg| arg.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let modifier = self.modifier.map(|c| c.to_string()).unwrap_or_default();
+        format!("{}{}({})", modifier, self.name, arguments)
+    }
+
+    /**
+      Writes the node as OpenSCAD code, indented by `indent` tabs
+    */
+    pub fn write_code(&self, indent: usize, target: &mut String)
+    {
+        let tabs = "\t".repeat(indent);
+        target.push_str(&tabs);
+        target.push_str(&self.header());
+
+        if self.children.is_empty()
+        {
+            target.push_str(";\n");
+        }
+        else
+        {
+            target.push('\n');
+            target.push_str(&tabs);
+            target.push_str("{\n");
+            for child in &self.children
+            {
+                child.write_code(indent + 1, target);
+            }
+            target.push_str(&tabs);
+            target.push_str("}\n");
+        }
+    }
+}
+
+impl fmt::Display for Node
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        let mut code = String::new();
+        self.write_code(0, &mut code);
+        write!(f, "{}", code)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParseError
+{
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "Failed to parse scad code at {}: {}", self.position, self.message)
+    }
+}
+
+/**
+  Parses all the statements in a piece of scad code
+*/
+pub fn parse(code: &str) -> Result<Vec<Statement>, ParseError>
+{
+    let mut parser = Parser{chars: code.chars().collect(), position: 0};
+
+    let mut result = vec!();
+    loop
+    {
+        parser.skip_whitespace();
+        if parser.peek().is_none()
+        {
+            break;
+        }
+        result.push(parser.statement()?);
+    }
+    Ok(result)
+}
+
+/**
+  Parses scad code and returns all the module nodes in it, ignoring
+  assignments
+*/
+pub fn parse_nodes(code: &str) -> Result<Vec<Node>, ParseError>
+{
+    Ok(parse(code)?.into_iter()
+        .filter_map(|statement| match statement
+        {
+            Statement::Module(node) => Some(node),
+            Statement::Assignment(_, _) => None,
+        })
+        .collect())
+}
+
+struct Parser
+{
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser
+{
+    fn peek(&self) -> Option<char>
+    {
+        self.chars.get(self.position).cloned()
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T, ParseError>
+    {
+        Err(ParseError{position: self.position, message: message.to_string()})
+    }
+
+    fn skip_whitespace(&mut self)
+    {
+        loop
+        {
+            match self.peek()
+            {
+                Some(c) if c.is_whitespace() => self.position += 1,
+                Some('/') if self.chars.get(self.position + 1) == Some(&'/') => {
+                    while self.peek().map(|c| c != '\n').unwrap_or(false)
+                    {
+                        self.position += 1;
+                    }
+                }
+                Some('/') if self.chars.get(self.position + 1) == Some(&'*') => {
+                    self.position += 2;
+                    while self.position < self.chars.len()
+                        && !(self.chars[self.position] == '*'
+                             && self.chars.get(self.position + 1) == Some(&'/'))
+                    {
+                        self.position += 1;
+                    }
+                    self.position += 2;
+                }
+                _ => return
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ParseError>
+    {
+        self.skip_whitespace();
+        if self.peek() == Some(expected)
+        {
+            self.position += 1;
+            Ok(())
+        }
+        else
+        {
+            self.error(&format!("Expected '{}'", expected))
+        }
+    }
+
+    fn identifier(&mut self) -> Result<String, ParseError>
+    {
+        self.skip_whitespace();
+        let start = self.position;
+        while self.peek().map(|c| c.is_alphanumeric() || c == '_' || c == '$').unwrap_or(false)
+        {
+            self.position += 1;
+        }
+
+        if start == self.position
+        {
+            return self.error("Expected identifier");
+        }
+        Ok(self.chars[start..self.position].iter().collect())
+    }
+
+    fn statement(&mut self) -> Result<Statement, ParseError>
+    {
+        self.skip_whitespace();
+        let modifier = match self.peek()
+        {
+            Some(c) if "!#%*".contains(c) => {
+                self.position += 1;
+                Some(c)
+            }
+            _ => None
+        };
+
+        let name = self.identifier()?;
+        self.skip_whitespace();
+
+        if modifier.is_none() && self.peek() == Some('=')
+        {
+            self.position += 1;
+            let value = self.value()?;
+            self.expect(';')?;
+            return Ok(Statement::Assignment(name, value));
+        }
+
+        let arguments = self.arguments()?;
+        let children = self.children()?;
+
+        Ok(Statement::Module(Node{name, arguments, children, modifier}))
+    }
+
+    fn arguments(&mut self) -> Result<Vec<Argument>, ParseError>
+    {
+        self.expect('(')?;
+        let mut result = vec!();
+        loop
+        {
+            self.skip_whitespace();
+            if self.peek() == Some(')')
+            {
+                self.position += 1;
+                return Ok(result);
+            }
+
+            //Named arguments start with an identifier followed by =
+            let start = self.position;
+            let name = match self.identifier()
+            {
+                Ok(name) => {
+                    self.skip_whitespace();
+                    if self.peek() == Some('=')
+                    {
+                        self.position += 1;
+                        Some(name)
+                    }
+                    else
+                    {
+                        self.position = start;
+                        None
+                    }
+                }
+                Err(_) => {
+                    self.position = start;
+                    None
+                }
+            };
+
+            let value = self.value()?;
+            result.push(Argument{name, value});
+
+            self.skip_whitespace();
+            match self.peek()
+            {
+                Some(',') => self.position += 1,
+                Some(')') => {}
+                _ => return self.error("Expected ',' or ')' in argument list")
+            }
+        }
+    }
+
+    fn children(&mut self) -> Result<Vec<Node>, ParseError>
+    {
+        self.skip_whitespace();
+        match self.peek()
+        {
+            Some(';') => {
+                self.position += 1;
+                Ok(vec!())
+            }
+            Some('{') => {
+                self.position += 1;
+                let mut result = vec!();
+                loop
+                {
+                    self.skip_whitespace();
+                    match self.peek()
+                    {
+                        Some('}') => {
+                            self.position += 1;
+                            return Ok(result);
+                        }
+                        Some(';') => self.position += 1,
+                        None => return self.error("Unterminated block"),
+                        _ => {
+                            if let Statement::Module(node) = self.statement()?
+                            {
+                                result.push(node);
+                            }
+                        }
+                    }
+                }
+            }
+            //A single child without braces
+            Some(_) => {
+                match self.statement()?
+                {
+                    Statement::Module(node) => Ok(vec!(node)),
+                    Statement::Assignment(_, _) => self.error("Unexpected assignment"),
+                }
+            }
+            None => self.error("Unexpected end of code"),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, ParseError>
+    {
+        self.skip_whitespace();
+        match self.peek()
+        {
+            Some('[') => {
+                self.position += 1;
+                let mut values = vec!();
+                loop
+                {
+                    self.skip_whitespace();
+                    if self.peek() == Some(']')
+                    {
+                        self.position += 1;
+                        return Ok(Value::Vector(values));
+                    }
+                    values.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.peek()
+                    {
+                        Some(',') => self.position += 1,
+                        Some(']') => {}
+                        _ => return self.error("Expected ',' or ']' in vector")
+                    }
+                }
+            }
+            Some('"') => {
+                self.position += 1;
+                let mut result = String::new();
+                loop
+                {
+                    match self.peek()
+                    {
+                        Some('"') => {
+                            self.position += 1;
+                            return Ok(Value::Str(result));
+                        }
+                        Some('\\') => {
+                            self.position += 1;
+                            if let Some(c) = self.peek()
+                            {
+                                result.push(c);
+                                self.position += 1;
+                            }
+                        }
+                        Some(c) => {
+                            result.push(c);
+                            self.position += 1;
+                        }
+                        None => return self.error("Unterminated string")
+                    }
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => {
+                let start = self.position;
+                self.position += 1;
+                while self.peek()
+                    .map(|c| c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E'
+                         || ((c == '-' || c == '+')
+                             && (self.chars[self.position - 1] == 'e'
+                                 || self.chars[self.position - 1] == 'E')))
+                    .unwrap_or(false)
+                {
+                    self.position += 1;
+                }
+                let text: String = self.chars[start..self.position].iter().collect();
+                match text.parse::<f32>()
+                {
+                    Ok(value) => Ok(Value::Number(value)),
+                    Err(_) => {
+                        self.position = start;
+                        self.error(&format!("Invalid number '{}'", text))
+                    }
+                }
+            }
+            _ => {
+                let identifier = self.identifier()?;
+                match identifier.as_str()
+                {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    "undef" => Ok(Value::Undef),
+                    "inf" => Ok(Value::Number(f32::INFINITY)),
+                    "nan" | "NaN" => Ok(Value::Number(f32::NAN)),
+                    _ => self.error(&format!("Unsupported value '{}'", identifier))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn arguments_are_found_by_name_or_position()
+    {
+        let nodes = parse_nodes("!translate([1, 2.5, -3e1]) cylinder(4, r1=2, center=true);").unwrap();
+        assert_eq!(nodes.len(), 1);
+
+        let translate = &nodes[0];
+        assert_eq!(translate.modifier, Some('!'));
+        assert_eq!(translate.argument("v", 0).and_then(|v| v.as_numbers()), Some(vec!(1., 2.5, -30.)));
+
+        let cylinder = &translate.children[0];
+        assert_eq!(cylinder.argument("h", 0), Some(&Value::Number(4.)));
+        assert_eq!(cylinder.argument("r1", 1), Some(&Value::Number(2.)));
+        assert_eq!(cylinder.named_argument("center"), Some(&Value::Bool(true)));
+        assert_eq!(cylinder.argument("r2", 2), None);
+    }
+
+    #[test]
+    fn blocks_and_assignments_are_parsed()
+    {
+        let code = "$fn = 20;\n// comment\nunion() { cube(1); /* block */ color(\"red\") sphere(undef); }";
+        let statements = parse(code).unwrap();
+
+        assert_eq!(statements[0], Statement::Assignment("$fn".to_string(), Value::Number(20.)));
+        match statements[1]
+        {
+            Statement::Module(ref node) => {
+                assert_eq!(node.name, "union");
+                assert_eq!(node.children.len(), 2);
+                assert_eq!(node.children[1].argument("c", 0), Some(&Value::Str("red".to_string())));
+                assert_eq!(node.children[1].children[0].argument("r", 0), Some(&Value::Undef));
+            }
+            ref other => panic!("Expected a module, got {:?}", other)
+        }
+    }
+
+    #[test]
+    fn written_code_parses_to_the_same_tree()
+    {
+        let nodes = parse_nodes("difference(){cube([1,2,3,],center=false);#circle(d=2);}").unwrap();
+        let mut code = String::new();
+        nodes[0].write_code(0, &mut code);
+
+        assert_eq!(nodes[0].header(), "difference()");
+        assert_eq!(parse_nodes(&code).unwrap(), nodes);
+    }
+
+    #[test]
+    fn invalid_code_is_an_error()
+    {
+        assert!(parse_nodes("cube(1").is_err());
+        assert!(parse_nodes("translate([1,2) cube(1);").is_err());
+        assert!(parse_nodes("union() { cube(1);").is_err());
+    }
+}
@@ -0,0 +1,589 @@
+/*!
+  Export of the craft as URDF and SDF models for offline simulation.
+
+  The model has a body link, one link per arm and one link per motor. The
+  front motors are fixed to their arms while the tail motor is attached with
+  a revolute joint around the tail arm that is driven by the yaw servo.
+  Meshes are written as STL files next to the model files.
+
+  The body frame has x pointing towards the tail. Simulators expect x to
+  point forward (REP-103), so the models are turned half a turn around z
+  with y pointing left and z up.
+*/
+
+use assembly::{Assembly, AssemblyPart};
+use evaluate::{Solid, Vec3, EvalError};
+use inertia::InertiaReport;
+use mass::MassModel;
+use mesh;
+use na;
+
+use scad::*;
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+//Size in mm of the cells used to create the meshes
+const MESH_RESOLUTION: f32 = 1.;
+//Size in mm of the cells used to estimate the mass properties
+const MASS_RESOLUTION: f32 = 1.;
+
+#[derive(Debug)]
+pub enum ExportError
+{
+    Eval(EvalError),
+    Io(io::Error),
+}
+
+impl fmt::Display for ExportError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self
+        {
+            ExportError::Eval(ref e) => write!(f, "{}", e),
+            ExportError::Io(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<EvalError> for ExportError
+{
+    fn from(error: EvalError) -> ExportError
+    {
+        ExportError::Eval(error)
+    }
+}
+
+impl From<io::Error> for ExportError
+{
+    fn from(error: io::Error) -> ExportError
+    {
+        ExportError::Io(error)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum JointKind
+{
+    Fixed,
+    //Rotation around an axis with limits in degrees
+    Revolute(Vec3, f32, f32),
+}
+
+struct Link
+{
+    name: String,
+    parent: Option<String>,
+    joint_name: String,
+    joint: JointKind,
+    //Position of the link frame in the body frame, in mm
+    origin: Vec3,
+}
+
+/**
+  Returns the name of the link that a part of the assembly belongs to
+*/
+fn link_of_part(part_name: &str) -> String
+{
+    if part_name.starts_with("motor_") || part_name.starts_with("arm_")
+    {
+        part_name.to_string()
+    }
+    else if part_name.starts_with("servo")
+    {
+        "arm_0".to_string()
+    }
+    else
+    {
+        "base_link".to_string()
+    }
+}
+
+fn get_links(assembly: &Assembly) -> Vec<Link>
+{
+    let mut result = vec!(Link{
+        name: "base_link".to_string(),
+        parent: None,
+        joint_name: String::new(),
+        joint: JointKind::Fixed,
+        origin: Vec3::new(0., 0., 0.),
+    });
+
+    let arm_z = assembly.arm_center_height();
+    let motor_positions = assembly.motor_positions();
+
+    for (i, angle) in assembly.arm_angles().iter().enumerate()
+    {
+        let radians = angle.to_radians();
+        let arm_name = format!("arm_{}", i);
+        result.push(Link{
+            name: arm_name.clone(),
+            parent: Some("base_link".to_string()),
+            joint_name: format!("{}_joint", arm_name),
+            joint: JointKind::Fixed,
+            origin: Vec3::new(
+                radians.cos() * assembly.arm_start,
+                radians.sin() * assembly.arm_start,
+                arm_z
+            ),
+        });
+
+        //The first motor is the tail motor which is tilted by the servo
+        let range = assembly.servo_mount.servo_tilt_range;
+        let (joint_name, joint) = if i == 0
+        {
+            ("tail_tilt".to_string(), JointKind::Revolute(Vec3::new(1., 0., 0.), -range, range))
+        }
+        else
+        {
+            (format!("motor_{}_joint", i), JointKind::Fixed)
+        };
+
+        let position = motor_positions[i];
+        result.push(Link{
+            name: format!("motor_{}", i),
+            parent: Some(arm_name),
+            joint_name,
+            joint,
+            origin: Vec3::new(position.x, position.y, assembly.motor_height(i)),
+        });
+    }
+
+    result
+}
+
+/**
+  Returns the rotation from the body frame to the frame of the models
+*/
+fn model_rotation() -> na::Matrix3<f32>
+{
+    na::Matrix3::from_diagonal(&Vec3::new(-1., -1., 1.))
+}
+
+fn parent_origin(links: &[Link], link: &Link) -> Vec3
+{
+    link.parent.as_ref()
+        .and_then(|parent| links.iter().find(|l| &l.name == parent))
+        .map(|parent| parent.origin)
+        .unwrap_or(Vec3::new(0., 0., 0.))
+}
+
+fn indent(text: &str, levels: usize) -> String
+{
+    let prefix = "  ".repeat(levels);
+    text.lines()
+        .map(|line| format!("{}{}\n", prefix, line))
+        .collect()
+}
+
+fn mesh_filename(link: &Link) -> String
+{
+    format!("meshes/{}.stl", link.name)
+}
+
+fn format_vector(vector: &Vec3) -> String
+{
+    format!("{:e} {:e} {:e}", vector.x, vector.y, vector.z)
+}
+
+fn link_inertia(model: &MassModel, link: &Link) -> InertiaReport
+{
+    let link_model = model.subset(|name| link_of_part(name) == link.name);
+    InertiaReport::new(&link_model)
+        .relative_to(link.origin * 1e-3)
+        .rotated(&model_rotation())
+}
+
+fn urdf(links: &[Link], model: &MassModel) -> String
+{
+    let mut result = String::from("<?xml version=\"1.0\"?>\n<robot name=\"tricopter\">\n");
+
+    for link in links
+    {
+        let geometry = format!(
+            "<geometry>\n  <mesh filename=\"{}\" scale=\"0.001 0.001 0.001\"/>\n</geometry>\n",
+            mesh_filename(link)
+        );
+
+        result += &format!("  <link name=\"{}\">\n", link.name);
+        result += &indent(&link_inertia(model, link).to_urdf_inertial(), 2);
+        for element in &["visual", "collision"]
+        {
+            result += &format!("    <{}>\n", element);
+            result += &indent(&geometry, 3);
+            result += &format!("    </{}>\n", element);
+        }
+        result += "  </link>\n";
+
+        if let Some(ref parent) = link.parent
+        {
+            let offset = model_rotation() * (link.origin - parent_origin(links, link)) * 1e-3;
+            let kind = match link.joint
+            {
+                JointKind::Fixed => "fixed",
+                JointKind::Revolute(_, _, _) => "revolute",
+            };
+
+            result += &format!("  <joint name=\"{}\" type=\"{}\">\n", link.joint_name, kind);
+            result += &format!("    <parent link=\"{}\"/>\n", parent);
+            result += &format!("    <child link=\"{}\"/>\n", link.name);
+            result += &format!("    <origin xyz=\"{}\" rpy=\"0 0 0\"/>\n", format_vector(&offset));
+            if let JointKind::Revolute(axis, lower, upper) = link.joint
+            {
+                result += &format!("    <axis xyz=\"{}\"/>\n", format_vector(&(model_rotation() * axis)));
+                result += &format!(
+                    "    <limit lower=\"{:e}\" upper=\"{:e}\" effort=\"1\" velocity=\"10\"/>\n",
+                    lower.to_radians(),
+                    upper.to_radians()
+                );
+            }
+            result += "  </joint>\n";
+        }
+    }
+
+    result + "</robot>\n"
+}
+
+fn sdf(links: &[Link], model: &MassModel) -> String
+{
+    let mut result = String::from(
+        "<?xml version=\"1.0\"?>\n<sdf version=\"1.6\">\n  <model name=\"tricopter\">\n"
+    );
+
+    for link in links
+    {
+        let geometry = format!(
+            concat!(
+                "<geometry>\n",
+                "  <mesh>\n",
+                "    <uri>{}</uri>\n",
+                "    <scale>0.001 0.001 0.001</scale>\n",
+                "  </mesh>\n",
+                "</geometry>\n"
+            ),
+            mesh_filename(link)
+        );
+
+        result += &format!("    <link name=\"{}\">\n", link.name);
+        let pose = model_rotation() * link.origin * 1e-3;
+        result += &format!("      <pose>{} 0 0 0</pose>\n", format_vector(&pose));
+        result += &indent(&link_inertia(model, link).to_sdf_inertial(), 3);
+        for element in &["visual", "collision"]
+        {
+            result += &format!("      <{} name=\"{}\">\n", element, element);
+            result += &indent(&geometry, 4);
+            result += &format!("      </{}>\n", element);
+        }
+        result += "    </link>\n";
+
+        if let Some(ref parent) = link.parent
+        {
+            let kind = match link.joint
+            {
+                JointKind::Fixed => "fixed",
+                JointKind::Revolute(_, _, _) => "revolute",
+            };
+
+            result += &format!("    <joint name=\"{}\" type=\"{}\">\n", link.joint_name, kind);
+            result += &format!("      <parent>{}</parent>\n", parent);
+            result += &format!("      <child>{}</child>\n", link.name);
+            if let JointKind::Revolute(axis, lower, upper) = link.joint
+            {
+                result += "      <axis>\n";
+                result += &format!("        <xyz>{}</xyz>\n", format_vector(&(model_rotation() * axis)));
+                result += "        <limit>\n";
+                result += &format!("          <lower>{:e}</lower>\n", lower.to_radians());
+                result += &format!("          <upper>{:e}</upper>\n", upper.to_radians());
+                result += "        </limit>\n";
+                result += "      </axis>\n";
+            }
+            result += "    </joint>\n";
+        }
+    }
+
+    result + "  </model>\n</sdf>\n"
+}
+
+/**
+  Returns all the geometry of a link, moved so that the link origin is at
+  the origin and turned into the frame of the models
+*/
+fn link_geometry(parts: &[AssemblyPart], link: &Link) -> ScadObject
+{
+    let mut union = scad!(Union);
+    for part in parts.iter().filter(|part| link_of_part(&part.name) == link.name)
+    {
+        union.add_child(part.object.clone());
+    }
+
+    let origin = link.origin;
+    scad!(Rotate(180., vec3(0., 0., 1.)); {
+        scad!(Translate(vec3(-origin.x, -origin.y, -origin.z)); union)
+    })
+}
+
+/**
+  Writes `tricopter.urdf`, `model.sdf` and the meshes they use to a directory
+*/
+pub fn export(assembly: &Assembly, directory: &Path) -> Result<(), ExportError>
+{
+    let links = get_links(assembly);
+    let parts = assembly.get_parts();
+    let model = MassModel::new(assembly, MASS_RESOLUTION)?;
+
+    fs::create_dir_all(directory.join("meshes"))?;
+
+    for link in &links
+    {
+        let solid = Solid::from_object(&link_geometry(&parts, link))?;
+        let triangles = mesh::triangulate(&solid, MESH_RESOLUTION);
+        mesh::write_stl(&directory.join(mesh_filename(link)), &triangles)?;
+    }
+
+    fs::write(directory.join("tricopter.urdf"), urdf(&links, &model))?;
+    fs::write(directory.join("model.sdf"), sdf(&links, &model))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use std::env;
+
+    //Coarser than the export to keep the tests fast
+    const TEST_RESOLUTION: f32 = 2.;
+
+    fn model(assembly: &Assembly) -> MassModel
+    {
+        MassModel::new(assembly, TEST_RESOLUTION).unwrap()
+    }
+
+    /**
+      Returns the value of an attribute of the first element on the line
+    */
+    fn attribute<'a>(line: &'a str, name: &str) -> Option<&'a str>
+    {
+        let start = line.find(&format!(" {}=\"", name))? + name.len() + 3;
+        let length = line[start..].find('"')?;
+        Some(&line[start..start + length])
+    }
+
+    /**
+      Returns the text between the tags of the element on the line
+    */
+    fn content<'a>(line: &'a str, element: &str) -> Option<&'a str>
+    {
+        let start = line.find(&format!("<{}>", element))? + element.len() + 2;
+        let length = line[start..].find('<')?;
+        Some(&line[start..start + length])
+    }
+
+    fn numbers(text: &str) -> Vec<f32>
+    {
+        text.split_whitespace().map(|number| number.parse().unwrap()).collect()
+    }
+
+    struct ParsedLink
+    {
+        name: String,
+        //kg
+        mass: f32,
+        //m, in the frame of the link
+        center_of_mass: Vec3,
+    }
+
+    struct ParsedJoint
+    {
+        kind: String,
+        parent: String,
+        child: String,
+        limits: Option<(f32, f32)>,
+    }
+
+    /**
+      Returns the links and the joints of a URDF model, with the names of
+      the joints
+    */
+    fn parse_urdf(urdf: &str) -> (Vec<ParsedLink>, Vec<(String, ParsedJoint)>)
+    {
+        let mut links = vec!();
+        let mut joints: Vec<(String, ParsedJoint)> = vec!();
+        let mut in_link = false;
+        for line in urdf.lines().map(|line| line.trim())
+        {
+            if line.starts_with("<link ")
+            {
+                in_link = true;
+                links.push(ParsedLink{
+                    name: attribute(line, "name").unwrap().to_string(),
+                    mass: 0.,
+                    center_of_mass: Vec3::new(0., 0., 0.),
+                });
+            }
+            else if line.starts_with("</link>")
+            {
+                in_link = false;
+            }
+            //Joints have origins too
+            else if line.starts_with("<origin ") && in_link
+            {
+                let xyz = numbers(attribute(line, "xyz").unwrap());
+                links.last_mut().unwrap().center_of_mass = Vec3::new(xyz[0], xyz[1], xyz[2]);
+            }
+            else if line.starts_with("<mass ")
+            {
+                links.last_mut().unwrap().mass = attribute(line, "value").unwrap().parse().unwrap();
+            }
+            else if line.starts_with("<joint ")
+            {
+                joints.push((attribute(line, "name").unwrap().to_string(), ParsedJoint{
+                    kind: attribute(line, "type").unwrap().to_string(),
+                    parent: String::new(),
+                    child: String::new(),
+                    limits: None,
+                }));
+            }
+            else if line.starts_with("<parent ")
+            {
+                joints.last_mut().unwrap().1.parent = attribute(line, "link").unwrap().to_string();
+            }
+            else if line.starts_with("<child ")
+            {
+                joints.last_mut().unwrap().1.child = attribute(line, "link").unwrap().to_string();
+            }
+            else if line.starts_with("<limit ")
+            {
+                let limit = |name| attribute(line, name).unwrap().parse::<f32>().unwrap();
+                joints.last_mut().unwrap().1.limits = Some((limit("lower"), limit("upper")));
+            }
+        }
+        (links, joints)
+    }
+
+    #[test]
+    fn urdf_has_a_tree_of_arms_and_motors()
+    {
+        let assembly = Assembly::new();
+        let (links, joints) = parse_urdf(&urdf(&get_links(&assembly), &model(&assembly)));
+
+        let names = links.iter().map(|link| link.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!("base_link", "arm_0", "motor_0", "arm_1", "motor_1", "arm_2", "motor_2"));
+
+        //Every link but the root is the child of exactly one joint
+        assert_eq!(joints.len(), links.len() - 1);
+        for i in 0..3
+        {
+            let arm = format!("arm_{}", i);
+            let motor = format!("motor_{}", i);
+            let parent_of = |child: &str| {
+                let found = joints.iter().filter(|joint| joint.1.child == child).collect::<Vec<_>>();
+                assert_eq!(found.len(), 1, "{}", child);
+                found[0].1.parent.clone()
+            };
+            assert_eq!(parent_of(&arm), "base_link");
+            assert_eq!(parent_of(&motor), arm);
+        }
+    }
+
+    #[test]
+    fn only_the_tail_motor_tilts()
+    {
+        let assembly = Assembly::new();
+        let (_, joints) = parse_urdf(&urdf(&get_links(&assembly), &model(&assembly)));
+
+        let range = assembly.servo_mount.servo_tilt_range.to_radians();
+        for (name, joint) in &joints
+        {
+            if name == "tail_tilt"
+            {
+                assert_eq!(joint.kind, "revolute");
+                assert_eq!(joint.child, "motor_0");
+                let (lower, upper) = joint.limits.unwrap();
+                assert!((lower + range).abs() < 1e-5, "{}", lower);
+                assert!((upper - range).abs() < 1e-5, "{}", upper);
+            }
+            else
+            {
+                assert_eq!(joint.kind, "fixed", "{}", name);
+                assert!(joint.limits.is_none(), "{}", name);
+            }
+        }
+        assert!(joints.iter().any(|(name, _)| name == "tail_tilt"));
+    }
+
+    #[test]
+    fn link_inertials_add_up_to_the_mass_model()
+    {
+        let assembly = Assembly::new();
+        let model = model(&assembly);
+        let links = get_links(&assembly);
+        let (parsed, _) = parse_urdf(&urdf(&links, &model));
+
+        //The inertials are in the frames of the links, in SI units
+        let total = parsed.iter().map(|link| link.mass).sum::<f32>();
+        let moment = parsed.iter().zip(links.iter())
+            .fold(Vec3::new(0., 0., 0.), |acc, (parsed, link)| {
+                acc + (parsed.center_of_mass + model_rotation() * link.origin * 1e-3) * parsed.mass
+            });
+
+        let expected = InertiaReport::new(&model).rotated(&model_rotation());
+        assert!((total - expected.mass).abs() < 1e-5, "{} != {}", total, expected.mass);
+        let center_of_mass = moment / total;
+        assert!(
+            (center_of_mass - expected.center_of_mass).norm() < 1e-5,
+            "{:?} != {:?}", center_of_mass, expected.center_of_mass
+        );
+
+        //The SDF model has the same masses
+        let sdf_total = sdf(&links, &model).lines()
+            .filter_map(|line| content(line, "mass"))
+            .map(|mass| mass.parse::<f32>().unwrap())
+            .sum::<f32>();
+        assert!((sdf_total - expected.mass).abs() < 1e-5, "{} != {}", sdf_total, expected.mass);
+    }
+
+    #[test]
+    fn models_have_x_pointing_forward()
+    {
+        let assembly = Assembly::new();
+        let links = get_links(&assembly);
+        let sdf = sdf(&links, &model(&assembly));
+
+        let mut lines = sdf.lines().map(|line| line.trim());
+        lines.find(|line| line.starts_with("<link name=\"motor_0\""));
+        let pose = numbers(content(lines.next().unwrap(), "pose").unwrap());
+        //The tail motor is behind the center of the body
+        assert!(pose[0] < 0., "{:?}", pose);
+        assert!(pose[1].abs() < 1e-6, "{:?}", pose);
+    }
+
+    #[test]
+    fn export_writes_the_models_and_their_meshes()
+    {
+        //Unique to the process so that test runs at the same time don't
+        //remove each others files
+        let directory = env::temp_dir()
+            .join(format!("tricopter_simulation_export_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let assembly = Assembly::new();
+
+        export(&assembly, &directory).unwrap();
+
+        let urdf = fs::read_to_string(directory.join("tricopter.urdf")).unwrap();
+        let sdf = fs::read_to_string(directory.join("model.sdf")).unwrap();
+        for link in get_links(&assembly)
+        {
+            let mesh = directory.join(mesh_filename(&link));
+            assert!(fs::metadata(&mesh).unwrap().len() > 0, "{:?}", mesh);
+            assert!(urdf.contains(&mesh_filename(&link)));
+            assert!(sdf.contains(&format!("<uri>{}</uri>", mesh_filename(&link))));
+        }
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,500 @@
+/*!
+  Generation of families of frames from a sweep over the parameters.
+
+  A sweep file lists values for parameters in the same `name = value` form as
+  a parameter file. Every combination of the listed values is a variant:
+
+  ```text
+  # The parts that are generated, all printed parts if left out
+  parts = body_bottom, body_top
+  # A range from 65 to 95 in steps of 10
+  body.radius = 65..95 step 10
+  # A list of values
+  material = Petg, Abs
+  ```
+
+  Variants that set several parameters together, like the dimensions of a
+  camera, are listed in sections. Every section is combined with every
+  combination of the values outside the sections:
+
+  ```text
+  [micro]
+  body.camera.width = 19
+  body.camera.mass = 6
+  ```
+
+  Each variant is checked like the design is before it is generated, and the
+  selected parts are written in print orientation to a directory named after
+  the variant.
+*/
+
+use scad::*;
+
+use assembly::Assembly;
+use evaluate::{EvalError, Solid};
+use interference;
+use parameters::{set_parameter, ParameterError, ParameterSet};
+use scad_modules;
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+//Size in mm of the cells used when looking for overlapping parts
+const INTERFERENCE_RESOLUTION: f32 = 1.;
+//Detail of the generated code, like in out.scad
+const DETAIL: i32 = 20;
+
+#[derive(Debug)]
+pub enum SweepError
+{
+    Io(io::Error),
+    Parameter(ParameterError),
+    Eval(EvalError),
+    //A line of a sweep file that can't be read, with the line number and
+    //what is wrong with it
+    Syntax(usize, String),
+    UnknownPart(String),
+}
+
+impl fmt::Display for SweepError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self
+        {
+            SweepError::Io(ref e) => write!(f, "{}", e),
+            SweepError::Parameter(ref e) => write!(f, "{}", e),
+            SweepError::Eval(ref e) => write!(f, "{}", e),
+            SweepError::Syntax(line, ref message) => write!(f, "Line {}: {}", line, message),
+            SweepError::UnknownPart(ref name) => write!(f, "There is no printed part called {}", name),
+        }
+    }
+}
+
+impl From<io::Error> for SweepError
+{
+    fn from(error: io::Error) -> SweepError
+    {
+        SweepError::Io(error)
+    }
+}
+
+impl From<ParameterError> for SweepError
+{
+    fn from(error: ParameterError) -> SweepError
+    {
+        SweepError::Parameter(error)
+    }
+}
+
+impl From<EvalError> for SweepError
+{
+    fn from(error: EvalError) -> SweepError
+    {
+        SweepError::Eval(error)
+    }
+}
+
+/**
+  A parameter and the values that it is swept over
+*/
+#[derive(Clone, PartialEq, Debug)]
+pub struct Axis
+{
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+/**
+  A set of parameters that are changed together, from a section of the
+  sweep file
+*/
+#[derive(Clone, PartialEq, Debug)]
+pub struct Section
+{
+    pub name: String,
+    pub values: Vec<(String, String)>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Sweep
+{
+    //None if all the printed parts are generated
+    pub parts: Option<Vec<String>>,
+    pub axes: Vec<Axis>,
+    pub sections: Vec<Section>,
+}
+
+/**
+  One combination of values from the sweep
+*/
+#[derive(Clone, PartialEq, Debug)]
+pub struct Variant
+{
+    pub name: String,
+    pub values: Vec<(String, String)>,
+}
+
+/**
+  Parses `start..end` or `start..end step size` into the values from start
+  up to and including end. None if the text is not a range
+*/
+fn parse_range(text: &str) -> Option<Result<Vec<String>, String>>
+{
+    let mut range = text.splitn(2, "..");
+    let start = range.next()?.trim();
+    let rest = range.next()?;
+
+    let mut rest = rest.splitn(2, "step");
+    let end = rest.next()?.trim();
+    let step = rest.next().map(|step| step.trim()).unwrap_or("1");
+
+    let numbers = (start.parse::<f32>(), end.parse::<f32>(), step.parse::<f32>());
+    let (start, end, step) = match numbers
+    {
+        (Ok(start), Ok(end), Ok(step)) => (start, end, step),
+        _ => return Some(Err(format!("{} is not a range of numbers", text))),
+    };
+    if step <= 0. || end < start
+    {
+        return Some(Err(format!("{} has no values", text)));
+    }
+
+    //The values are computed from the start so that the steps don't add up
+    //rounding errors, and rounded so that 0.1 steps print as 0.1
+    let count = ((end - start) / step + 1e-3).floor() as usize + 1;
+    Some(Ok((0..count)
+        .map(|i| ((start + i as f32 * step) * 1e4).round() / 1e4)
+        .map(|value| value.to_string())
+        .collect()))
+}
+
+fn parse_list(text: &str) -> Vec<String>
+{
+    text.split(',').map(|value| value.trim().to_string()).collect()
+}
+
+impl Sweep
+{
+    /**
+      Parses a sweep file. Empty lines and lines starting with # are ignored
+    */
+    pub fn parse(text: &str) -> Result<Sweep, SweepError>
+    {
+        let mut result = Sweep{parts: None, axes: vec!(), sections: vec!()};
+
+        for (i, line) in text.lines().enumerate()
+        {
+            let line = line.trim();
+            let syntax_error = |message: &str| SweepError::Syntax(i + 1, message.to_string());
+            if line.is_empty() || line.starts_with('#')
+            {
+                continue;
+            }
+
+            if line.starts_with('[')
+            {
+                if !line.ends_with(']') || line.len() < 3
+                {
+                    return Err(syntax_error("a section name has to be written as [name]"));
+                }
+                let name = line[1..line.len() - 1].trim().to_string();
+                result.sections.push(Section{name, values: vec!()});
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=');
+            let (name, value) = match (parts.next(), parts.next())
+            {
+                (Some(name), Some(value)) => (name.trim().to_string(), value.trim()),
+                _ => return Err(syntax_error("the line is not on the form name = values")),
+            };
+
+            if let Some(section) = result.sections.last_mut()
+            {
+                section.values.push((name, value.to_string()));
+            }
+            else if name == "parts"
+            {
+                result.parts = Some(parse_list(value));
+            }
+            else
+            {
+                let values = match parse_range(value)
+                {
+                    Some(values) => values.map_err(|message| syntax_error(&message))?,
+                    None => parse_list(value),
+                };
+                result.axes.push(Axis{name, values});
+            }
+        }
+        Ok(result)
+    }
+
+    /**
+      Returns every combination of the values of the sweep. The last axis
+      changes fastest. A sweep without any values has the default variant
+    */
+    pub fn variants(&self) -> Vec<Variant>
+    {
+        if self.axes.is_empty() && self.sections.is_empty()
+        {
+            return vec!(Variant{name: "default".to_string(), values: vec!()});
+        }
+
+        let sections = if self.sections.is_empty()
+        {
+            vec!(Variant{name: String::new(), values: vec!()})
+        }
+        else
+        {
+            self.sections.iter()
+                .map(|section| Variant{name: section.name.clone(), values: section.values.clone()})
+                .collect()
+        };
+
+        self.axes.iter().fold(sections, |variants, axis| {
+            //The last part of the name is enough to tell the parameters apart
+            //in the names of the variants
+            let short_name = axis.name.rsplit('.').next().unwrap_or(axis.name.as_str());
+
+            variants.iter()
+                .flat_map(|variant| axis.values.iter().map(move |value| {
+                    let part = format!("{}-{}", short_name, value);
+                    let name = if variant.name.is_empty()
+                    {
+                        part
+                    }
+                    else
+                    {
+                        format!("{}_{}", variant.name, part)
+                    };
+
+                    let mut values = variant.values.clone();
+                    values.push((axis.name.clone(), value.clone()));
+                    Variant{name, values}
+                }))
+                .collect()
+        })
+    }
+}
+
+impl Variant
+{
+    /**
+      Returns the default assembly with the values of the variant
+    */
+    pub fn assembly(&self) -> Result<Assembly, ParameterError>
+    {
+        let mut result = Assembly::new();
+        for &(ref name, ref value) in &self.values
+        {
+            set_parameter(&mut result, name, value)?;
+        }
+        Ok(result)
+    }
+}
+
+/**
+  Returns the reasons that the selected parts don't fit on the print bed in
+  their print orientation. Parts may be turned around the z axis to fit
+*/
+pub fn bed_fit_problems(assembly: &Assembly, parts: &[String]) -> Result<Vec<String>, EvalError>
+{
+    let mut result = vec!();
+    for part in assembly.get_print_parts().iter().filter(|part| parts.contains(&part.name))
+    {
+        let size = Solid::from_object(&part.oriented())?.bounds().size();
+
+        let fits_flat = size.x <= assembly.bed_width && size.y <= assembly.bed_depth;
+        let fits_turned = size.y <= assembly.bed_width && size.x <= assembly.bed_depth;
+        if !(fits_flat || fits_turned) || size.z > assembly.bed_height
+        {
+            result.push(format!(
+                "{} is {:.1} x {:.1} x {:.1} mm which doesn't fit on the {} x {} x {} mm bed",
+                part.name, size.x, size.y, size.z,
+                assembly.bed_width, assembly.bed_depth, assembly.bed_height
+            ));
+        }
+    }
+    Ok(result)
+}
+
+/**
+  Returns the reasons that the assembly can't be built: parts that are
+  fastened together that don't match and parts that overlap
+*/
+pub fn validation_problems(assembly: &Assembly) -> Result<Vec<String>, EvalError>
+{
+    let mut result = ::mating::check_all(&assembly.body.mating_interfaces())?.iter()
+        .map(|disagreement| disagreement.to_string())
+        .collect::<Vec<_>>();
+
+    result.extend(
+        interference::check_assembly(assembly, INTERFERENCE_RESOLUTION)?.iter()
+            .map(|interference| interference.to_string())
+    );
+    Ok(result)
+}
+
+/**
+  The outcome of generating a variant
+*/
+#[derive(Clone, PartialEq, Debug)]
+pub struct VariantResult
+{
+    pub name: String,
+    pub validation: Vec<String>,
+    pub bed_fit: Vec<String>,
+}
+
+impl fmt::Display for VariantResult
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        if self.validation.is_empty() && self.bed_fit.is_empty()
+        {
+            return writeln!(f, "{}: ok", self.name);
+        }
+
+        writeln!(f, "{}:", self.name)?;
+        for problem in &self.validation
+        {
+            writeln!(f, "    invalid: {}", problem)?;
+        }
+        for problem in &self.bed_fit
+        {
+            writeln!(f, "    too large: {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/**
+  Writes the code of a part placed on the print bed
+*/
+fn write_part(path: &Path, object: ScadObject) -> Result<(), SweepError>
+{
+    let mut file = ScadFile::new();
+    file.set_detail(DETAIL);
+    file.add_object(object);
+
+    let code = scad_modules::with_modules(&file.get_code()).map_err(EvalError::from)?;
+    fs::write(path, code)?;
+    Ok(())
+}
+
+/**
+  Checks every variant of the sweep and writes its parts and parameters to
+  a directory named after it in `directory`. Nothing is written if the sweep
+  has a parameter or part that doesn't exist
+*/
+pub fn generate(sweep: &Sweep, directory: &Path) -> Result<Vec<VariantResult>, SweepError>
+{
+    let variants = sweep.variants();
+    let assemblies = variants.iter()
+        .map(|variant| variant.assembly())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let all_parts = Assembly::new().get_print_parts().into_iter()
+        .map(|part| part.name)
+        .collect::<Vec<_>>();
+    let parts = sweep.parts.clone().unwrap_or_else(|| all_parts.clone());
+    if let Some(unknown) = parts.iter().find(|part| !all_parts.contains(part))
+    {
+        return Err(SweepError::UnknownPart(unknown.clone()));
+    }
+
+    let mut result = vec!();
+    for (variant, assembly) in variants.iter().zip(assemblies.iter())
+    {
+        let variant_directory = directory.join(&variant.name);
+        fs::create_dir_all(&variant_directory)?;
+        fs::write(variant_directory.join("parameters.txt"), ParameterSet::of(assembly).to_text())?;
+
+        for part in assembly.get_print_parts().iter().filter(|part| parts.contains(&part.name))
+        {
+            write_part(&variant_directory.join(&part.name).with_extension("scad"), part.on_bed()?)?;
+        }
+
+        result.push(VariantResult{
+            name: variant.name.clone(),
+            validation: validation_problems(assembly)?,
+            bed_fit: bed_fit_problems(assembly, &parts)?,
+        });
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    use assembly::Material;
+
+    #[test]
+    fn ranges_include_both_ends()
+    {
+        let sweep = Sweep::parse("body.radius = 65..95 step 10\nnozzle_width = 0.2..0.4 step 0.1").unwrap();
+        assert_eq!(sweep.axes[0].values, vec!("65", "75", "85", "95"));
+        assert_eq!(sweep.axes[1].values, vec!("0.2", "0.3", "0.4"));
+    }
+
+    #[test]
+    fn variants_are_all_combinations()
+    {
+        let text = "parts = body_bottom\n\
+                    # Comment\n\
+                    material = Petg, Abs\n\
+                    [micro]\n\
+                    body.camera.width = 19\n\
+                    [nano]\n\
+                    body.camera.width = 14\n\
+                    body.camera.mass = 4\n";
+        let sweep = Sweep::parse(text).unwrap();
+        assert_eq!(sweep.parts, Some(vec!("body_bottom".to_string())));
+
+        let names = sweep.variants().into_iter().map(|variant| variant.name).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec!("micro_material-Petg", "micro_material-Abs", "nano_material-Petg", "nano_material-Abs")
+        );
+
+        let assembly = sweep.variants()[3].assembly().unwrap();
+        assert_eq!(assembly.body.camera.width, 14.);
+        assert_eq!(assembly.body.camera.mass, 4.);
+        assert_eq!(assembly.material, Material::Abs);
+    }
+
+    #[test]
+    fn bad_lines_are_errors()
+    {
+        match Sweep::parse("body.radius = 65..60")
+        {
+            Err(SweepError::Syntax(1, _)) => {}
+            other => panic!("{:?}", other),
+        }
+        match Sweep::parse("material = Abs\n[camera")
+        {
+            Err(SweepError::Syntax(2, _)) => {}
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn parts_have_to_fit_on_the_bed()
+    {
+        let mut assembly = Assembly::new();
+        let parts = vec!("body_bottom".to_string());
+        assert!(bed_fit_problems(&assembly, &parts).unwrap().is_empty());
+
+        assembly.bed_width = 50.;
+        assembly.bed_depth = 50.;
+        let problems = bed_fit_problems(&assembly, &parts).unwrap();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("body_bottom"));
+    }
+}
@@ -0,0 +1,394 @@
+/*!
+  Solving the parameters of the frame from high level targets.
+
+  A target file has targets like the distance between the motors and the
+  battery that the frame is built for, and parameters that are fixed:
+
+  ```text
+  motor_distance = 300
+  prop_size = 5
+  flight_controller = 30.5
+  battery = 3S 1300
+  # Parameters are kept at the value they are given
+  body.front_section_width = 40
+  ```
+
+  The targets set the parameters that they correspond to directly. The
+  widths of the body are then made wide enough for the battery and the
+  flight controller, keeping the steps between them that the default body
+  has. A target that needs a fixed parameter to change, or a solved frame
+  that fails the checks that the design has to pass, is a conflict.
+*/
+
+use assembly::Assembly;
+use evaluate::EvalError;
+use mating;
+use parameters::{set_parameter, ParameterError, ParameterSet};
+use props::PropClearance;
+use Battery;
+
+use std::fmt;
+
+const MM_PER_INCH: f32 = 25.4;
+//Difference between the widths of the default body. The front section is
+//narrower than the arm sections which are narrower than the center
+const WIDTH_STEP: f32 = 6.;
+
+#[derive(Debug)]
+pub enum TargetError
+{
+    Parameter(ParameterError),
+    Eval(EvalError),
+    //The name of a target and the value that could not be read
+    InvalidTarget(String, String),
+}
+
+impl fmt::Display for TargetError
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        match *self
+        {
+            TargetError::Parameter(ref e) => write!(f, "{}", e),
+            TargetError::Eval(ref e) => write!(f, "{}", e),
+            TargetError::InvalidTarget(ref name, ref value) => {
+                write!(f, "{} is not a valid value for the target {}", value, name)
+            }
+        }
+    }
+}
+
+impl From<ParameterError> for TargetError
+{
+    fn from(error: ParameterError) -> TargetError
+    {
+        TargetError::Parameter(error)
+    }
+}
+
+impl From<EvalError> for TargetError
+{
+    fn from(error: EvalError) -> TargetError
+    {
+        TargetError::Eval(error)
+    }
+}
+
+pub struct Targets
+{
+    //Distance in mm between the axes of neighbouring motors
+    pub motor_distance: Option<f32>,
+    //Diameter of the propellers in inches
+    pub prop_size: Option<f32>,
+    //Distance in mm between the mounting holes of the flight controller
+    pub flight_controller: Option<f32>,
+    pub battery: Option<Battery>,
+    //Parameters that keep their values
+    pub fixed: ParameterSet,
+}
+
+/**
+  A target that can't be met
+*/
+#[derive(Clone, PartialEq, Debug)]
+pub struct Conflict
+{
+    pub target: String,
+    pub reason: String,
+}
+
+impl fmt::Display for Conflict
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        write!(f, "{}: {}", self.target, self.reason)
+    }
+}
+
+fn parse_battery(value: &str) -> Option<Battery>
+{
+    let mut words = value.split_whitespace();
+    let cells = words.next()?.trim_end_matches(|c| c == 'S' || c == 's').parse::<u32>().ok()?;
+    let capacity = words.next()?.trim_end_matches("mAh").parse::<u32>().ok()?;
+
+    Battery::known().into_iter()
+        .find(|battery| battery.cell_count == cells && battery.capacity == capacity)
+}
+
+impl Targets
+{
+    /**
+      Parses a target file. Lines that are not targets are fixed parameters
+    */
+    pub fn parse(text: &str) -> Result<Targets, TargetError>
+    {
+        let parameters = ParameterSet::parse(text)?;
+
+        let mut result = Targets{
+            motor_distance: None,
+            prop_size: None,
+            flight_controller: None,
+            battery: None,
+            fixed: ParameterSet{values: vec!()},
+        };
+
+        for (name, value) in parameters.values
+        {
+            let invalid = || TargetError::InvalidTarget(name.clone(), value.clone());
+            let number = || value.parse::<f32>().ok().filter(|number| *number > 0.);
+
+            match name.as_str()
+            {
+                "motor_distance" => result.motor_distance = Some(number().ok_or_else(invalid)?),
+                "prop_size" => result.prop_size = Some(number().ok_or_else(invalid)?),
+                "flight_controller" => result.flight_controller = Some(number().ok_or_else(invalid)?),
+                "battery" => result.battery = Some(parse_battery(&value).ok_or_else(invalid)?),
+                _ => result.fixed.values.push((name, value)),
+            }
+        }
+        Ok(result)
+    }
+}
+
+/**
+  The parameters that the targets are solved into
+*/
+pub struct Solution
+{
+    pub assembly: Assembly,
+    pub conflicts: Vec<Conflict>,
+}
+
+struct Solver<'a>
+{
+    assembly: Assembly,
+    fixed: &'a ParameterSet,
+    conflicts: Vec<Conflict>,
+}
+
+impl<'a> Solver<'a>
+{
+    fn conflict(&mut self, target: &str, reason: String)
+    {
+        self.conflicts.push(Conflict{target: target.to_string(), reason});
+    }
+
+    /**
+      Sets a parameter that a target decides unless it is fixed to another
+      value
+    */
+    fn set(&mut self, target: &str, name: &str, value: f32) -> Result<(), ParameterError>
+    {
+        match self.fixed.get(name).map(|fixed| fixed.parse::<f32>())
+        {
+            Some(Ok(fixed)) if (fixed - value).abs() > 1e-3 => {
+                let reason = format!("needs {} to be {:.2} but it is fixed at {}", name, value, fixed);
+                self.conflict(target, reason);
+                Ok(())
+            }
+            _ => set_parameter(&mut self.assembly, name, &value.to_string()),
+        }
+    }
+
+    /**
+      Widens a parameter to at least the minimum unless it is fixed
+    */
+    fn require(&mut self, target: &str, name: &str, current: f32, minimum: f32)
+        -> Result<(), ParameterError>
+    {
+        if current >= minimum
+        {
+            return Ok(());
+        }
+
+        if self.fixed.get(name).is_some()
+        {
+            let reason = format!("needs {} to be at least {:.2} but it is fixed at {}", name, minimum, current);
+            self.conflict(target, reason);
+            Ok(())
+        }
+        else
+        {
+            set_parameter(&mut self.assembly, name, &minimum.to_string())
+        }
+    }
+
+    fn solve_battery(&mut self, battery: &Battery) -> Result<(), ParameterError>
+    {
+        //The strap slots on both sides of the battery need material outside
+        //of them in the front section
+        let body = &self.assembly.body;
+        let minimum = battery.width
+            + 2. * (body.battery_strap_clearance + body.battery_strap_slot_width + body.min_screw_wall);
+
+        for (name, value) in [
+            ("body.battery.cell_count", battery.cell_count.to_string()),
+            ("body.battery.capacity", battery.capacity.to_string()),
+            ("body.battery.length", battery.length.to_string()),
+            ("body.battery.width", battery.width.to_string()),
+            ("body.battery.height", battery.height.to_string()),
+            ("body.battery.mass", battery.mass.to_string()),
+        ]
+        {
+            set_parameter(&mut self.assembly, name, &value)?;
+        }
+
+        let current = self.assembly.body.front_section_width;
+        self.require("battery", "body.front_section_width", current, minimum)
+    }
+
+    fn solve_flight_controller(&mut self, hole_distance: f32) -> Result<(), ParameterError>
+    {
+        self.set("flight_controller", "body.flight_controller_hole_distance", hole_distance)?;
+        self.set("flight_controller", "flight_controller.hole_distance", hole_distance)?;
+
+        //The mid section narrows from the center to the front section, so
+        //the holes fit if both ends are wide enough for them
+        let body = &self.assembly.body;
+        let holes = body.flight_controller_mount().hole_pattern();
+        let minimum = body.flight_controller_hole_distance + holes.diameter + 2. * body.min_screw_wall;
+
+        let (front, center) = (body.front_section_width, body.center_width);
+        self.require("flight_controller", "body.front_section_width", front, minimum)?;
+        self.require("flight_controller", "body.center_width", center, minimum)
+    }
+
+    /**
+      Keeps the steps between the widths of the body that the default body
+      has when the front section has been widened
+    */
+    fn solve_widths(&mut self) -> Result<(), ParameterError>
+    {
+        let body = &self.assembly.body;
+        let (front, inner) = (body.front_section_width, body.inner_width);
+        self.require("body", "body.inner_width", inner, front + WIDTH_STEP)?;
+
+        let body = &self.assembly.body;
+        let (inner, center) = (body.inner_width, body.center_width);
+        self.require("body", "body.center_width", center, inner + WIDTH_STEP)
+    }
+
+    /**
+      Checks the solved frame like the design is checked before it is
+      generated
+    */
+    fn check(&mut self, targets: &Targets) -> Result<(), EvalError>
+    {
+        for disagreement in mating::check_all(&self.assembly.body.mating_interfaces())?
+        {
+            self.conflict("body", disagreement.to_string());
+        }
+
+        if let Some(size) = targets.prop_size
+        {
+            let clearance = PropClearance::new(&self.assembly)?;
+            if let Some(limit) = clearance.binding_limit()
+            {
+                if limit.max_diameter < self.assembly.prop_diameter
+                {
+                    let reason = format!(
+                        "{} inch propellers are {:.1} mm but {} only leaves room for {:.1} mm",
+                        size, self.assembly.prop_diameter, limit.obstacle, limit.max_diameter
+                    );
+                    self.conflict("prop_size", reason);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/**
+  Solves the parameters of the default assembly from the targets
+*/
+pub fn solve(targets: &Targets) -> Result<Solution, TargetError>
+{
+    let mut assembly = Assembly::new();
+    targets.fixed.apply(&mut assembly)?;
+
+    let mut solver = Solver{assembly, fixed: &targets.fixed, conflicts: vec!()};
+
+    if let Some(distance) = targets.motor_distance
+    {
+        //The motors are at the corners of an equilateral triangle
+        let arm_length = distance / 3f32.sqrt();
+        solver.set("motor_distance", "arm_length", arm_length)?;
+        solver.set("motor_distance", "back_arm_length", arm_length)?;
+    }
+    if let Some(size) = targets.prop_size
+    {
+        solver.set("prop_size", "prop_diameter", size * MM_PER_INCH)?;
+    }
+    if let Some(ref battery) = targets.battery
+    {
+        solver.solve_battery(battery)?;
+    }
+    if let Some(hole_distance) = targets.flight_controller
+    {
+        solver.solve_flight_controller(hole_distance)?;
+    }
+    solver.solve_widths()?;
+    solver.check(targets)?;
+
+    Ok(Solution{assembly: solver.assembly, conflicts: solver.conflicts})
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn targets_set_their_parameters()
+    {
+        let text = "motor_distance = 300\nflight_controller = 20\nbattery = 4S 1300\n";
+        let solution = solve(&Targets::parse(text).unwrap()).unwrap();
+        let assembly = &solution.assembly;
+
+        assert!(solution.conflicts.is_empty(), "{:?}", solution.conflicts);
+        let positions = assembly.motor_positions();
+        assert!(((positions[1] - positions[2]).norm() - 300.).abs() < 0.01);
+        assert!(((positions[0] - positions[1]).norm() - 300.).abs() < 0.01);
+        assert_eq!(assembly.body.flight_controller_hole_distance, 20.);
+        assert_eq!(assembly.flight_controller.hole_distance, 20.);
+        assert_eq!(assembly.body.battery.cell_count, 4);
+        assert_eq!(assembly.body.battery.width, Battery::lipo_4s_1300().width);
+    }
+
+    #[test]
+    fn wide_batteries_widen_the_body()
+    {
+        let mut battery = Battery::lipo_4s_1300();
+        battery.width = 50.;
+        let mut targets = Targets::parse("").unwrap();
+        targets.battery = Some(battery);
+
+        let body = solve(&targets).unwrap().assembly.body;
+        assert!(body.front_section_width >= 60.);
+        assert!(body.inner_width >= body.front_section_width + WIDTH_STEP);
+        assert!(body.center_width >= body.inner_width + WIDTH_STEP);
+    }
+
+    #[test]
+    fn fixed_parameters_conflict_with_targets()
+    {
+        let text = "battery = 4S 1300\nbody.front_section_width = 40\narm_length = 100\nmotor_distance = 300";
+        let solution = solve(&Targets::parse(text).unwrap()).unwrap();
+
+        let targets = solution.conflicts.iter().map(|conflict| conflict.target.as_str()).collect::<Vec<_>>();
+        assert!(targets.contains(&"battery"), "{:?}", solution.conflicts);
+        assert!(targets.contains(&"motor_distance"), "{:?}", solution.conflicts);
+        assert_eq!(solution.assembly.body.front_section_width, 40.);
+        assert_eq!(solution.assembly.arm_length, 100.);
+    }
+
+    #[test]
+    fn unknown_batteries_are_errors()
+    {
+        match Targets::parse("battery = 6S 5000")
+        {
+            Err(TargetError::InvalidTarget(ref name, _)) => assert_eq!(name, "battery"),
+            other => panic!("{:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,263 @@
+/*!
+  Detection of walls that are too thin to print.
+
+  The surface of every printed part is meshed and a ray is cast into the part
+  from the middle of every triangle, straight against the surface normal.
+  The distance that the ray travels before leaving the part again is the
+  thickness of the wall at that point. Thin points that are close to each
+  other are grouped into regions.
+*/
+
+use assembly::{Assembly, AssemblyPart, PartKind};
+use evaluate::{EvalError, Solid, Vec3};
+use mesh::{self, SurfaceRegions};
+
+use std::fmt;
+
+//Largest size in mm of the cells used to mesh the parts
+const MAX_RESOLUTION: f32 = 0.5;
+//Size of the mesh cells relative to the thinnest allowed wall. Cells have to
+//be well below half of the wall to put a surface on both of its sides
+const RESOLUTION_PER_THICKNESS: f32 = 0.4;
+//Smallest step taken when following a ray through a part
+const MIN_STEP: f32 = 0.02;
+//Cosine of the largest angle between the surfaces on both sides of a wall
+const MIN_EXIT_ALIGNMENT: f32 = 0.7;
+
+/**
+  A part of the surface of a part where the wall is thinner than allowed
+*/
+#[derive(Clone, PartialEq, Debug)]
+pub struct ThinRegion
+{
+    pub part: String,
+    //Position of the thinnest point of the region
+    pub position: Vec3,
+    pub thickness: f32,
+    //Surface area in mm^2 that is too thin
+    pub area: f32,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct WallThicknessReport
+{
+    pub min_thickness: f32,
+    pub regions: Vec<ThinRegion>,
+}
+
+/**
+  Returns the distance from a point on the surface of the solid through the
+  solid along the direction. Gives up after `max_distance`. Returns None if
+  the ray doesn't enter the solid, which happens at sharp edges where the
+  normal is not well defined. `resolution` is the size of the mesh cells
+  that the start point comes from
+*/
+fn ray_thickness(
+    solid: &Solid,
+    start: &Vec3,
+    direction: &Vec3,
+    max_distance: f32,
+    resolution: f32,
+) -> Option<f32>
+{
+    let mut travelled = 0.;
+    let mut inside = false;
+    while travelled < max_distance
+    {
+        let distance = solid.distance(&(start + direction * travelled));
+        if distance < 0.
+        {
+            inside = true;
+            travelled += (-distance).max(MIN_STEP);
+        }
+        else if inside
+        {
+            return Some(travelled);
+        }
+        else if travelled > resolution
+        {
+            return None;
+        }
+        else
+        {
+            travelled += MIN_STEP;
+        }
+    }
+    Some(max_distance)
+}
+
+/**
+  Returns the regions of a solid that are thinner than `min_thickness`
+*/
+pub fn find_thin_regions(name: &str, solid: &Solid, min_thickness: f32) -> Vec<ThinRegion>
+{
+    let mut regions = SurfaceRegions::new(|a: &f32, b: &f32| a < b);
+
+    let resolution = (min_thickness * RESOLUTION_PER_THICKNESS).min(MAX_RESOLUTION);
+    for triangle in mesh::triangulate(solid, resolution)
+    {
+        let [a, b, c] = triangle.vertices;
+        let center = (a + b + c) / 3.;
+        let normal = solid.normal(&center);
+        if normal.norm() == 0.
+        {
+            continue;
+        }
+
+        //Move the point onto the surface before following the normal inwards
+        let start = center - normal * solid.distance(&center);
+        let thickness = match ray_thickness(solid, &start, &-normal, min_thickness, resolution)
+        {
+            Some(thickness) if thickness < min_thickness => thickness,
+            _ => continue,
+        };
+
+        //Rays that start next to an edge can leave through the neighbouring
+        //face. Only walls where the ray leaves through the opposite side count
+        let exit_normal = solid.normal(&(start - normal * thickness));
+        if exit_normal.dot(&-normal) < MIN_EXIT_ALIGNMENT
+        {
+            continue;
+        }
+
+        regions.add(start, triangle.area(), thickness);
+    }
+
+    let mut result = regions.into_regions().into_iter()
+        .map(|region| ThinRegion{
+            part: name.to_string(),
+            position: region.position,
+            thickness: region.worst,
+            area: region.area,
+        })
+        .collect::<Vec<_>>();
+    result.sort_by(|a, b| a.thickness.total_cmp(&b.thickness));
+    result
+}
+
+impl WallThicknessReport
+{
+    /**
+      Checks the printed parts of the assembly, including the side plates.
+      Walls must be at least `Assembly::min_wall_lines` extrusions wide
+    */
+    pub fn new(assembly: &Assembly) -> Result<WallThicknessReport, EvalError>
+    {
+        let min_thickness = assembly.min_wall_thickness();
+
+        let mut parts = assembly.get_parts();
+        parts.append(&mut assembly.get_side_plates());
+
+        let printed = parts.iter().filter(|part| match part.kind
+        {
+            PartKind::Printed(_) => true,
+            PartKind::Component(_) => false,
+        });
+
+        let mut regions = vec!();
+        for part in printed
+        {
+            regions.append(&mut WallThicknessReport::check_part(part, min_thickness)?);
+        }
+
+        Ok(WallThicknessReport{min_thickness, regions})
+    }
+
+    fn check_part(part: &AssemblyPart, min_thickness: f32) -> Result<Vec<ThinRegion>, EvalError>
+    {
+        let solid = Solid::from_object(&part.object)?;
+        Ok(find_thin_regions(&part.name, &solid, min_thickness))
+    }
+}
+
+impl fmt::Display for WallThicknessReport
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result
+    {
+        writeln!(f, "Minimum wall thickness: {:.2} mm", self.min_thickness)?;
+        if self.regions.is_empty()
+        {
+            return writeln!(f, "No walls are too thin");
+        }
+
+        writeln!(f)?;
+        writeln!(
+            f,
+            "{:<18} {:>26} {:>15} {:>12}",
+            "part", "position (mm)", "thickness (mm)", "area (mm^2)"
+        )?;
+        for region in &self.regions
+        {
+            let p = region.position;
+            writeln!(
+                f,
+                "{:<18} {:>8.1} {:>8.1} {:>8.1} {:>15.2} {:>12.1}",
+                region.part, p.x, p.y, p.z, region.thickness, region.area
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn solid(code: &str) -> Solid
+    {
+        Solid::from_code(code).unwrap()
+    }
+
+    #[test]
+    fn thin_walls_are_found()
+    {
+        let plate = solid("cube([20, 20, 0.8]);");
+        let regions = find_thin_regions("plate", &plate, 1.2);
+
+        assert!(!regions.is_empty());
+        for region in &regions
+        {
+            assert_eq!(region.part, "plate");
+            assert!((region.thickness - 0.8).abs() < 0.1, "{:?}", region);
+        }
+        let area = regions.iter().map(|region| region.area).sum::<f32>();
+        //Both faces of the plate are too thin
+        assert!(area > 20. * 20., "{}", area);
+    }
+
+    #[test]
+    fn walls_thinner_than_the_default_mesh_are_found()
+    {
+        let plate = solid("cube([20, 20, 0.3]);");
+        let regions = find_thin_regions("plate", &plate, 0.5);
+
+        assert!(!regions.is_empty());
+        for region in &regions
+        {
+            assert!((region.thickness - 0.3).abs() < 0.1, "{:?}", region);
+        }
+    }
+
+    #[test]
+    fn thick_walls_are_not_reported()
+    {
+        let block = solid("cube([20, 20, 5]);");
+        assert_eq!(find_thin_regions("block", &block, 1.2), vec!());
+    }
+
+    #[test]
+    fn only_the_thin_part_of_a_wall_is_reported()
+    {
+        //A 5 mm block with a 0.8 mm fin standing on top of it
+        let part = solid("union(){cube([20, 20, 5]); translate([0, 0, 5]) cube([20, 0.8, 10]);}");
+        let regions = find_thin_regions("part", &part, 1.2);
+
+        assert!(!regions.is_empty());
+        for region in &regions
+        {
+            assert!(region.position.z > 5., "{:?}", region);
+            assert!(region.position.y > -0.1 && region.position.y < 0.9, "{:?}", region);
+        }
+    }
+}